@@ -0,0 +1,51 @@
+//! Records the versions of the bundled `ruff` checkout for the `version` method.
+
+use std::path::Path;
+use std::process::Command;
+
+fn main() {
+    let ruff = Path::new("ruff");
+
+    let ty_version = package_version(&ruff.join("crates/ty/Cargo.toml"));
+    let ruff_version = package_version(&ruff.join("crates/ruff/Cargo.toml"));
+    let typeshed_rev =
+        std::fs::read_to_string(ruff.join("crates/ty_vendored/vendor/typeshed/source_commit.txt"))
+            .map(|rev| rev.trim().to_string())
+            .ok();
+    let ruff_commit = Command::new("git")
+        .args(["-C", "ruff", "rev-parse", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|commit| commit.trim().to_string());
+
+    for (name, value) in [
+        ("TY_TYPES_TY_VERSION", ty_version),
+        ("TY_TYPES_RUFF_VERSION", ruff_version),
+        ("TY_TYPES_RUFF_COMMIT", ruff_commit),
+        ("TY_TYPES_TYPESHED_REV", typeshed_rev),
+    ] {
+        let value = value.unwrap_or_else(|| "unknown".to_string());
+        println!("cargo:rustc-env={name}={value}");
+    }
+
+    println!("cargo:rerun-if-changed=ruff/crates/ty/Cargo.toml");
+    println!("cargo:rerun-if-changed=ruff/crates/ruff/Cargo.toml");
+    println!("cargo:rerun-if-changed=ruff/crates/ty_vendored/vendor/typeshed/source_commit.txt");
+    println!("cargo:rerun-if-changed=.git/modules/ruff/HEAD");
+}
+
+/// The `version = "..."` of a crate manifest's `[package]` table.
+fn package_version(manifest: &Path) -> Option<String> {
+    let manifest = std::fs::read_to_string(manifest).ok()?;
+    manifest
+        .lines()
+        .skip_while(|line| line.trim() != "[package]")
+        .take_while(|line| !line.starts_with('[') || line.trim() == "[package]")
+        .find_map(|line| {
+            let value = line.trim().strip_prefix("version")?.trim_start();
+            let value = value.strip_prefix('=')?.trim();
+            Some(value.trim_matches('"').to_string())
+        })
+}
@@ -4,11 +4,15 @@ use std::collections::HashMap;
 use ruff_python_ast::{
     self as ast, visitor::source_order, visitor::source_order::SourceOrderVisitor,
 };
-use ruff_text_size::Ranged;
+use ruff_text_size::{Ranged, TextRange, TextSize};
+use ty_python_semantic::semantic_index::semantic_index;
 use ty_python_semantic::types::call::CallArguments;
 use ty_python_semantic::types::constraints::ConstraintSetBuilder;
+use ty_python_semantic::types::function::KnownFunction;
 use ty_python_semantic::types::signatures::{ConcatenateTail, ParametersKind};
-use ty_python_semantic::types::{ParameterKind, Type, TypeContext};
+use ty_python_semantic::types::{
+    KnownInstanceType, ParameterKind, SubclassOfInner, Type, TypeContext, binding_type,
+};
 use ty_python_semantic::{Db, HasType, SemanticModel};
 
 use crate::protocol::{CallSignatureInfo, NodeAttribution, ParameterInfo, TypeDescriptor, TypeId};
@@ -25,31 +29,131 @@ pub fn collect_types<'db>(
     registry: &mut TypeRegistry<'db>,
 ) -> CollectionResult {
     let ast = ruff_db::parsed::parsed_module(db, file).load(db);
+    collect(db, file, registry, true, |collector| {
+        collector.visit_body(ast.suite());
+    })
+}
+
+/// Like `collect_types`, but only over the body of the innermost function or
+/// class definition containing `offset`, nested definitions included; its
+/// decorators, parameters, bases and return annotation are left out. Returns
+/// the definition's range with the result, or `None` if `offset` is outside
+/// any.
+pub fn collect_scope_types<'db>(
+    db: &'db dyn Db,
+    file: ruff_db::files::File,
+    registry: &mut TypeRegistry<'db>,
+    offset: TextSize,
+) -> Option<(TextRange, CollectionResult)> {
+    let ast = ruff_db::parsed::parsed_module(db, file).load(db);
+    let mut finder = ScopeFinder {
+        offset,
+        scope: None,
+    };
+    finder.visit_body(ast.suite());
+    let (range, body) = finder.scope?;
+
+    let result = collect(db, file, registry, true, |collector| {
+        collector.visit_body(body);
+    });
+    Some((range, result))
+}
+
+/// Like `collect_types`, but only registers types: no nodes or call signatures
+/// are built, so `nodes` is always empty.
+pub fn collect_types_only<'db>(
+    db: &'db dyn Db,
+    file: ruff_db::files::File,
+    registry: &mut TypeRegistry<'db>,
+) -> CollectionResult {
+    let ast = ruff_db::parsed::parsed_module(db, file).load(db);
+    collect(db, file, registry, false, |collector| {
+        collector.visit_body(ast.suite());
+    })
+}
+
+/// Like `collect_types`, or `collect_types_only` without `record_nodes`, but
+/// only returns the nodes: the types registered stay tracked in `registry`, so
+/// a caller collecting several files can drain them once.
+pub fn collect_nodes<'db>(
+    db: &'db dyn Db,
+    file: ruff_db::files::File,
+    registry: &mut TypeRegistry<'db>,
+    record_nodes: bool,
+) -> Vec<NodeAttribution> {
+    let ast = ruff_db::parsed::parsed_module(db, file).load(db);
+    visit_nodes(db, file, registry, record_nodes, |collector| {
+        collector.visit_body(ast.suite());
+    })
+}
 
+fn collect<'db>(
+    db: &'db dyn Db,
+    file: ruff_db::files::File,
+    registry: &mut TypeRegistry<'db>,
+    record_nodes: bool,
+    visit: impl FnOnce(&mut TypeCollector<'db, '_>),
+) -> CollectionResult {
     registry.start_tracking();
+    let nodes = visit_nodes(db, file, registry, record_nodes, visit);
+    CollectionResult {
+        nodes,
+        new_types: registry.drain_new_types(),
+    }
+}
 
+fn visit_nodes<'db>(
+    db: &'db dyn Db,
+    file: ruff_db::files::File,
+    registry: &mut TypeRegistry<'db>,
+    record_nodes: bool,
+    visit: impl FnOnce(&mut TypeCollector<'db, '_>),
+) -> Vec<NodeAttribution> {
     let mut collector = TypeCollector {
         model: SemanticModel::new(db, file),
         db,
+        file,
         registry,
         nodes: Vec::new(),
+        record_nodes,
+        in_annotation: false,
     };
 
-    collector.visit_body(ast.suite());
+    visit(&mut collector);
+    collector.nodes
+}
 
-    let new_types = collector.registry.drain_new_types();
+/// Finds the innermost function or class definition containing `offset`, as
+/// its range and body.
+struct ScopeFinder<'a> {
+    offset: TextSize,
+    scope: Option<(TextRange, &'a [ast::Stmt])>,
+}
 
-    CollectionResult {
-        nodes: collector.nodes,
-        new_types,
+impl<'a> SourceOrderVisitor<'a> for ScopeFinder<'a> {
+    fn visit_stmt(&mut self, stmt: &'a ast::Stmt) {
+        if !stmt.range().contains(self.offset) {
+            return;
+        }
+        match stmt {
+            ast::Stmt::FunctionDef(function) => self.scope = Some((stmt.range(), &function.body)),
+            ast::Stmt::ClassDef(class) => self.scope = Some((stmt.range(), &class.body)),
+            _ => {}
+        }
+        source_order::walk_stmt(self, stmt);
     }
 }
 
 struct TypeCollector<'db, 'reg> {
     model: SemanticModel<'db>,
     db: &'db dyn Db,
+    file: ruff_db::files::File,
     registry: &'reg mut TypeRegistry<'db>,
     nodes: Vec<NodeAttribution>,
+    /// When false, types are registered but no nodes are recorded.
+    record_nodes: bool,
+    /// Whether the expressions being visited are part of an annotation.
+    in_annotation: bool,
 }
 
 impl<'db, 'reg> TypeCollector<'db, 'reg> {
@@ -59,30 +163,128 @@ impl<'db, 'reg> TypeCollector<'db, 'reg> {
         range: ruff_text_size::TextRange,
         type_id: Option<TypeId>,
     ) {
+        if !self.record_nodes {
+            return;
+        }
+        let symbol_kind = match node_kind {
+            "ExprName" | "Alias" => type_id
+                .and_then(|id| self.registry.get_descriptor(id))
+                .map(TypeDescriptor::symbol_kind),
+            _ => None,
+        };
+        let inferred = self.is_inferred(type_id);
         self.nodes.push(NodeAttribution {
             start: range.start().into(),
             end: range.end().into(),
             node_kind: Cow::Borrowed(node_kind),
             type_id,
             call_signature: None,
+            symbol_kind,
+            inferred,
+            assertion_holds: None,
+            revealed_type_id: None,
         });
     }
 
-    fn record_call_node(
-        &mut self,
-        range: ruff_text_size::TextRange,
-        type_id: Option<TypeId>,
-        call_signature: Option<CallSignatureInfo>,
-    ) {
+    /// Whether `type_id` is anything but ty's `Unknown` fallback.
+    fn is_inferred(&self, type_id: Option<TypeId>) -> bool {
+        !matches!(
+            type_id.and_then(|id| self.registry.get_descriptor(id)),
+            Some(TypeDescriptor::Dynamic { dynamic_kind, .. }) if dynamic_kind == "Unknown"
+        )
+    }
+
+    fn record_call_node(&mut self, call_expr: &ast::ExprCall, type_id: Option<TypeId>) {
+        if !self.record_nodes {
+            return;
+        }
+        let call_signature = self.build_call_signature(call_expr);
+        let range = call_expr.range();
+        let inferred = self.is_inferred(type_id);
+        let assertion_holds = self.assert_type_holds(call_expr);
+        let revealed_type_id = self.revealed_type_id(call_expr);
         self.nodes.push(NodeAttribution {
             start: range.start().into(),
             end: range.end().into(),
             node_kind: Cow::Borrowed("ExprCall"),
             type_id,
             call_signature,
+            symbol_kind: None,
+            inferred,
+            assertion_holds,
+            revealed_type_id,
         });
     }
 
+    /// Whether `call_expr` calls the known function `known`.
+    fn calls_known(&self, call_expr: &ast::ExprCall, known: KnownFunction) -> bool {
+        matches!(
+            call_expr.func.inferred_type(&self.model),
+            Some(Type::FunctionLiteral(function)) if function.is_known(self.db, known)
+        )
+    }
+
+    /// For an `assert_type(value, T)` call, whether the type of `value` is
+    /// equivalent to the type expression `T`, as ty requires.
+    fn assert_type_holds(&self, call_expr: &ast::ExprCall) -> Option<bool> {
+        if !self.calls_known(call_expr, KnownFunction::AssertType) {
+            return None;
+        }
+        let [value, asserted] = &*call_expr.arguments.args else {
+            return None;
+        };
+        let actual = value.inferred_type(&self.model)?;
+        // ty infers the second argument as a type expression.
+        let asserted = asserted.inferred_type(&self.model)?;
+        Some(actual.is_equivalent_to(self.db, asserted))
+    }
+
+    /// For a `reveal_type(value)` call, the ID of the type ty reveals: that
+    /// of `value`.
+    fn revealed_type_id(&mut self, call_expr: &ast::ExprCall) -> Option<TypeId> {
+        if !self.calls_known(call_expr, KnownFunction::RevealType) {
+            return None;
+        }
+        let [value] = &*call_expr.arguments.args else {
+            return None;
+        };
+        let revealed = value.inferred_type(&self.model)?;
+        Some(self.register_type(revealed))
+    }
+
+    /// In a type expression, `list[int]` denotes the specialized class itself
+    /// rather than an instance of it, so report the generic alias. Subscripts
+    /// of special forms, such as `Annotated[int, ...]` or `Final[int]`, denote
+    /// the type they wrap and are left alone.
+    fn annotation_subscript_type(
+        &self,
+        subscript: &ast::ExprSubscript,
+        ty: Type<'db>,
+    ) -> Type<'db> {
+        let generic_value = matches!(
+            subscript.value.inferred_type(&self.model),
+            Some(Type::ClassLiteral(class)) if class.generic_context(self.db).is_some()
+        );
+        match ty {
+            Type::NominalInstance(instance) if generic_value => Type::from(instance.class(self.db)),
+            _ => ty,
+        }
+    }
+
+    /// The alias declared by a PEP 695 `type` statement.
+    ///
+    /// The name binds a `TypeAliasType` instance; report the alias itself so the
+    /// node resolves to a `typeAlias` descriptor with its value and type parameters.
+    fn type_alias_type(&self, type_alias: &ast::StmtTypeAlias) -> Option<Type<'db>> {
+        let definition = semantic_index(self.db, self.file).expect_single_definition(type_alias);
+        match binding_type(self.db, definition) {
+            Type::KnownInstance(KnownInstanceType::TypeAliasType(alias)) => {
+                Some(Type::TypeAlias(alias))
+            }
+            _ => None,
+        }
+    }
+
     fn register_type(&mut self, ty: ty_python_semantic::types::Type<'db>) -> TypeId {
         self.registry.register(ty, self.db).type_id
     }
@@ -92,6 +294,22 @@ impl<'db, 'reg> TypeCollector<'db, 'reg> {
 
         // Get the callable type from the function expression
         let func_type = call_expr.func.inferred_type(&self.model)?;
+
+        // Calling `cls: type[T]` runs the constructor of T's upper bound (or
+        // `object`), but produces a `T`.
+        let (func_type, constructed_typevar) = match func_type {
+            Type::SubclassOf(subclass_of) => match subclass_of.subclass_of() {
+                SubclassOfInner::TypeVar(bound_tv) => {
+                    let bound = bound_tv
+                        .typevar(db)
+                        .upper_bound(db)
+                        .unwrap_or_else(Type::object);
+                    (bound.to_meta_type(db), Some(Type::TypeVar(bound_tv)))
+                }
+                _ => (func_type, None),
+            },
+            _ => (func_type, None),
+        };
         let callable_type = func_type.try_upcast_to_callable(db)?.into_type(db);
 
         // Build typed arguments so check_types can infer TypeVar specializations
@@ -115,23 +333,35 @@ impl<'db, 'reg> TypeCollector<'db, 'reg> {
             &[],
         );
 
-        // Pick the first matching overload (fallback to first overload)
-        let binding = bindings.iter_flat().flatten().next()?;
+        // Pick the first matching overload, falling back to the first overload
+        // when none matches. The index is only reported for overloaded callees.
+        let callable_binding = bindings.iter_flat().next()?;
+        let (matched_index, binding) = match callable_binding.matching_overloads().next() {
+            Some((index, binding)) => (Some(index), binding),
+            None => (None, callable_binding.overloads().first()?),
+        };
+        let matched = matched_index.is_some();
+        let matched_overload = if callable_binding.overloads().len() > 1 {
+            matched_index
+        } else {
+            None
+        };
 
         let specialization = binding.specialization();
 
         // Compute the specialized return type from the binding
-        let return_type_id = Some(self.register_type(binding.return_type()));
+        let return_type = constructed_typevar.unwrap_or_else(|| binding.return_type());
+        let return_type_id = Some(self.register_type(return_type));
 
         // Extract parameters from the binding's signature
-        let (in_concatenate, param_spec_name) = match binding.signature.parameters().kind() {
-            ParametersKind::ParamSpec(tv) => (false, Some(tv.name(db).to_string())),
-            ParametersKind::Concatenate(ConcatenateTail::ParamSpec(tv)) => {
-                (true, Some(tv.name(db).to_string()))
-            }
+        let (in_concatenate, param_spec) = match binding.signature.parameters().kind() {
+            ParametersKind::ParamSpec(tv) => (false, Some(tv)),
+            ParametersKind::Concatenate(ConcatenateTail::ParamSpec(tv)) => (true, Some(tv)),
             ParametersKind::Concatenate(ConcatenateTail::Gradual) => (true, None),
             _ => (false, None),
         };
+        let param_spec_name = param_spec.map(|tv| tv.name(db).to_string());
+        let param_spec_id = param_spec.map(|tv| self.register_type(Type::TypeVar(tv)));
 
         let parameters: Vec<ParameterInfo> = binding
             .signature
@@ -139,6 +369,7 @@ impl<'db, 'reg> TypeCollector<'db, 'reg> {
             .iter()
             .map(|param| {
                 let mut ty = param.annotated_type();
+                let annotated = !ty.is_unknown();
                 if let Some(spec) = specialization {
                     ty = ty.apply_specialization(db, spec);
                 }
@@ -162,10 +393,10 @@ impl<'db, 'reg> TypeCollector<'db, 'reg> {
 
                 let is_variadic = param.is_variadic() || param.is_keyword_variadic();
                 let concatenate_prefix = in_concatenate && !is_variadic;
-                let this_param_spec_name = if is_variadic {
-                    param_spec_name.clone()
+                let (this_param_spec_name, this_param_spec_id) = if is_variadic {
+                    (param_spec_name.clone(), param_spec_id)
                 } else {
-                    None
+                    (None, None)
                 };
 
                 ParameterInfo {
@@ -175,10 +406,15 @@ impl<'db, 'reg> TypeCollector<'db, 'reg> {
                         .unwrap_or_default(),
                     type_id,
                     kind,
+                    annotated,
                     has_default,
                     default_type_id,
                     concatenate_prefix,
                     param_spec_name: this_param_spec_name,
+                    param_spec_id: this_param_spec_id,
+                    narrowed: false,
+                    start: None,
+                    end: None,
                 }
             })
             .collect();
@@ -197,6 +433,8 @@ impl<'db, 'reg> TypeCollector<'db, 'reg> {
             parameters,
             return_type_id,
             type_arguments,
+            matched_overload,
+            matched,
         })
     }
 
@@ -208,6 +446,16 @@ impl<'db, 'reg> TypeCollector<'db, 'reg> {
                     self.visit_target(element);
                 }
             }
+            // `*rest` takes the type of the name it binds: a `list` of the
+            // remaining elements.
+            ast::Expr::Starred(starred) => {
+                let type_id = starred
+                    .value
+                    .inferred_type(&self.model)
+                    .map(|ty| self.register_type(ty));
+                self.record_node("ExprStarred", starred.range(), type_id);
+                self.visit_target(&starred.value);
+            }
             _ => self.visit_expr(target),
         }
     }
@@ -232,6 +480,18 @@ impl SourceOrderVisitor<'_> for TypeCollector<'_, '_> {
                     self.record_node("StmtClassDef", class.range(), None);
                 }
             }
+            ast::Stmt::TypeAlias(type_alias) => {
+                let type_id = self
+                    .type_alias_type(type_alias)
+                    .map(|ty| self.register_type(ty));
+                self.record_node("StmtTypeAlias", type_alias.range(), type_id);
+                self.record_node("ExprName", type_alias.name.range(), type_id);
+                if let Some(type_params) = &type_alias.type_params {
+                    self.visit_type_params(type_params);
+                }
+                self.visit_expr(&type_alias.value);
+                return;
+            }
             ast::Stmt::Assign(assign) => {
                 self.record_node("StmtAssign", assign.range(), None);
                 for target in &assign.targets {
@@ -265,21 +525,32 @@ impl SourceOrderVisitor<'_> for TypeCollector<'_, '_> {
         source_order::walk_stmt(self, stmt);
     }
 
+    fn visit_annotation(&mut self, expr: &ast::Expr) {
+        let was_in_annotation = std::mem::replace(&mut self.in_annotation, true);
+        source_order::walk_annotation(self, expr);
+        self.in_annotation = was_in_annotation;
+    }
+
     fn visit_expr(&mut self, expr: &ast::Expr) {
         let node_kind = expr_kind_name(expr);
 
-        if let Some(ty) = expr.inferred_type(&self.model) {
+        let mut ty = expr.inferred_type(&self.model);
+        if self.in_annotation
+            && let ast::Expr::Subscript(subscript) = expr
+        {
+            ty = ty.map(|ty| self.annotation_subscript_type(subscript, ty));
+        }
+
+        if let Some(ty) = ty {
             let type_id = self.register_type(ty);
 
             if let ast::Expr::Call(call_expr) = expr {
-                let call_sig = self.build_call_signature(call_expr);
-                self.record_call_node(expr.range(), Some(type_id), call_sig);
+                self.record_call_node(call_expr, Some(type_id));
             } else {
                 self.record_node(node_kind, expr.range(), Some(type_id));
             }
         } else if let ast::Expr::Call(call_expr) = expr {
-            let call_sig = self.build_call_signature(call_expr);
-            self.record_call_node(expr.range(), None, call_sig);
+            self.record_call_node(call_expr, None);
         } else {
             self.record_node(node_kind, expr.range(), None);
         }
@@ -321,6 +592,25 @@ impl SourceOrderVisitor<'_> for TypeCollector<'_, '_> {
         source_order::walk_parameter_with_default(self, parameter_with_default);
     }
 
+    fn visit_except_handler(&mut self, except_handler: &ast::ExceptHandler) {
+        let ast::ExceptHandler::ExceptHandler(handler) = except_handler;
+
+        // Only `except E as e` creates a binding; its type is the caught exception.
+        let ty = if handler.name.is_some() {
+            handler.inferred_type(&self.model)
+        } else {
+            None
+        };
+        if let Some(ty) = ty {
+            let type_id = self.register_type(ty);
+            self.record_node("ExceptHandler", handler.range(), Some(type_id));
+        } else {
+            self.record_node("ExceptHandler", handler.range(), None);
+        }
+
+        source_order::walk_except_handler(self, except_handler);
+    }
+
     fn visit_alias(&mut self, alias: &ast::Alias) {
         if let Some(ty) = alias.inferred_type(&self.model) {
             let type_id = self.register_type(ty);
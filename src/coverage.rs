@@ -0,0 +1,71 @@
+use crate::protocol::{CoverageCount, GetCoverageResult, NodeAttribution, TypeDescriptor};
+use crate::registry::TypeRegistry;
+
+/// Summarize how much of a file ty can type, from its collected nodes.
+///
+/// - `expressions`: `Expr*` nodes whose type is known, i.e. not `dynamic`
+///   (`Unknown`, `Any`, ...).
+/// - `parameters`: parameters of the file's `def`s that carry an annotation.
+///   Receivers ty types without one (an unannotated `self` / `cls`) are skipped.
+/// - `returns`: `def`s whose return type is known.
+pub fn coverage(nodes: &[NodeAttribution], registry: &TypeRegistry<'_>) -> GetCoverageResult {
+    let descriptor =
+        |node: &NodeAttribution| node.type_id.and_then(|id| registry.get_descriptor(id));
+
+    let mut expressions = Counter::default();
+    let mut parameters = Counter::default();
+    let mut returns = Counter::default();
+
+    for node in nodes {
+        if node.node_kind.starts_with("Expr") {
+            let known = descriptor(node).is_some_and(|d| d.kind() != "dynamic");
+            expressions.add(known);
+        } else if node.node_kind == "StmtFunctionDef"
+            && let Some(TypeDescriptor::Function {
+                parameters: params,
+                return_type,
+                ..
+            }) = descriptor(node)
+        {
+            for param in params {
+                if param.annotated || param.type_id.is_none() {
+                    parameters.add(param.annotated);
+                }
+            }
+            returns.add(return_type.is_some());
+        }
+    }
+
+    GetCoverageResult {
+        expressions: expressions.finish(),
+        parameters: parameters.finish(),
+        returns: returns.finish(),
+    }
+}
+
+#[derive(Default)]
+struct Counter {
+    covered: u32,
+    total: u32,
+}
+
+impl Counter {
+    fn add(&mut self, covered: bool) {
+        self.covered += u32::from(covered);
+        self.total += 1;
+    }
+
+    fn finish(self) -> CoverageCount {
+        // Nothing to cover counts as fully covered.
+        let percent = if self.total == 0 {
+            100.0
+        } else {
+            f64::from(self.covered) * 100.0 / f64::from(self.total)
+        };
+        CoverageCount {
+            covered: self.covered,
+            total: self.total,
+            percent,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use ruff_db::diagnostic::Severity;
+use ruff_db::files::File;
+use ty_python_semantic::Db;
+use ty_python_semantic::types::check_types;
+
+use crate::position::OffsetEncoder;
+use crate::protocol::{DiagnosticInfo, DiagnosticRange};
+
+/// Run ty's checker on `file` and report its diagnostics, ordered by position.
+/// Offsets are converted with `encoder`, like those of `NodeAttribution`s.
+pub fn diagnostics(db: &dyn Db, file: File, encoder: &OffsetEncoder) -> Vec<DiagnosticInfo> {
+    let mut diagnostics: Vec<DiagnosticInfo> = check_types(db, file)
+        .iter()
+        .map(|diagnostic| {
+            let range = diagnostic
+                .primary_span()
+                .and_then(|span| span.range())
+                .map(|range| DiagnosticRange {
+                    start: encoder.encode(range.start().into()),
+                    end: encoder.encode(range.end().into()),
+                });
+            DiagnosticInfo {
+                range,
+                severity: match diagnostic.severity() {
+                    Severity::Info => "info",
+                    Severity::Warning => "warning",
+                    Severity::Error | Severity::Fatal => "error",
+                },
+                code: diagnostic.id().as_str().to_string(),
+                message: diagnostic.concise_message().to_string(),
+            }
+        })
+        .collect();
+    diagnostics.sort_by_key(|d| d.range.as_ref().map(|r| (r.start, r.end)));
+    diagnostics
+}
@@ -0,0 +1,187 @@
+use std::io::{self, BufRead, Read, Write};
+
+/// How JSON-RPC messages are delimited on the server's input and output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Framing {
+    /// One message per line.
+    #[default]
+    Line,
+    /// LSP base protocol: `Content-Length: N` and optional other headers, a
+    /// blank line, then exactly `N` bytes of JSON.
+    Headers,
+}
+
+impl Framing {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "line" => Some(Self::Line),
+            "headers" => Some(Self::Headers),
+            _ => None,
+        }
+    }
+}
+
+/// A message that could not be read from its framing. The reader has already
+/// skipped past it to the next message.
+#[derive(Debug)]
+pub struct MalformedMessage(String);
+
+impl std::fmt::Display for MalformedMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Reads framed messages from `input`.
+pub struct MessageReader<R> {
+    input: R,
+    framing: Framing,
+}
+
+impl<R: BufRead> MessageReader<R> {
+    pub fn new(input: R, framing: Framing) -> Self {
+        Self { input, framing }
+    }
+
+    /// Read the next message body, or the reason it is malformed. Returns
+    /// `None` at the end of input, or after logging a read error.
+    pub fn next_message(&mut self) -> Option<Result<String, MalformedMessage>> {
+        let message = match self.framing {
+            Framing::Line => self.read_line(),
+            Framing::Headers => self.read_framed(),
+        };
+        message.unwrap_or_else(|e| {
+            eprintln!("Error reading input: {e}");
+            None
+        })
+    }
+
+    /// The next non-blank line, without its line ending.
+    fn read_line(&mut self) -> io::Result<Option<Result<String, MalformedMessage>>> {
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.input.read_until(b'\n', &mut line)? == 0 {
+                return Ok(None);
+            }
+            let Ok(text) = std::str::from_utf8(&line) else {
+                return Ok(Some(Err(MalformedMessage(
+                    "message is not valid UTF-8".to_string(),
+                ))));
+            };
+            let text = text.trim_end_matches(['\r', '\n']);
+            if !text.trim().is_empty() {
+                return Ok(Some(Ok(text.to_string())));
+            }
+        }
+    }
+
+    fn read_framed(&mut self) -> io::Result<Option<Result<String, MalformedMessage>>> {
+        let mut content_length = None;
+        let length = loop {
+            let Some(mut header) = self.read_header()? else {
+                return Ok(None);
+            };
+            if header.is_empty() {
+                // Blank lines before the headers are skipped, like between
+                // line-delimited messages.
+                if let Some(length) = content_length {
+                    break length;
+                }
+                continue;
+            }
+            // After a malformed message, its unread body runs into the next
+            // message's first header.
+            if content_length.is_none()
+                && let Some(at) = header.find("Content-Length:").filter(|&at| at > 0)
+            {
+                header.drain(..at);
+            }
+            match parse_header(&header) {
+                Ok(Some(length)) => content_length = Some(length),
+                // Other headers, such as `Content-Type`, are accepted and ignored.
+                Ok(None) => {}
+                Err(reason) => {
+                    self.skip_message(content_length)?;
+                    return Ok(Some(Err(MalformedMessage(reason))));
+                }
+            }
+        };
+
+        let mut body = vec![0; length];
+        self.input.read_exact(&mut body)?;
+        Ok(Some(String::from_utf8(body).map_err(|_| {
+            MalformedMessage("message body is not valid UTF-8".to_string())
+        })))
+    }
+
+    /// Skip the rest of a malformed message's headers, up to the blank line
+    /// that ends them, and its body if its length is known.
+    fn skip_message(&mut self, content_length: Option<usize>) -> io::Result<()> {
+        while let Some(header) = self.read_header()? {
+            if header.is_empty() {
+                if let Some(length) = content_length {
+                    io::copy(&mut (&mut self.input).take(length as u64), &mut io::sink())?;
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// The next header line, without its line ending; empty for the blank
+    /// line that ends the headers.
+    fn read_header(&mut self) -> io::Result<Option<String>> {
+        let mut line = Vec::new();
+        if self.input.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = String::from_utf8_lossy(&line);
+        Ok(Some(line.trim_end_matches(['\r', '\n']).to_string()))
+    }
+}
+
+/// Parse a header line: the length for `Content-Length`, `None` for any
+/// other header, or why the line is not a header.
+fn parse_header(header: &str) -> Result<Option<usize>, String> {
+    let malformed = || format!("malformed header '{header}'");
+    let (name, value) = header.split_once(':').ok_or_else(malformed)?;
+    let name = name.trim();
+    if name.is_empty() || !name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+        return Err(malformed());
+    }
+    if !name.eq_ignore_ascii_case("Content-Length") {
+        return Ok(None);
+    }
+    let value = value.trim();
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| format!("invalid Content-Length '{value}'"))
+}
+
+/// Writes framed messages to stdout.
+pub struct MessageWriter {
+    stdout: io::Stdout,
+    framing: Framing,
+}
+
+impl MessageWriter {
+    pub fn new(framing: Framing) -> Self {
+        Self {
+            stdout: io::stdout(),
+            framing,
+        }
+    }
+
+    /// Write one message body, framed, and flush it.
+    pub fn write(&self, body: &[u8]) {
+        let mut out = self.stdout.lock();
+        let _ = match self.framing {
+            Framing::Line => out.write_all(body).and_then(|()| out.write_all(b"\n")),
+            Framing::Headers => write!(out, "Content-Length: {}\r\n\r\n", body.len())
+                .and_then(|()| out.write_all(body)),
+        };
+        let _ = out.flush();
+    }
+}
@@ -0,0 +1,79 @@
+use ruff_db::files::File;
+use ruff_python_ast::{
+    self as ast, visitor::source_order, visitor::source_order::SourceOrderVisitor,
+};
+use ty_python_semantic::types::{ClassBase, ClassLiteral, Type};
+use ty_python_semantic::{Db, HasType, SemanticModel};
+
+use crate::protocol::TypeId;
+use crate::registry::TypeRegistry;
+
+/// The ancestors and known descendants of a class.
+pub struct ClassHierarchy {
+    /// The class entries of the MRO, excluding the class itself.
+    pub ancestors: Vec<TypeId>,
+    /// Classes defined in `files` that inherit from the class, directly or not.
+    pub descendants: Vec<TypeId>,
+}
+
+pub fn class_hierarchy<'db>(
+    db: &'db dyn Db,
+    class: ClassLiteral<'db>,
+    files: impl IntoIterator<Item = File>,
+    registry: &mut TypeRegistry<'db>,
+) -> ClassHierarchy {
+    let class_type = class.default_specialization(db);
+
+    let ancestors = class_type
+        .iter_mro(db)
+        .skip(1)
+        .filter_map(|base| match base {
+            ClassBase::Class(ancestor) => Some(registry.register_component(ancestor.into(), db)),
+            _ => None,
+        })
+        .collect();
+
+    let mut descendants: Vec<TypeId> = Vec::new();
+    for file in files {
+        let ast = ruff_db::parsed::parsed_module(db, file).load(db);
+        let mut finder = ClassFinder {
+            model: SemanticModel::new(db, file),
+            classes: Vec::new(),
+        };
+        finder.visit_body(ast.suite());
+
+        for candidate in finder.classes {
+            if candidate != class
+                && candidate
+                    .default_specialization(db)
+                    .is_subclass_of(db, class_type)
+            {
+                descendants.push(registry.register_component(Type::ClassLiteral(candidate), db));
+            }
+        }
+    }
+    descendants.sort_unstable();
+    descendants.dedup();
+
+    ClassHierarchy {
+        ancestors,
+        descendants,
+    }
+}
+
+/// Collects every class defined in a file, including nested ones.
+struct ClassFinder<'db> {
+    model: SemanticModel<'db>,
+    classes: Vec<ClassLiteral<'db>>,
+}
+
+impl SourceOrderVisitor<'_> for ClassFinder<'_> {
+    fn visit_stmt(&mut self, stmt: &ast::Stmt) {
+        if let ast::Stmt::ClassDef(class_def) = stmt
+            && let Some(Type::ClassLiteral(class)) = class_def.inferred_type(&self.model)
+        {
+            self.classes.push(class);
+        }
+        source_order::walk_stmt(self, stmt);
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::{BTreeSet, HashSet};
+
+use ruff_db::files::File;
+use ruff_db::system::SystemPath;
+use ruff_python_ast::{
+    self as ast, visitor::source_order, visitor::source_order::SourceOrderVisitor,
+};
+use ruff_text_size::Ranged;
+use ty_module_resolver::{ModuleName, resolve_module};
+use ty_python_semantic::Db;
+
+use crate::protocol::{GetDependenciesResult, ImportInfo};
+
+/// Whether `module_name`, resolved from `file`, is a standard-library module
+/// (from the vendored or a custom typeshed).
+pub fn is_stdlib_module(db: &dyn Db, file: File, module_name: &str) -> bool {
+    ModuleName::new(module_name)
+        .and_then(|name| resolve_module(db, file, &name))
+        .and_then(|module| {
+            module
+                .search_path(db)
+                .map(|path| path.is_standard_library())
+        })
+        .unwrap_or(false)
+}
+
+/// Follow the imports of `file` for up to `depth` levels (`None` for no
+/// limit), through the project files it reaches.
+///
+/// Project files are those under `project_root` that aren't part of the
+/// standard library; only they are followed. `from pkg import sub` depends on
+/// `pkg` itself, not on a `pkg.sub` submodule.
+pub fn dependencies(
+    db: &dyn Db,
+    file: File,
+    project_root: &SystemPath,
+    depth: Option<usize>,
+) -> GetDependenciesResult {
+    let mut files = vec![file.path(db).as_str().to_string()];
+    let mut external = BTreeSet::new();
+    let mut unresolved = BTreeSet::new();
+
+    let mut seen = HashSet::from([file]);
+    let mut frontier = vec![file];
+    let mut level = 0;
+    while !frontier.is_empty() && depth.is_none_or(|depth| level < depth) {
+        level += 1;
+        let mut next = Vec::new();
+        for importer in frontier {
+            for import in collect_imports(db, importer) {
+                let Some(module) = ModuleName::new(&import.module)
+                    .and_then(|name| resolve_module(db, importer, &name))
+                else {
+                    unresolved.insert(import.module);
+                    continue;
+                };
+                let is_stdlib = module
+                    .search_path(db)
+                    .is_some_and(|path| path.is_standard_library());
+                let project_file = module.file(db).filter(|module_file| {
+                    !is_stdlib
+                        && module_file
+                            .path(db)
+                            .as_system_path()
+                            .is_some_and(|path| path.starts_with(project_root))
+                });
+                match project_file {
+                    Some(module_file) => {
+                        if seen.insert(module_file) {
+                            files.push(module_file.path(db).as_str().to_string());
+                            next.push(module_file);
+                        }
+                    }
+                    None => {
+                        external.insert(import.module);
+                    }
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    files[1..].sort_unstable();
+    GetDependenciesResult {
+        files,
+        external: external.into_iter().collect(),
+        unresolved: unresolved.into_iter().collect(),
+    }
+}
+
+/// Collect every `import` / `from ... import` statement in a file, anywhere in
+/// its body, together with the module each one resolves to.
+pub fn collect_imports(db: &dyn Db, file: File) -> Vec<ImportInfo> {
+    let ast = ruff_db::parsed::parsed_module(db, file).load(db);
+
+    let mut collector = ImportCollector {
+        db,
+        file,
+        imports: Vec::new(),
+    };
+    collector.visit_body(ast.suite());
+    collector.imports
+}
+
+struct ImportCollector<'db> {
+    db: &'db dyn Db,
+    file: File,
+    imports: Vec<ImportInfo>,
+}
+
+impl ImportCollector<'_> {
+    fn push(
+        &mut self,
+        stmt: &impl Ranged,
+        written: String,
+        module_name: Option<ModuleName>,
+        names: Vec<String>,
+    ) {
+        let module = module_name
+            .as_ref()
+            .and_then(|name| resolve_module(self.db, self.file, name));
+        let resolved_path = module
+            .as_ref()
+            .and_then(|m| m.file(self.db))
+            .map(|f| f.path(self.db).as_str().to_string());
+
+        self.imports.push(ImportInfo {
+            start: stmt.start().into(),
+            end: stmt.end().into(),
+            module: module_name.map_or(written, |name| name.to_string()),
+            resolved_path,
+            names,
+            unresolved: module.is_none(),
+        });
+    }
+}
+
+impl SourceOrderVisitor<'_> for ImportCollector<'_> {
+    fn visit_stmt(&mut self, stmt: &ast::Stmt) {
+        match stmt {
+            ast::Stmt::Import(import) => {
+                for alias in &import.names {
+                    let module_name = ModuleName::new(alias.name.as_str());
+                    self.push(import, alias.name.to_string(), module_name, vec![]);
+                }
+            }
+            ast::Stmt::ImportFrom(import_from) => {
+                let written = format!(
+                    "{}{}",
+                    ".".repeat(import_from.level as usize),
+                    import_from.module.as_ref().map_or("", |m| m.as_str())
+                );
+                let module_name =
+                    ModuleName::from_import_statement(self.db, self.file, import_from).ok();
+                let names = import_from
+                    .names
+                    .iter()
+                    .map(|alias| alias.name.to_string())
+                    .collect();
+                self.push(import_from, written, module_name, names);
+            }
+            _ => source_order::walk_stmt(self, stmt),
+        }
+    }
+}
@@ -1,33 +1,103 @@
 #![allow(dead_code)]
 
 mod collector;
+mod coverage;
+mod diagnostics;
+mod framing;
+mod hierarchy;
+mod imports;
+mod overlay;
+mod pool;
+mod position;
 mod project;
 mod protocol;
 mod registry;
+mod tokens;
 
-use std::io::{self, BufRead, Write};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io::{self, BufRead, Read, Write};
+use std::panic::AssertUnwindSafe;
 use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
 
+use framing::{Framing, MalformedMessage, MessageReader, MessageWriter};
+use pool::WorkerPool;
+use position::{OffsetEncoder, PositionEncoding};
+use project::EnvironmentError;
 use protocol::{
-    CliResult, GetTypeRegistryResult, GetTypesParams, GetTypesResult, InitializeParams,
-    InitializeResult, JsonRpcRequest, JsonRpcResponse,
+    BatchFileEntry, BatchRequest, BatchResult, CliResult, DumpRegistryGraphResult, ErrorCode,
+    FileParams, GetClassHierarchyParams, GetClassHierarchyResult, GetCoverageParams,
+    GetDependenciesParams, GetDiagnosticsParams, GetDiagnosticsResult, GetImportsParams,
+    GetImportsResult, GetParentsParams, GetParentsResult, GetRawTypeParams, GetRawTypeResult,
+    GetTokenTypesParams, GetTokenTypesResult, GetTypeAtPositionParams, GetTypeAtPositionResult,
+    GetTypeByDisplayParams, GetTypeByDisplayResult, GetTypeRegistryParams, GetTypeRegistryResult,
+    GetTypesBatchParams, GetTypesBatchResult, GetTypesForScopeParams, GetTypesForScopeResult,
+    GetTypesParams, GetTypesResult, HealthcheckResult, InitializeParams, InitializeResult,
+    JsonRpcNotification, JsonRpcRequest, JsonRpcResponse, ListFilesResult, NodeAttribution,
+    NodeEncoding, NodeList, SubscribeParams, TypeDescriptor, TypeId, TypeOptions,
+    UnionDisplayStyle, VersionResult,
 };
 use registry::TypeRegistry;
-use ruff_db::files::system_path_to_file;
+use ruff_db::files::{File, system_path_to_file};
+use ruff_db::source::{line_index, source_text};
 use ruff_db::system::{SystemPath, SystemPathBuf};
-use ty_project::ProjectDatabase;
+use serde::de::DeserializeOwned;
+use ty_project::{Db as _, ProjectDatabase};
+use ty_python_semantic::types::{Type, check_types};
 
 fn main() {
     let args: Vec<String> = std::env::args().skip(1).collect();
 
     let mut serve = false;
+    let mut replay: Option<String> = None;
+    let mut include_display = true;
+    let mut pretty = false;
+    let mut json = true;
+    let mut emit_empty = true;
+    let mut validate = false;
+    let mut workers: usize = 0;
+    let mut debug = false;
+    let mut framing: Option<Framing> = None;
+    let mut threads: Option<usize> = None;
+    let mut max_file_size: Option<usize> = None;
+    let mut benchmark: Option<usize> = None;
     let mut project_root: Option<String> = None;
+    let mut extra_paths: Vec<String> = Vec::new();
     let mut file_paths: Vec<String> = Vec::new();
+    let mut requests: Option<String> = None;
+    let mut stdin = false;
+    let mut as_path: Option<String> = None;
 
     let mut i = 0;
     while i < args.len() {
         match args[i].as_str() {
             "--serve" => serve = true,
+            "--version" => {
+                let v = VersionResult::current();
+                println!(
+                    "ty-types {} (ty {}, ruff {} {}, typeshed {})",
+                    v.version, v.ty_version, v.ruff_version, v.ruff_commit, v.typeshed_revision
+                );
+                return;
+            }
+            "--no-display" => include_display = false,
+            "--pretty-errors" => pretty = true,
+            "--no-json" => json = false,
+            "--emit-empty" => emit_empty = true,
+            "--no-emit-empty" => emit_empty = false,
+            "--validate" => validate = true,
+            "--debug" => debug = true,
+            "--stdin" => stdin = true,
+            "--as" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --as requires a value");
+                    process::exit(1);
+                }
+                as_path = Some(args[i].clone());
+            }
             "--project-root" => {
                 i += 1;
                 if i >= args.len() {
@@ -36,6 +106,68 @@ fn main() {
                 }
                 project_root = Some(args[i].clone());
             }
+            "--extra-path" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --extra-path requires a value");
+                    process::exit(1);
+                }
+                extra_paths.push(args[i].clone());
+            }
+            "--replay" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --replay requires a value");
+                    process::exit(1);
+                }
+                replay = Some(args[i].clone());
+            }
+            "--requests" => {
+                i += 1;
+                if i >= args.len() {
+                    eprintln!("Error: --requests requires a value");
+                    process::exit(1);
+                }
+                requests = Some(args[i].clone());
+            }
+            "--workers" => {
+                i += 1;
+                workers = args.get(i).and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                    eprintln!("Error: --workers requires a non-negative integer");
+                    process::exit(1);
+                });
+            }
+            "--threads" => {
+                i += 1;
+                let n = args.get(i).and_then(|n| n.parse().ok()).filter(|&n| n > 0);
+                threads = Some(n.unwrap_or_else(|| {
+                    eprintln!("Error: --threads requires a positive integer");
+                    process::exit(1);
+                }));
+            }
+            "--benchmark" => {
+                i += 1;
+                let n = args.get(i).and_then(|n| n.parse().ok()).filter(|&n| n > 0);
+                benchmark = Some(n.unwrap_or_else(|| {
+                    eprintln!("Error: --benchmark requires a positive integer");
+                    process::exit(1);
+                }));
+            }
+            arg if arg.starts_with("--framing=") => {
+                let mode = arg.strip_prefix("--framing=").unwrap_or_default();
+                framing = Some(Framing::parse(mode).unwrap_or_else(|| {
+                    eprintln!("Error: --framing must be 'line' or 'headers', got '{mode}'");
+                    process::exit(1);
+                }));
+            }
+            "--max-file-size" => {
+                i += 1;
+                max_file_size =
+                    Some(args.get(i).and_then(|n| n.parse().ok()).unwrap_or_else(|| {
+                        eprintln!("Error: --max-file-size requires a non-negative integer");
+                        process::exit(1);
+                    }));
+            }
             arg if arg.starts_with('-') => {
                 eprintln!("Error: unknown option '{arg}'");
                 print_usage();
@@ -48,15 +180,130 @@ fn main() {
         i += 1;
     }
 
-    if serve && !file_paths.is_empty() {
-        eprintln!("Error: --serve and FILE are mutually exclusive");
+    if [
+        serve,
+        replay.is_some(),
+        requests.is_some(),
+        stdin,
+        !file_paths.is_empty(),
+    ]
+    .iter()
+    .filter(|&&mode| mode)
+    .count()
+        > 1
+    {
+        eprintln!("Error: --serve, --replay, --requests, --stdin and FILE are mutually exclusive");
+        process::exit(1);
+    }
+
+    if as_path.is_some() && !stdin {
+        eprintln!("Error: --as requires --stdin");
+        process::exit(1);
+    }
+
+    if pretty && !stdin && file_paths.is_empty() {
+        eprintln!("Error: --pretty-errors requires FILE or --stdin");
+        process::exit(1);
+    }
+
+    if !json && !pretty {
+        eprintln!("Error: --no-json requires --pretty-errors");
+        process::exit(1);
+    }
+
+    if benchmark.is_some() && (file_paths.is_empty() || validate || pretty) {
+        eprintln!("Error: --benchmark requires FILE and excludes --validate and --pretty-errors");
         process::exit(1);
     }
 
+    if workers > 0 && !serve && replay.is_none() {
+        eprintln!("Error: --workers requires --serve or --replay");
+        process::exit(1);
+    }
+
+    if debug && !serve && replay.is_none() {
+        eprintln!("Error: --debug requires --serve or --replay");
+        process::exit(1);
+    }
+
+    if max_file_size.is_some() && !serve && replay.is_none() {
+        eprintln!("Error: --max-file-size requires --serve or --replay");
+        process::exit(1);
+    }
+
+    if framing.is_some() && !serve && replay.is_none() {
+        eprintln!("Error: --framing requires --serve or --replay");
+        process::exit(1);
+    }
+    let framing = framing.unwrap_or_default();
+
+    // ty parallelizes on rayon's global pool, which must be sized before the
+    // database is created.
+    if let Some(threads) = threads
+        && let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+    {
+        eprintln!("Error: failed to configure {threads} thread(s): {e}");
+        process::exit(1);
+    }
+
+    let output = CliOutput {
+        include_display,
+        pretty,
+        json,
+        emit_empty,
+    };
+
     if serve {
-        run_serve();
+        run_serve(
+            io::BufReader::new(io::stdin()),
+            framing,
+            workers,
+            debug,
+            max_file_size,
+        );
+    } else if let Some(log) = replay {
+        let file = std::fs::File::open(&log).unwrap_or_else(|e| {
+            eprintln!("Error: cannot open replay log '{log}': {e}");
+            process::exit(1);
+        });
+        run_serve(
+            io::BufReader::new(file),
+            framing,
+            workers,
+            debug,
+            max_file_size,
+        );
+    } else if let Some(requests) = requests {
+        run_batch(
+            &requests,
+            project_root.as_deref(),
+            &extra_paths,
+            include_display,
+        );
+    } else if stdin {
+        run_stdin(
+            as_path.as_deref().unwrap_or("stdin.py"),
+            project_root.as_deref(),
+            &extra_paths,
+            output,
+        );
+    } else if let Some(iterations) = benchmark {
+        run_benchmark(
+            &file_paths,
+            project_root.as_deref(),
+            &extra_paths,
+            iterations,
+        );
     } else if !file_paths.is_empty() {
-        run_oneshot(&file_paths, project_root.as_deref());
+        run_oneshot(
+            &file_paths,
+            project_root.as_deref(),
+            &extra_paths,
+            validate,
+            output,
+        );
     } else {
         print_usage();
         process::exit(1);
@@ -64,21 +311,301 @@ fn main() {
 }
 
 fn print_usage() {
-    eprintln!("Usage: ty-types <FILE>... [--project-root DIR]");
-    eprintln!("       ty-types --serve");
+    eprintln!(
+        "Usage: ty-types <FILE>... [--project-root DIR] [--extra-path DIR]... [--no-display] [--validate]"
+    );
+    eprintln!("                        [--pretty-errors [--no-json]] [--no-emit-empty]");
+    eprintln!(
+        "       ty-types --serve [--workers N] [--debug] [--max-file-size BYTES] [--framing=MODE]"
+    );
+    eprintln!(
+        "       ty-types --replay LOG [--workers N] [--debug] [--max-file-size BYTES] [--framing=MODE]"
+    );
+    eprintln!("       ty-types --requests FILE [--project-root DIR] [--no-display]");
+    eprintln!(
+        "       ty-types --stdin [--as PATH] [--project-root DIR] [--no-display] [--pretty-errors [--no-json]]"
+    );
+    eprintln!("       ty-types --benchmark N <FILE>... [--project-root DIR] [--extra-path DIR]...");
+    eprintln!("       ty-types --version");
     eprintln!();
     eprintln!("Modes:");
     eprintln!("  <FILE>...   Infer types for one or more Python files, print JSON to stdout");
     eprintln!("  --serve     Run as a JSON-RPC server over stdin/stdout");
+    eprintln!("  --replay    Like --serve, reading newline-delimited requests from LOG");
+    eprintln!("  --requests  Infer types for a JSON array of {{\"file\": ...}} requests in FILE");
+    eprintln!("  --stdin     Infer types for source read from stdin, as if it were at --as PATH");
     eprintln!();
     eprintln!("Options:");
     eprintln!("  --project-root DIR   Override project root (defaults to first FILE's parent)");
+    eprintln!("  --as PATH            Path of the --stdin source, relative to the project root");
+    eprintln!("  --extra-path DIR     Add DIR to the module search path (repeatable)");
+    eprintln!("  --no-display         Omit `display` strings from type descriptors");
+    eprintln!("  --validate           Only check that FILEs parse and their imports resolve");
+    eprintln!(
+        "  --pretty-errors      Also print `line:col  kind  display` per typed node to stderr"
+    );
+    eprintln!("  --no-json            With --pretty-errors, don't print JSON to stdout");
+    eprintln!("  --no-emit-empty      Leave files without any nodes out of the JSON `files` map");
+    eprintln!("  --workers N          Serve read-only requests on N worker threads (default 0)");
+    eprintln!("  --debug              Enable maintainer methods such as `getRawType`");
+    eprintln!("  --max-file-size N    Skip `getTypes` for files larger than N bytes");
+    eprintln!("  --framing=MODE       Delimit messages by `line` (default) or LSP-style `headers`");
+    eprintln!("  --benchmark N        Infer FILEs N times and report throughput to stderr");
+    eprintln!(
+        "  --threads N          Cap ty's internal parallelism at N threads (default: all cores)"
+    );
+}
+
+/// What one-shot and stdin mode print.
+#[derive(Debug, Clone, Copy)]
+struct CliOutput {
+    include_display: bool,
+    /// Print a `line:col  kind  display` summary to stderr.
+    pretty: bool,
+    /// Print the JSON result to stdout.
+    json: bool,
+    /// Keep files without any nodes in the JSON `files` map.
+    emit_empty: bool,
 }
 
 /// One-shot mode: infer types for one or more files and print JSON to stdout.
-fn run_oneshot(file_args: &[String], project_root_arg: Option<&str>) {
-    let first_absolute = std::fs::canonicalize(&file_args[0]).unwrap_or_else(|e| {
-        eprintln!("Error: cannot resolve '{}': {e}", file_args[0]);
+fn run_oneshot(
+    file_args: &[String],
+    project_root_arg: Option<&str>,
+    extra_paths: &[String],
+    validate: bool,
+    output: CliOutput,
+) {
+    let db = oneshot_database(&file_args[0], project_root_arg, extra_paths);
+
+    let mut registry = TypeRegistry::new();
+    let mut files = HashMap::new();
+    let mut parse_errors = 0;
+    let mut unresolved_imports = 0;
+
+    for file_arg in file_args {
+        let (absolute, file) = oneshot_file(&db, file_arg);
+        let result = collector::collect_types(&db, file, &mut registry);
+
+        if validate {
+            let file_parse_errors = ruff_db::parsed::parsed_module(&db, file)
+                .load(&db)
+                .errors()
+                .len();
+            let file_unresolved = imports::collect_imports(&db, file)
+                .iter()
+                .filter(|import| import.unresolved)
+                .count();
+            println!(
+                "{file_arg}: {file_parse_errors} parse error(s), \
+                 {file_unresolved} unresolved import(s)"
+            );
+            parse_errors += file_parse_errors;
+            unresolved_imports += file_unresolved;
+            continue;
+        }
+
+        if output.pretty {
+            write_pretty(&db, file, file_arg, &result.nodes, &registry);
+        }
+        files.insert(absolute.to_string_lossy().into_owned(), result.nodes);
+    }
+
+    if validate {
+        println!(
+            "Validated {} file(s): {parse_errors} parse error(s), \
+             {unresolved_imports} unresolved import(s)",
+            file_args.len()
+        );
+        if parse_errors + unresolved_imports > 0 {
+            process::exit(1);
+        }
+        return;
+    }
+
+    if output.json {
+        write_cli_result(files, &registry, output);
+    }
+}
+
+/// Benchmark mode: infer `file_args` `iterations` times, each time on a fresh
+/// database and registry, and report throughput and per-file latency to
+/// stderr. Only type collection is timed, not database setup.
+fn run_benchmark(
+    file_args: &[String],
+    project_root_arg: Option<&str>,
+    extra_paths: &[String],
+    iterations: usize,
+) {
+    let mut latencies = Vec::with_capacity(file_args.len() * iterations);
+    let mut types = 0;
+
+    for _ in 0..iterations {
+        let db = oneshot_database(&file_args[0], project_root_arg, extra_paths);
+        let mut registry = TypeRegistry::new();
+        for file_arg in file_args {
+            let (_, file) = oneshot_file(&db, file_arg);
+            let start = Instant::now();
+            let result = collector::collect_types(&db, file, &mut registry);
+            latencies.push(start.elapsed());
+            types += result.new_types.len();
+        }
+    }
+
+    let total: Duration = latencies.iter().sum();
+    let seconds = total.as_secs_f64().max(f64::EPSILON);
+    latencies.sort_unstable();
+    eprintln!(
+        "Benchmarked {} file(s) x {iterations} iteration(s) in {:.3}s",
+        file_args.len(),
+        total.as_secs_f64()
+    );
+    eprintln!("files/sec: {:.1}", latencies.len() as f64 / seconds);
+    eprintln!("types/sec: {:.1}", types as f64 / seconds);
+    eprintln!(
+        "per-file latency: p50 {:.2}ms, p95 {:.2}ms",
+        percentile(&latencies, 0.50).as_secs_f64() * 1000.0,
+        percentile(&latencies, 0.95).as_secs_f64() * 1000.0
+    );
+}
+
+/// The nearest-rank `p` percentile of ascending `sorted` samples.
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let rank = (p * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Print a human-readable `line:col  kind  display` line to stderr for each
+/// typed node of `file`, under a `path` header. Columns count characters.
+fn write_pretty(
+    db: &ProjectDatabase,
+    file: File,
+    path: &str,
+    nodes: &[NodeAttribution],
+    registry: &TypeRegistry<'_>,
+) {
+    let source = source_text(db, file);
+    let index = line_index(db, file);
+
+    let mut stderr = io::stderr().lock();
+    let _ = writeln!(stderr, "{path}");
+    for node in nodes {
+        let Some(descriptor) = node.type_id.and_then(|id| registry.get_descriptor(id)) else {
+            continue;
+        };
+        let position = index.line_column(node.start.into(), &source);
+        let _ = writeln!(
+            stderr,
+            "  {}:{}  {}  {}",
+            position.line,
+            position.column,
+            node.node_kind,
+            descriptor.display().unwrap_or(descriptor.kind())
+        );
+    }
+}
+
+/// Print the one-shot output for `files` and every type in `registry`.
+fn write_cli_result(
+    mut files: HashMap<String, Vec<NodeAttribution>>,
+    registry: &TypeRegistry<'_>,
+    output: CliOutput,
+) {
+    if !output.emit_empty {
+        files.retain(|_, nodes| !nodes.is_empty());
+    }
+    let mut types = registry.all_descriptors();
+    if !output.include_display {
+        for desc in types.values_mut() {
+            desc.strip_display();
+        }
+    }
+
+    let result = CliResult { files, types };
+
+    serde_json::to_writer_pretty(io::stdout().lock(), &result).unwrap_or_else(|e| {
+        eprintln!("Error: failed to write JSON: {e}");
+        process::exit(1);
+    });
+    println!();
+}
+
+/// Stdin mode: infer types for source piped on stdin as if it were the file
+/// at `as_path` under the project root, and print JSON like one-shot mode.
+///
+/// The database reads the source from memory at `as_path` (see
+/// `OverlaySystem`), shadowing any file already there, so it has the module
+/// name and relative imports of a file at that path.
+fn run_stdin(
+    as_path: &str,
+    project_root_arg: Option<&str>,
+    extra_paths: &[String],
+    output: CliOutput,
+) {
+    let relative = std::path::Path::new(as_path);
+    if as_path.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, std::path::Component::Normal(_)))
+    {
+        eprintln!("Error: --as must be a relative path inside the project, got '{as_path}'");
+        process::exit(1);
+    }
+
+    let mut source = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut source) {
+        eprintln!("Error: failed to read stdin: {e}");
+        process::exit(1);
+    }
+
+    let root = project_root_arg.unwrap_or(".");
+    let root_path = std::fs::canonicalize(root).unwrap_or_else(|e| {
+        eprintln!("Error: cannot resolve project root '{root}': {e}");
+        process::exit(1);
+    });
+    let path = root_path.join(relative);
+    let sys_path = SystemPathBuf::from_path_buf(path.clone()).unwrap_or_else(|p| {
+        eprintln!("Error: non-Unicode path: {}", p.display());
+        process::exit(1);
+    });
+
+    // The source is read from memory at its `--as` path, so it resolves
+    // imports from the project around it.
+    let db = project::create_database_with_overlay(
+        &root_path.to_string_lossy(),
+        &absolute_extra_paths(extra_paths),
+        Some((sys_path.clone(), &source)),
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("Error: failed to initialize project: {e:#}");
+        process::exit(1);
+    });
+
+    let mut registry = TypeRegistry::new();
+    let file = system_path_to_file(&db, &sys_path).unwrap_or_else(|e| {
+        eprintln!("Error: failed to resolve file '{as_path}': {e}");
+        process::exit(1);
+    });
+    let result = collector::collect_types(&db, file, &mut registry);
+
+    if output.pretty {
+        write_pretty(&db, file, as_path, &result.nodes, &registry);
+    }
+    if output.json {
+        let files = HashMap::from([(path.to_string_lossy().into_owned(), result.nodes)]);
+        write_cli_result(files, &registry, output);
+    }
+}
+
+/// Open the one-shot project database: rooted at `project_root_arg`, or else at
+/// the parent directory of `first_file`.
+fn oneshot_database(
+    first_file: &str,
+    project_root_arg: Option<&str>,
+    extra_paths: &[String],
+) -> ProjectDatabase {
+    let first_absolute = std::fs::canonicalize(first_file).unwrap_or_else(|e| {
+        eprintln!("Error: cannot resolve '{first_file}': {e}");
         process::exit(1);
     });
 
@@ -97,75 +624,128 @@ fn run_oneshot(file_args: &[String], project_root_arg: Option<&str>) {
             .into_owned(),
     };
 
-    let db = project::create_database(&root_str).unwrap_or_else(|e| {
-        eprintln!("Error: failed to initialize project: {e}");
+    project::create_database(&root_str, &absolute_extra_paths(extra_paths)).unwrap_or_else(|e| {
+        eprintln!("Error: failed to initialize project: {e:#}");
+        process::exit(1);
+    })
+}
+
+/// Resolve `--extra-path` arguments, which are relative to the working
+/// directory.
+fn absolute_extra_paths(extra_paths: &[String]) -> Vec<String> {
+    extra_paths
+        .iter()
+        .map(|p| {
+            std::path::absolute(p)
+                .unwrap_or_else(|e| {
+                    eprintln!("Error: cannot resolve extra path '{p}': {e}");
+                    process::exit(1);
+                })
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+/// Resolve a command-line file argument to its canonical path and ty `File`.
+fn oneshot_file(db: &ProjectDatabase, file_arg: &str) -> (std::path::PathBuf, File) {
+    let absolute = std::fs::canonicalize(file_arg).unwrap_or_else(|e| {
+        eprintln!("Error: cannot resolve '{file_arg}': {e}");
         process::exit(1);
     });
 
-    let mut registry = TypeRegistry::new();
-    let mut files = std::collections::HashMap::new();
+    let sys_path = SystemPathBuf::from_path_buf(absolute.clone()).unwrap_or_else(|p| {
+        eprintln!("Error: non-Unicode path: {}", p.display());
+        process::exit(1);
+    });
 
-    for file_arg in file_args {
-        let absolute = std::fs::canonicalize(file_arg).unwrap_or_else(|e| {
-            eprintln!("Error: cannot resolve '{file_arg}': {e}");
-            process::exit(1);
-        });
+    let file = system_path_to_file(db, SystemPath::new(sys_path.as_str())).unwrap_or_else(|e| {
+        eprintln!("Error: failed to resolve file '{file_arg}': {e}");
+        process::exit(1);
+    });
+
+    (absolute, file)
+}
 
-        let sys_path = SystemPathBuf::from_path_buf(absolute.clone()).unwrap_or_else(|p| {
-            eprintln!("Error: non-Unicode path: {}", p.display());
+/// Batch mode: run a JSON array of `{file}` requests through one shared
+/// registry and print a JSON array of per-file results.
+fn run_batch(
+    requests_path: &str,
+    project_root_arg: Option<&str>,
+    extra_paths: &[String],
+    include_display: bool,
+) {
+    let requests: Vec<BatchRequest> = std::fs::read_to_string(requests_path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+        .unwrap_or_else(|e| {
+            eprintln!("Error: cannot read requests from '{requests_path}': {e}");
             process::exit(1);
         });
+    let Some(first) = requests.first() else {
+        println!("[]");
+        return;
+    };
 
-        let file =
-            system_path_to_file(&db, SystemPath::new(sys_path.as_str())).unwrap_or_else(|e| {
-                eprintln!("Error: failed to resolve file '{file_arg}': {e}");
-                process::exit(1);
-            });
-
-        let result = collector::collect_types(&db, file, &mut registry);
-        files.insert(absolute.to_string_lossy().into_owned(), result.nodes);
-    }
+    let db = oneshot_database(&first.file, project_root_arg, extra_paths);
+    let mut registry = TypeRegistry::new();
 
-    let output = CliResult {
-        files,
-        types: registry.all_descriptors(),
-    };
+    let results: Vec<BatchResult> = requests
+        .into_iter()
+        .map(|request| {
+            let (_, file) = oneshot_file(&db, &request.file);
+            let result = collector::collect_types(&db, file, &mut registry);
+            let mut types = result.new_types;
+            if !include_display {
+                for desc in types.values_mut() {
+                    desc.strip_display();
+                }
+            }
+            BatchResult {
+                file: request.file,
+                nodes: result.nodes,
+                types,
+            }
+        })
+        .collect();
 
-    serde_json::to_writer_pretty(io::stdout().lock(), &output).unwrap_or_else(|e| {
+    serde_json::to_writer_pretty(io::stdout().lock(), &results).unwrap_or_else(|e| {
         eprintln!("Error: failed to write JSON: {e}");
         process::exit(1);
     });
     println!();
 }
 
-/// JSON-RPC server mode over stdin/stdout.
-fn run_serve() {
-    let stdin = io::stdin();
-    let stdout = io::stdout();
+/// JSON-RPC server mode: requests from `input` (stdin, or a replay log),
+/// responses to stdout, both delimited according to `framing`.
+///
+/// `max_file_size` is the session default for files `getTypes` skips, unless
+/// `initialize` sets its own.
+fn run_serve(
+    input: impl BufRead + Send + 'static,
+    framing: Framing,
+    workers: usize,
+    debug: bool,
+    max_file_size: Option<usize>,
+) {
+    let stdout = MessageWriter::new(framing);
 
-    let mut lines = stdin.lock().lines();
+    let mut inbox = Inbox::spawn(input, framing);
 
     // Outer loop: wait for initialize, then enter session
-    while let Some(line) = read_line(&mut lines) {
-        let request: JsonRpcRequest = match serde_json::from_str(&line) {
+    while let Event::Input(Some(message)) = inbox.next() {
+        let request = match parse_request(message) {
             Ok(r) => r,
-            Err(e) => {
-                write_response(
-                    &stdout,
-                    &JsonRpcResponse::error(
-                        serde_json::Value::Null,
-                        -32700,
-                        format!("Parse error: {e}"),
-                    ),
-                );
+            Err(response) => {
+                write_response(&stdout, &response);
                 continue;
             }
         };
 
         match request.method.as_str() {
             "initialize" => {
-                let (db, root) = match do_initialize(&request) {
-                    Ok(pair) => {
+                let (mut db, root, options) = match do_initialize(&request, max_file_size) {
+                    Ok(session) => {
                         write_response(
                             &stdout,
                             &JsonRpcResponse::success(
@@ -173,7 +753,7 @@ fn run_serve() {
                                 serde_json::to_value(InitializeResult { ok: true }).unwrap(),
                             ),
                         );
-                        pair
+                        session
                     }
                     Err(response) => {
                         write_response(&stdout, &response);
@@ -181,13 +761,59 @@ fn run_serve() {
                     }
                 };
 
-                // Enter session loop with persistent registry
-                if run_session(&db, &root, &mut lines, &stdout) {
-                    return; // shutdown requested
+                // Files passed to `subscribe`, as the client named them, by
+                // path. Unlike the registry, they outlive `reload`.
+                let mut subscriptions: HashMap<SystemPathBuf, String> = HashMap::new();
+
+                // Enter session loop with persistent registry. `reload`
+                // replaces the database, so it starts a fresh session,
+                // registry included.
+                loop {
+                    match run_session(
+                        &mut db,
+                        &root,
+                        options,
+                        &mut subscriptions,
+                        &mut inbox,
+                        &stdout,
+                        workers,
+                        debug,
+                    ) {
+                        SessionEnd::Shutdown(shutdown) => {
+                            if let Some(shutdown) = shutdown {
+                                write_response(
+                                    &stdout,
+                                    &JsonRpcResponse::success(
+                                        shutdown.id,
+                                        serde_json::json!({"ok": true}),
+                                    ),
+                                );
+                            }
+                            return;
+                        }
+                        SessionEnd::Reload(reload) => {
+                            match do_initialize(&request, max_file_size) {
+                                Ok((fresh, _, _)) => {
+                                    db = fresh;
+                                    write_response(
+                                        &stdout,
+                                        &JsonRpcResponse::success(
+                                            reload.id,
+                                            serde_json::json!({"ok": true}),
+                                        ),
+                                    );
+                                }
+                                Err(mut response) => {
+                                    // Keep serving from the previous database.
+                                    response.id = reload.id;
+                                    write_response(&stdout, &response);
+                                }
+                            }
+                        }
+                    }
                 }
-                // If session ended without shutdown (e.g., re-initialize),
-                // loop back to wait for next initialize
             }
+            "version" => write_response(&stdout, &handle_version(&request)),
             "shutdown" => {
                 write_response(
                     &stdout,
@@ -200,7 +826,7 @@ fn run_serve() {
                     &stdout,
                     &JsonRpcResponse::error(
                         request.id,
-                        -32000,
+                        ErrorCode::NotInitialized,
                         "Not initialized. Call 'initialize' first.".to_string(),
                     ),
                 );
@@ -209,163 +835,1021 @@ fn run_serve() {
     }
 }
 
+/// Requests that only run read-only queries and never touch the session
+/// registry. With `--workers`, these are handled on the worker pool and may
+/// complete out of order.
+const READ_ONLY_METHODS: &[&str] = &[
+    "getImports",
+    "getDependencies",
+    "getTokenTypes",
+    "getCoverage",
+    "getDiagnostics",
+    "listFiles",
+];
+
+/// Requests that collect one file's types into the session registry. With
+/// `--workers`, the file is inferred on the worker pool first, so a slow file
+/// doesn't hold up other requests; requests for the same file are still
+/// answered in order.
+const COLLECTOR_METHODS: &[&str] = &[
+    "getTypes",
+    "getTypesForScope",
+    "getParents",
+    "getTypeAtPosition",
+];
+
+/// Requests that read the session registry as a whole or look types up by
+/// ID. Before answering, the files collected so far are registered again if
+/// the registry holds stale descriptors (see `reregister_collected`).
+const REGISTRY_METHODS: &[&str] = &[
+    "getClassHierarchy",
+    "getTypeRegistry",
+    "getTypeByDisplay",
+    "dumpRegistryGraph",
+    "getRawType",
+];
+
+/// Work handed to the worker pool.
+enum Job {
+    /// One of the `READ_ONLY_METHODS`, answered by the worker.
+    Request(JsonRpcRequest),
+    /// Infer a file for queued `COLLECTOR_METHODS` requests, which the
+    /// session thread answers on `Event::Inferred`.
+    Infer(File),
+}
+
+/// Why a session loop returned.
+enum SessionEnd {
+    /// `shutdown` was requested, or the input ended (`None`). The caller
+    /// answers the request.
+    Shutdown(Option<JsonRpcRequest>),
+    /// `reload` was requested. The caller rebuilds the database and answers
+    /// the request.
+    Reload(JsonRpcRequest),
+}
+
+/// Why serving one database revision stopped.
+enum RevisionEnd {
+    Session(SessionEnd),
+    /// `clearFile` or `fileChanged` was requested for the file at the given
+    /// path. The caller re-reads it into the database, answers the request,
+    /// and resumes the registry.
+    ClearFile(JsonRpcRequest, SystemPathBuf),
+}
+
+/// Per-session settings negotiated at `initialize`.
+#[derive(Debug, Clone, Copy)]
+struct SessionOptions {
+    position_encoding: PositionEncoding,
+    max_response_bytes: Option<usize>,
+    max_file_size: Option<usize>,
+    request_timeout: Option<Duration>,
+    union_display_style: UnionDisplayStyle,
+}
+
 /// Run the session loop with a persistent TypeRegistry.
-/// Returns true if shutdown was requested.
+#[allow(clippy::too_many_arguments)]
 fn run_session(
-    db: &ProjectDatabase,
+    db: &mut ProjectDatabase,
     project_root: &SystemPathBuf,
-    lines: &mut io::Lines<io::StdinLock<'_>>,
-    stdout: &io::Stdout,
-) -> bool {
-    // The registry lives for the duration of this function,
-    // sharing the 'db lifetime with the database reference.
-    let mut registry = TypeRegistry::new();
+    options: SessionOptions,
+    subscriptions: &mut HashMap<SystemPathBuf, String>,
+    inbox: &mut Inbox,
+    stdout: &MessageWriter,
+    workers: usize,
+    debug: bool,
+) -> SessionEnd {
+    let position_encoding = options.position_encoding;
+    let events = inbox.sender();
+    let handle_job = |db: &ProjectDatabase, job: Job| match job {
+        Job::Request(request) => {
+            if let Some(response) = handle_read_only(&request, db, project_root, position_encoding)
+            {
+                write_response(stdout, &response);
+            }
+        }
+        Job::Infer(file) => {
+            let outcome = match options.request_timeout {
+                Some(timeout) => infer_within(db, file, timeout),
+                None => {
+                    // A panic shows up again when the session thread
+                    // collects the file, and fails that request.
+                    let _ = std::panic::catch_unwind(AssertUnwindSafe(|| check_types(db, file)));
+                    Ok(())
+                }
+            };
+            let _ = events.send(Event::Inferred(file, outcome));
+        }
+    };
+
+    let mut suspended = TypeRegistry::new()
+        .with_position_encoding(position_encoding)
+        .with_union_display_style(options.union_display_style)
+        .suspend();
+    // Files passed to `getTypes` so far, searched by `getClassHierarchy`.
+    let mut collected_files: HashSet<File> = HashSet::new();
+    // A subscribed file re-read by the last `clearFile` or `fileChanged`, to
+    // push types for.
+    let mut changed: Option<String> = None;
 
+    // Each pass serves one revision of the database, until `clearFile` or
+    // `fileChanged` changes it.
     loop {
-        let Some(line) = read_line(lines) else {
-            return true;
-        };
+        let (end, registry) = {
+            let db = &*db;
+            std::thread::scope(|scope| {
+                let mut pool =
+                    (workers > 0).then(|| WorkerPool::spawn(scope, workers, &handle_job));
 
-        let request: JsonRpcRequest = match serde_json::from_str(&line) {
-            Ok(r) => r,
-            Err(e) => {
-                write_response(
-                    stdout,
-                    &JsonRpcResponse::error(
-                        serde_json::Value::Null,
-                        -32700,
-                        format!("Parse error: {e}"),
-                    ),
-                );
-                continue;
-            }
+                // The registry lives for the duration of this revision, sharing
+                // the 'db lifetime with the database reference.
+                let mut registry = TypeRegistry::resume(suspended);
+                // Collector requests waiting for a worker to infer their file,
+                // in the order they arrived.
+                let mut queued: HashMap<File, VecDeque<JsonRpcRequest>> = HashMap::new();
+                // Files a worker has inferred, which need no worker again.
+                let mut inferred: HashSet<File> = HashSet::new();
+                // Set by a request that ends the revision, which waits for the
+                // queued requests to be answered.
+                let mut ending: Option<RevisionEnd> = None;
+
+                if let Some(file) = changed.take() {
+                    push_types_changed(
+                        &file,
+                        db,
+                        project_root,
+                        options,
+                        &mut registry,
+                        &mut collected_files,
+                        stdout,
+                    );
+                }
+
+                loop {
+                    if queued.is_empty()
+                        && let Some(end) = ending.take()
+                    {
+                        if let RevisionEnd::ClearFile(..) = end {
+                            reregister_collected(db, options, &mut registry, &collected_files);
+                        }
+                        return (end, registry.suspend());
+                    }
+
+                    let event = if ending.is_some() {
+                        inbox.next_inferred()
+                    } else {
+                        inbox.next()
+                    };
+                    let message = match event {
+                        Event::Input(Some(message)) => message,
+                        Event::Input(None) => {
+                            if let Some(pool) = pool.take() {
+                                pool.finish();
+                            }
+                            ending = Some(RevisionEnd::Session(SessionEnd::Shutdown(None)));
+                            continue;
+                        }
+                        Event::Inferred(file, outcome) => {
+                            // The rest of the queue finds the file inferred,
+                            // unless inference timed out.
+                            let collect_options = match outcome {
+                                Ok(()) => {
+                                    inferred.insert(file);
+                                    SessionOptions {
+                                        request_timeout: None,
+                                        ..options
+                                    }
+                                }
+                                Err(_) => options,
+                            };
+                            let requests = queued.remove(&file).unwrap_or_default();
+                            for (i, request) in requests.into_iter().enumerate() {
+                                let response = match outcome {
+                                    Err(reason) if i == 0 => {
+                                        let file_param = parse_params::<FileParams>(&request)
+                                            .map(|p| p.file)
+                                            .unwrap_or_default();
+                                        let timeout = options.request_timeout.unwrap_or_default();
+                                        Some(timeout_response(
+                                            &request,
+                                            &file_param,
+                                            timeout,
+                                            reason,
+                                        ))
+                                    }
+                                    _ => handle_collector(
+                                        &request,
+                                        db,
+                                        project_root,
+                                        collect_options,
+                                        &mut registry,
+                                        &mut collected_files,
+                                    ),
+                                };
+                                if let Some(response) = response {
+                                    write_response(stdout, &response);
+                                }
+                            }
+                            continue;
+                        }
+                    };
+
+                    let request = match parse_request(message) {
+                        Ok(r) => r,
+                        Err(response) => {
+                            write_response(stdout, &response);
+                            continue;
+                        }
+                    };
+
+                    if READ_ONLY_METHODS.contains(&request.method.as_str()) {
+                        match &pool {
+                            Some(pool) => pool.submit(db, Job::Request(request)),
+                            None => handle_job(db, Job::Request(request)),
+                        }
+                        continue;
+                    }
+
+                    if COLLECTOR_METHODS.contains(&request.method.as_str()) {
+                        if let Some(pool) = &pool
+                            && let Some(file) = inferred_file(&request, db, project_root, options)
+                            && !inferred.contains(&file)
+                        {
+                            let requests = queued.entry(file).or_default();
+                            if requests.is_empty() {
+                                pool.submit(db, Job::Infer(file));
+                            }
+                            requests.push_back(request);
+                        } else if let Some(response) = handle_collector(
+                            &request,
+                            db,
+                            project_root,
+                            options,
+                            &mut registry,
+                            &mut collected_files,
+                        ) {
+                            write_response(stdout, &response);
+                        }
+                        continue;
+                    }
+
+                    if REGISTRY_METHODS.contains(&request.method.as_str()) {
+                        reregister_collected(db, options, &mut registry, &collected_files);
+                    }
+
+                    match request.method.as_str() {
+                        "getTypesBatch" => {
+                            let response = handle_get_types_batch(
+                                &request,
+                                db,
+                                project_root,
+                                options,
+                                &mut registry,
+                                &mut collected_files,
+                            );
+                            write_response(stdout, &response);
+                        }
+                        "getClassHierarchy" => {
+                            let response = handle_get_class_hierarchy(
+                                &request,
+                                db,
+                                &mut registry,
+                                &collected_files,
+                            );
+                            write_response(stdout, &response);
+                        }
+                        "getTypeRegistry" => {
+                            let response = handle_get_type_registry(&request, &registry);
+                            write_response(stdout, &response);
+                        }
+                        "getTypeByDisplay" => {
+                            let response = handle_get_type_by_display(&request, &registry);
+                            write_response(stdout, &response);
+                        }
+                        "dumpRegistryGraph" => {
+                            let response = handle_dump_registry_graph(&request, &registry);
+                            write_response(stdout, &response);
+                        }
+                        "healthcheck" => write_response(stdout, &handle_healthcheck(&request, db)),
+                        "getRawType" if debug => {
+                            write_response(stdout, &handle_get_raw_type(&request, db, &registry));
+                        }
+                        "version" => write_response(stdout, &handle_version(&request)),
+                        "shutdown" => {
+                            // Let in-flight worker requests respond before acknowledging.
+                            if let Some(pool) = pool.take() {
+                                pool.finish();
+                            }
+                            ending =
+                                Some(RevisionEnd::Session(SessionEnd::Shutdown(Some(request))));
+                        }
+                        "reload" => {
+                            // In-flight worker requests finish against the old database.
+                            if let Some(pool) = pool.take() {
+                                pool.finish();
+                            }
+                            ending = Some(RevisionEnd::Session(SessionEnd::Reload(request)));
+                        }
+                        "clearFile" | "fileChanged" => {
+                            let params: FileParams = match parse_params(&request) {
+                                Ok(p) => p,
+                                Err(response) => {
+                                    write_response(stdout, &response);
+                                    continue;
+                                }
+                            };
+                            // In-flight worker requests finish against the old revision.
+                            if let Some(pool) = pool.take() {
+                                pool.finish();
+                            }
+                            let path = file_system_path(project_root, &params.file);
+                            ending = Some(RevisionEnd::ClearFile(request, path));
+                        }
+                        "subscribe" | "unsubscribe" => {
+                            let params: SubscribeParams = match parse_params(&request) {
+                                Ok(p) => p,
+                                Err(response) => {
+                                    write_response(stdout, &response);
+                                    continue;
+                                }
+                            };
+                            for file in params.files {
+                                let path = file_system_path(project_root, &file);
+                                if request.method == "subscribe" {
+                                    subscriptions.insert(path, file);
+                                } else {
+                                    subscriptions.remove(&path);
+                                }
+                            }
+                            write_response(
+                                stdout,
+                                &JsonRpcResponse::success(
+                                    request.id,
+                                    serde_json::json!({"ok": true}),
+                                ),
+                            );
+                        }
+                        "initialize" => {
+                            // Re-initialize: respond with error suggesting restart
+                            write_response(
+                                stdout,
+                                &JsonRpcResponse::error(
+                                    request.id,
+                                    ErrorCode::AlreadyInitialized,
+                                    "Already initialized. Send 'shutdown' first to reinitialize."
+                                        .to_string(),
+                                ),
+                            );
+                        }
+                        _ => {
+                            write_response(
+                                stdout,
+                                &JsonRpcResponse::error(
+                                    request.id,
+                                    ErrorCode::MethodNotFound,
+                                    format!("Method not found: {}", request.method),
+                                ),
+                            );
+                        }
+                    }
+                }
+            })
         };
 
-        match request.method.as_str() {
-            "getTypes" => {
-                let response = handle_get_types(&request, db, project_root, &mut registry);
-                write_response(stdout, &response);
-            }
-            "getTypeRegistry" => {
-                let response = handle_get_type_registry(&request, &registry);
-                write_response(stdout, &response);
-            }
-            "shutdown" => {
+        match end {
+            RevisionEnd::Session(end) => return end,
+            RevisionEnd::ClearFile(request, path) => {
+                File::sync_path(db, &path);
                 write_response(
                     stdout,
                     &JsonRpcResponse::success(request.id, serde_json::json!({"ok": true})),
                 );
-                return true;
-            }
-            "initialize" => {
-                // Re-initialize: respond with error suggesting restart
-                write_response(
-                    stdout,
-                    &JsonRpcResponse::error(
-                        request.id,
-                        -32000,
-                        "Already initialized. Send 'shutdown' first to reinitialize.".to_string(),
-                    ),
-                );
-            }
-            _ => {
-                write_response(
-                    stdout,
-                    &JsonRpcResponse::error(
-                        request.id,
-                        -32601,
-                        format!("Method not found: {}", request.method),
-                    ),
-                );
+                suspended = registry;
+                changed = subscriptions.get(&path).cloned();
             }
         }
     }
 }
 
-fn read_line(lines: &mut io::Lines<io::StdinLock<'_>>) -> Option<String> {
-    loop {
-        match lines.next()? {
-            Ok(line) if line.trim().is_empty() => continue,
-            Ok(line) => return Some(line),
-            Err(e) => {
-                eprintln!("Error reading stdin: {e}");
-                return None;
-            }
-        }
-    }
+/// What the server reads from its input thread and hears back from workers.
+enum Event {
+    /// The next message, or `None` once the input ends.
+    Input(Option<Result<String, MalformedMessage>>),
+    /// A worker finished inferring a file for `Job::Infer`.
+    Inferred(File, Result<(), InferenceTimeout>),
 }
 
-fn write_response(stdout: &io::Stdout, response: &JsonRpcResponse) {
-    let mut out = stdout.lock();
-    let _ = serde_json::to_writer(&mut out, response);
-    let _ = out.write_all(b"\n");
-    let _ = out.flush();
+/// The server's event queue. Messages are read on their own thread, so the
+/// session thread can answer requests whose file a worker inferred while it
+/// waits for the next message.
+struct Inbox {
+    events: mpsc::Receiver<Event>,
+    sender: mpsc::Sender<Event>,
+    /// Messages that arrived while a revision was ending, handled first.
+    deferred: VecDeque<Option<Result<String, MalformedMessage>>>,
 }
 
-fn do_initialize(
-    request: &JsonRpcRequest,
-) -> Result<(ProjectDatabase, SystemPathBuf), JsonRpcResponse> {
-    let params: InitializeParams = serde_json::from_value(request.params.clone()).map_err(|e| {
-        JsonRpcResponse::error(request.id.clone(), -32602, format!("Invalid params: {e}"))
-    })?;
+impl Inbox {
+    fn spawn(input: impl BufRead + Send + 'static, framing: Framing) -> Self {
+        let (sender, events) = mpsc::channel();
+        let reader = sender.clone();
+        std::thread::spawn(move || {
+            let mut input = MessageReader::new(input, framing);
+            loop {
+                let message = input.next_message();
+                let end = message.is_none();
+                if reader.send(Event::Input(message)).is_err() || end {
+                    return;
+                }
+            }
+        });
+        Self {
+            events,
+            sender,
+            deferred: VecDeque::new(),
+        }
+    }
+
+    fn sender(&self) -> mpsc::Sender<Event> {
+        self.sender.clone()
+    }
+
+    /// The next event, deferred messages first.
+    fn next(&mut self) -> Event {
+        match self.deferred.pop_front() {
+            Some(message) => Event::Input(message),
+            None => self.recv(),
+        }
+    }
+
+    /// The next `Event::Inferred`, deferring messages that arrive first.
+    fn next_inferred(&mut self) -> Event {
+        loop {
+            match self.recv() {
+                Event::Input(message) => self.deferred.push_back(message),
+                inferred => return inferred,
+            }
+        }
+    }
+
+    fn recv(&self) -> Event {
+        // `self.sender` keeps the channel open.
+        self.events.recv().expect("the inbox holds a sender")
+    }
+}
+
+/// Send a `typesChanged` notification with the types of the subscribed
+/// `file`, as `getTypes` with default params returns them.
+fn push_types_changed<'db>(
+    file: &str,
+    db: &'db ProjectDatabase,
+    project_root: &SystemPathBuf,
+    options: SessionOptions,
+    registry: &mut TypeRegistry<'db>,
+    collected_files: &mut HashSet<File>,
+    stdout: &MessageWriter,
+) {
+    let request = JsonRpcRequest {
+        jsonrpc: "2.0".to_string(),
+        method: "getTypes".to_string(),
+        params: serde_json::json!({"file": file}),
+        id: None,
+    };
+    let response = handle_get_types(
+        &request,
+        db,
+        project_root,
+        options,
+        registry,
+        collected_files,
+    );
+    let Some(serde_json::Value::Object(mut params)) = response.result else {
+        let message = response.error.map(|e| e.message).unwrap_or_default();
+        eprintln!("Error: could not push types for {file}: {message}");
+        return;
+    };
+    params.insert("file".to_string(), file.into());
+    let notification = JsonRpcNotification {
+        jsonrpc: "2.0",
+        method: "typesChanged",
+        params: params.into(),
+    };
+    stdout.write(&serde_json::to_vec(&notification).unwrap());
+}
+
+/// Collect `collected_files` again if the registry holds stale descriptors,
+/// so the types still in those files take over their earlier IDs, then drop
+/// the descriptors no type took over. Types registered for the first time
+/// are sent with the next response that references them.
+fn reregister_collected<'db>(
+    db: &'db ProjectDatabase,
+    options: SessionOptions,
+    registry: &mut TypeRegistry<'db>,
+    collected_files: &HashSet<File>,
+) {
+    if !registry.has_stale() {
+        return;
+    }
+    for &file in collected_files {
+        if exceeds_max_file_size(db, file, options) {
+            continue;
+        }
+        // A file that panics keeps its stale descriptors out of the registry,
+        // and fails again on its next collector request.
+        let collected = std::panic::catch_unwind(AssertUnwindSafe(|| {
+            collector::collect_types(db, file, registry)
+        }));
+        if let Ok(result) = collected {
+            registry.mark_unsent(result.new_types.into_keys());
+        }
+    }
+    registry.drop_stale();
+}
+
+/// Handle one of the `COLLECTOR_METHODS`. Returns `None` for any other method.
+fn handle_collector<'db>(
+    request: &JsonRpcRequest,
+    db: &'db ProjectDatabase,
+    project_root: &SystemPathBuf,
+    options: SessionOptions,
+    registry: &mut TypeRegistry<'db>,
+    collected_files: &mut HashSet<File>,
+) -> Option<JsonRpcResponse> {
+    let handle = match request.method.as_str() {
+        "getTypes" => handle_get_types,
+        "getTypesForScope" => handle_get_types_for_scope,
+        "getParents" => handle_get_parents,
+        "getTypeAtPosition" => handle_get_type_at_position,
+        _ => return None,
+    };
+    Some(handle(
+        request,
+        db,
+        project_root,
+        options,
+        registry,
+        collected_files,
+    ))
+}
+
+/// The file a `COLLECTOR_METHODS` request infers, or `None` when the request
+/// fails before inferring anything or `getTypes` skips the file as too large.
+fn inferred_file(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    project_root: &SystemPathBuf,
+    options: SessionOptions,
+) -> Option<File> {
+    let params: FileParams = parse_params(request).ok()?;
+    let file = resolve_file(request, db, project_root, &params.file).ok()?;
+    let skipped = request.method == "getTypes" && exceeds_max_file_size(db, file, options);
+    (!skipped).then_some(file)
+}
+
+/// Whether `getTypes` skips `file` under the session's `maxFileSize`.
+fn exceeds_max_file_size(db: &ProjectDatabase, file: File, options: SessionOptions) -> bool {
+    options
+        .max_file_size
+        .is_some_and(|max_file_size| source_text(db, file).len() > max_file_size)
+}
+
+/// Handle one of the `READ_ONLY_METHODS`. Returns `None` for any other method.
+fn handle_read_only(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    project_root: &SystemPathBuf,
+    position_encoding: PositionEncoding,
+) -> Option<JsonRpcResponse> {
+    let response = match request.method.as_str() {
+        "getImports" => handle_get_imports(request, db, project_root, position_encoding),
+        "getDependencies" => handle_get_dependencies(request, db, project_root),
+        "getTokenTypes" => handle_get_token_types(request, db, project_root, position_encoding),
+        "getCoverage" => handle_get_coverage(request, db, project_root),
+        "getDiagnostics" => handle_get_diagnostics(request, db, project_root, position_encoding),
+        "listFiles" => handle_list_files(request, db),
+        _ => return None,
+    };
+    Some(response)
+}
+
+/// Write `response` as one message, unless it answers a notification.
+fn write_response(stdout: &MessageWriter, response: &JsonRpcResponse) {
+    if response.id.is_none() {
+        return;
+    }
+    stdout.write(&serde_json::to_vec(response).unwrap());
+}
+
+fn do_initialize(
+    request: &JsonRpcRequest,
+    default_max_file_size: Option<usize>,
+) -> Result<(ProjectDatabase, SystemPathBuf, SessionOptions), JsonRpcResponse> {
+    let params: InitializeParams = parse_params(request)?;
 
     let root = SystemPathBuf::from_path_buf(std::path::PathBuf::from(&params.project_root))
         .map_err(|p| {
             JsonRpcResponse::error(
                 request.id.clone(),
-                -32000,
+                ErrorCode::NonUnicodePath,
                 format!("Non-Unicode path: {}", p.display()),
             )
         })?;
 
-    let db = project::create_database(&params.project_root).map_err(|e| {
+    let db = project::create_database(&params.project_root, &params.extra_paths).map_err(|e| {
+        if e.is::<EnvironmentError>() {
+            JsonRpcResponse::error(
+                request.id.clone(),
+                ErrorCode::EnvironmentUnresolved,
+                format!("{e:#}"),
+            )
+        } else {
+            JsonRpcResponse::error(
+                request.id.clone(),
+                ErrorCode::ServerError,
+                format!("Failed to initialize: {e}"),
+            )
+        }
+    })?;
+
+    let options = SessionOptions {
+        position_encoding: params.position_encoding,
+        max_response_bytes: params.max_response_bytes,
+        max_file_size: params.max_file_size.or(default_max_file_size),
+        request_timeout: params.request_timeout_ms.map(Duration::from_millis),
+        union_display_style: params.union_display_style,
+    };
+
+    Ok((db, root, options))
+}
+
+/// Deserialize a message read from the input, or answer it with `ParseError`
+/// if it isn't a request or its framing was malformed.
+fn parse_request(
+    message: Result<String, MalformedMessage>,
+) -> Result<JsonRpcRequest, JsonRpcResponse> {
+    let parse_error = |reason: String| {
+        JsonRpcResponse::error(
+            Some(serde_json::Value::Null),
+            ErrorCode::ParseError,
+            format!("Parse error: {reason}"),
+        )
+    };
+    let message = message.map_err(|malformed| parse_error(malformed.to_string()))?;
+    serde_json::from_str(&message).map_err(|e| parse_error(e.to_string()))
+}
+
+/// Deserialize a request's params, or fail it with `InvalidParams`.
+fn parse_params<T: DeserializeOwned>(request: &JsonRpcRequest) -> Result<T, JsonRpcResponse> {
+    serde_json::from_value(request.params.clone()).map_err(|e| {
         JsonRpcResponse::error(
             request.id.clone(),
-            -32000,
-            format!("Failed to initialize: {e}"),
+            ErrorCode::InvalidParams,
+            format!("Invalid params: {e}"),
         )
-    })?;
+    })
+}
+
+/// Run `collect` over `file`, named `file_param` by a request.
+///
+/// With a `requestTimeoutMs`, `file` is inferred under that deadline first, and
+/// the request fails with `RequestTimeout` if it runs over. A panic inside ty
+/// fails this request with `InferenceFailed` rather than the whole session.
+fn collect_guarded<R>(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    file: File,
+    file_param: &str,
+    options: SessionOptions,
+    collect: impl FnOnce() -> R,
+) -> Result<R, JsonRpcResponse> {
+    if let Some(timeout) = options.request_timeout
+        && let Err(reason) = infer_within(db, file, timeout)
+    {
+        return Err(timeout_response(request, file_param, timeout, reason));
+    }
+
+    std::panic::catch_unwind(AssertUnwindSafe(collect)).map_err(|payload| {
+        JsonRpcResponse::error(
+            request.id.clone(),
+            ErrorCode::InferenceFailed,
+            format!(
+                "Failed to infer types for '{file_param}': {}",
+                panic_message(&*payload)
+            ),
+        )
+    })
+}
+
+/// The `RequestTimeout` error for a request whose file `infer_within` gave up on.
+fn timeout_response(
+    request: &JsonRpcRequest,
+    file_param: &str,
+    timeout: Duration,
+    reason: InferenceTimeout,
+) -> JsonRpcResponse {
+    let message = match reason {
+        InferenceTimeout::Elapsed => format!(
+            "Inferring types for '{file_param}' took longer than {}ms",
+            timeout.as_millis()
+        ),
+        InferenceTimeout::Saturated => format!(
+            "Not inferring types for '{file_param}': {MAX_ABANDONED_INFERENCES} \
+             inferences that timed out are still running"
+        ),
+    };
+    JsonRpcResponse::error(request.id.clone(), ErrorCode::RequestTimeout, message)
+}
+
+/// The message of a caught panic, when it carries one.
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("panic")
+}
+
+/// Resolve a `file` param (absolute or relative to the project root) to a ty `File`.
+fn resolve_file(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    project_root: &SystemPathBuf,
+    file: &str,
+) -> Result<File, JsonRpcResponse> {
+    let file_path = file_system_path(project_root, file);
+
+    system_path_to_file(db, SystemPath::new(file_path.as_str())).map_err(|e| {
+        JsonRpcResponse::error(
+            request.id.clone(),
+            ErrorCode::FileNotFound,
+            format!("Failed to resolve file '{file}': {e}"),
+        )
+    })
+}
 
-    Ok((db, root))
+/// The path of a request's `file` param: absolute, or relative to the project root.
+fn file_system_path(project_root: &SystemPathBuf, file: &str) -> SystemPathBuf {
+    if std::path::Path::new(file).is_absolute() {
+        SystemPathBuf::from_path_buf(std::path::PathBuf::from(file))
+            .unwrap_or_else(|_| SystemPathBuf::from(file))
+    } else {
+        project_root.join(file)
+    }
 }
 
 fn handle_get_types<'db>(
     request: &JsonRpcRequest,
     db: &'db ProjectDatabase,
     project_root: &SystemPathBuf,
+    options: SessionOptions,
     registry: &mut TypeRegistry<'db>,
+    collected_files: &mut HashSet<File>,
 ) -> JsonRpcResponse {
-    let params: GetTypesParams = match serde_json::from_value(request.params.clone()) {
+    let params: GetTypesParams = match parse_params(request) {
         Ok(p) => p,
-        Err(e) => {
-            return JsonRpcResponse::error(
-                request.id.clone(),
-                -32602,
-                format!("Invalid params: {e}"),
-            );
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    if exceeds_max_file_size(db, file, options) {
+        let response = GetTypesResult {
+            nodes: NodeList::new(&[], params.node_encoding),
+            types: HashMap::new(),
+            ref_counts: params.include_ref_counts.then(HashMap::new),
+            index: params.include_index.then(BTreeMap::new),
+            truncated: false,
+            total_nodes: None,
+            skipped_too_large: true,
+            line_starts: None,
+        };
+        return JsonRpcResponse::success(
+            request.id.clone(),
+            serde_json::to_value(response).unwrap(),
+        );
+    }
+
+    let collected = collect_guarded(request, db, file, &params.file, options, || {
+        if params.types.types_only {
+            collector::collect_types_only(db, file, registry)
+        } else {
+            collector::collect_types(db, file, registry)
         }
+    });
+    let mut result = match collected {
+        Ok(result) => result,
+        Err(response) => return response,
     };
+    collected_files.insert(file);
 
-    let file_path = if std::path::Path::new(&params.file).is_absolute() {
-        SystemPathBuf::from_path_buf(std::path::PathBuf::from(&params.file))
-            .unwrap_or_else(|_| SystemPathBuf::from(params.file.as_str()))
-    } else {
-        project_root.join(&params.file)
+    let encoder = OffsetEncoder::new(db, file, options.position_encoding);
+    for node in &mut result.nodes {
+        node.start = encoder.encode(node.start);
+        node.end = encoder.encode(node.end);
+    }
+    let line_starts = params.include_line_index.then(|| {
+        line_index(db, file)
+            .line_starts()
+            .iter()
+            .map(|&start| encoder.encode(start.into()))
+            .collect()
+    });
+
+    let mut types = result.new_types;
+    apply_type_options(db, file, &params.types, &result.nodes, &mut types, registry);
+
+    let index = params.include_index.then(|| kind_index(&types));
+
+    let encoding = params.node_encoding;
+    let mut nodes = result.nodes;
+    let mut response = GetTypesResult {
+        ref_counts: params
+            .include_ref_counts
+            .then(|| ref_counts(&nodes, &types)),
+        nodes: NodeList::new(&nodes, encoding),
+        types,
+        index,
+        truncated: false,
+        total_nodes: None,
+        skipped_too_large: false,
+        line_starts,
+    };
+    if let Some(max_bytes) = options.max_response_bytes
+        && let Some(kept) = fit_nodes(&mut response, &nodes, encoding, max_bytes)
+    {
+        nodes.truncate(kept);
+        response.nodes = NodeList::new(&nodes, encoding);
+        if let Some(ref_counts) = &mut response.ref_counts {
+            *ref_counts = self::ref_counts(&nodes, &response.types);
+        }
+    }
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+/// Apply the `TypeOptions` of a `getTypes` or `getTypesBatch` request to the
+/// types drained for it. `file` is one of the collected files, to resolve
+/// modules from for `excludeStdlib`.
+fn apply_type_options<'db, 'a>(
+    db: &'db ProjectDatabase,
+    file: File,
+    params: &TypeOptions,
+    nodes: impl IntoIterator<Item = &'a NodeAttribution>,
+    types: &mut HashMap<TypeId, TypeDescriptor>,
+    registry: &mut TypeRegistry<'db>,
+) {
+    let registered: Vec<TypeId> = types.keys().copied().collect();
+    if !params.include_display {
+        let keep: Vec<&str> = params.keep_display_for.iter().map(String::as_str).collect();
+        for desc in types.values_mut() {
+            desc.strip_display_except(&keep);
+        }
+    }
+    if params.exclude_stdlib {
+        let mut stdlib_modules: HashMap<String, bool> = HashMap::new();
+        types.retain(|_, desc| {
+            let Some(module) = desc.module_name() else {
+                return true;
+            };
+            let is_stdlib = *stdlib_modules
+                .entry(module.to_string())
+                .or_insert_with(|| imports::is_stdlib_module(db, file, module));
+            !is_stdlib
+        });
+    }
+    if params.prune_unreferenced {
+        prune_unreferenced(nodes, types);
+    }
+    // Excluded and pruned types still need sending once something that isn't
+    // excluded references them.
+    registry.mark_unsent(registered.into_iter().filter(|id| !types.contains_key(id)));
+}
+
+/// Collect several files in one request. A file that fails gets an error
+/// entry without failing the others.
+fn handle_get_types_batch<'db>(
+    request: &JsonRpcRequest,
+    db: &'db ProjectDatabase,
+    project_root: &SystemPathBuf,
+    options: SessionOptions,
+    registry: &mut TypeRegistry<'db>,
+    collected_files: &mut HashSet<File>,
+) -> JsonRpcResponse {
+    let params: GetTypesBatchParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
     };
 
-    let file = match system_path_to_file(db, SystemPath::new(file_path.as_str())) {
+    // Every file is collected first and the new types are drained once, so
+    // each file's `newTypeIds` are the tracked IDs it added.
+    registry.start_tracking();
+    let mut results = BTreeMap::new();
+    let mut collected: Vec<(String, File, Vec<NodeAttribution>, Vec<TypeId>)> = Vec::new();
+    for file_param in params.files {
+        let file = match resolve_file(request, db, project_root, &file_param) {
+            Ok(f) => f,
+            Err(response) => {
+                let error = response.error.expect("resolve_file fails with an error");
+                results.insert(file_param, BatchFileEntry::Failed { error });
+                continue;
+            }
+        };
+
+        let tracked = registry.tracked_new_ids().len();
+        let nodes = collect_guarded(request, db, file, &file_param, options, || {
+            collector::collect_nodes(db, file, registry, !params.types.types_only)
+        });
+        let nodes = match nodes {
+            Ok(nodes) => nodes,
+            Err(response) => {
+                let error = response.error.expect("collect_guarded fails with an error");
+                results.insert(file_param, BatchFileEntry::Failed { error });
+                continue;
+            }
+        };
+        collected_files.insert(file);
+        let introduced = registry.tracked_new_ids()[tracked..].to_vec();
+        collected.push((file_param, file, nodes, introduced));
+    }
+    let mut types = registry.drain_new_types();
+
+    if let Some(&(_, file, ..)) = collected.first() {
+        let nodes = collected.iter().flat_map(|(_, _, nodes, _)| nodes);
+        apply_type_options(db, file, &params.types, nodes, &mut types, registry);
+    }
+
+    for (file_param, file, mut nodes, introduced) in collected {
+        let encoder = OffsetEncoder::new(db, file, options.position_encoding);
+        for node in &mut nodes {
+            node.start = encoder.encode(node.start);
+            node.end = encoder.encode(node.end);
+        }
+        let mut new_type_ids: Vec<TypeId> = introduced
+            .into_iter()
+            .filter(|id| types.contains_key(id))
+            .collect();
+        new_type_ids.sort_unstable();
+        results.insert(
+            file_param,
+            BatchFileEntry::Collected {
+                nodes,
+                new_type_ids,
+            },
+        );
+    }
+
+    let response = GetTypesBatchResult { results, types };
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_types_for_scope<'db>(
+    request: &JsonRpcRequest,
+    db: &'db ProjectDatabase,
+    project_root: &SystemPathBuf,
+    options: SessionOptions,
+    registry: &mut TypeRegistry<'db>,
+    collected_files: &mut HashSet<File>,
+) -> JsonRpcResponse {
+    let params: GetTypesForScopeParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
         Ok(f) => f,
-        Err(e) => {
+        Err(response) => return response,
+    };
+
+    let encoder = OffsetEncoder::new(db, file, options.position_encoding);
+    let offset = encoder.decode(params.offset).into();
+
+    let collected = collect_guarded(request, db, file, &params.file, options, || {
+        collector::collect_scope_types(db, file, registry, offset)
+    });
+    let (range, result) = match collected {
+        Ok(Some(scope)) => scope,
+        Ok(None) => {
             return JsonRpcResponse::error(
                 request.id.clone(),
-                -32000,
-                format!("Failed to resolve file '{}': {e}", params.file),
+                ErrorCode::InvalidParams,
+                format!(
+                    "No function or class definition in '{}' contains offset {}",
+                    params.file, params.offset
+                ),
             );
         }
+        Err(response) => return response,
     };
+    collected_files.insert(file);
 
-    let result = collector::collect_types(db, file, registry);
-
+    let mut nodes = result.nodes;
+    for node in &mut nodes {
+        node.start = encoder.encode(node.start);
+        node.end = encoder.encode(node.end);
+    }
     let mut types = result.new_types;
     if !params.include_display {
         for desc in types.values_mut() {
@@ -373,10 +1857,321 @@ fn handle_get_types<'db>(
         }
     }
 
-    let response = GetTypesResult {
-        nodes: result.nodes,
+    let response = GetTypesForScopeResult {
+        start: encoder.encode(range.start().into()),
+        end: encoder.encode(range.end().into()),
+        nodes,
         types,
     };
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_parents<'db>(
+    request: &JsonRpcRequest,
+    db: &'db ProjectDatabase,
+    project_root: &SystemPathBuf,
+    options: SessionOptions,
+    registry: &mut TypeRegistry<'db>,
+    collected_files: &mut HashSet<File>,
+) -> JsonRpcResponse {
+    let params: GetParentsParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    let result = match collect_guarded(request, db, file, &params.file, options, || {
+        collector::collect_types(db, file, registry)
+    }) {
+        Ok(result) => result,
+        Err(response) => return response,
+    };
+    collected_files.insert(file);
+
+    let encoder = OffsetEncoder::new(db, file, options.position_encoding);
+    let offset = encoder.decode(params.offset);
+    let mut nodes: Vec<NodeAttribution> = result
+        .nodes
+        .into_iter()
+        .filter(|node| node.start <= offset && offset < node.end)
+        .collect();
+    // Outermost first; a stable sort keeps same-range nodes in source order.
+    nodes.sort_by_key(|node| (node.start, std::cmp::Reverse(node.end)));
+    for node in &mut nodes {
+        node.start = encoder.encode(node.start);
+        node.end = encoder.encode(node.end);
+    }
+
+    // As for `getTypeAtPosition`, types registered by this request count as
+    // sent, so they must be included even when no node references them.
+    let mut types = result.new_types;
+    types.extend(registry.reachable_descriptors(nodes.iter().flat_map(|node| node.type_ids())));
+
+    let response = GetParentsResult { nodes, types };
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_type_at_position<'db>(
+    request: &JsonRpcRequest,
+    db: &'db ProjectDatabase,
+    project_root: &SystemPathBuf,
+    options: SessionOptions,
+    registry: &mut TypeRegistry<'db>,
+    collected_files: &mut HashSet<File>,
+) -> JsonRpcResponse {
+    let params: GetTypeAtPositionParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    let result = match collect_guarded(request, db, file, &params.file, options, || {
+        collector::collect_types(db, file, registry)
+    }) {
+        Ok(result) => result,
+        Err(response) => return response,
+    };
+    collected_files.insert(file);
+
+    let encoder = OffsetEncoder::new(db, file, options.position_encoding);
+    let offset = encoder.decode(params.offset);
+    // Nodes come in source order, so among equally narrow nodes the last one
+    // is the innermost; `min_by_key` keeps the first minimum it sees.
+    let Some(mut node) = result
+        .nodes
+        .into_iter()
+        .rev()
+        .filter(|node| node.type_id.is_some() && node.start <= offset && offset < node.end)
+        .min_by_key(|node| node.end - node.start)
+    else {
+        return JsonRpcResponse::success(request.id.clone(), serde_json::Value::Null);
+    };
+    node.start = encoder.encode(node.start);
+    node.end = encoder.encode(node.end);
+
+    // Types registered by this request count as sent, so they must be
+    // included even when the node doesn't reference them.
+    let mut types = result.new_types;
+    types.extend(registry.reachable_descriptors(node.type_ids()));
+
+    let response = GetTypeAtPositionResult { node, types };
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+/// How many inference threads may outlive their request's timeout. Past
+/// this, requests with a timeout fail right away instead of starting another.
+const MAX_ABANDONED_INFERENCES: usize = 4;
+
+/// Inference threads started by `infer_within` that are still running.
+static RUNNING_INFERENCES: AtomicUsize = AtomicUsize::new(0);
+
+/// Why `infer_within` gave up on a file.
+#[derive(Debug, Clone, Copy)]
+enum InferenceTimeout {
+    /// Inference didn't finish before the timeout.
+    Elapsed,
+    /// `MAX_ABANDONED_INFERENCES` threads are still running, so none was started.
+    Saturated,
+}
+
+/// Counts a running inference thread in `RUNNING_INFERENCES` until dropped,
+/// which also happens when the thread unwinds.
+struct RunningInference;
+
+impl Drop for RunningInference {
+    fn drop(&mut self) {
+        RUNNING_INFERENCES.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Infer `file` on a separate thread, waiting at most `timeout` for it to finish.
+///
+/// Inference results are cached in the database, so collecting types afterwards
+/// is cheap. On timeout the thread is abandoned rather than joined: it keeps
+/// running on its own clone of the database until it finishes, the database
+/// changes (which cancels it), or the process exits, and the session moves on.
+fn infer_within(
+    db: &ProjectDatabase,
+    file: File,
+    timeout: Duration,
+) -> Result<(), InferenceTimeout> {
+    // Only threads abandoned by earlier requests can be running here.
+    let reserved = RUNNING_INFERENCES.fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| {
+        (n < MAX_ABANDONED_INFERENCES).then_some(n + 1)
+    });
+    if reserved.is_err() {
+        return Err(InferenceTimeout::Saturated);
+    }
+
+    let db = db.clone();
+    let (done, finished) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _running = RunningInference;
+        check_types(&db, file);
+        let _ = done.send(());
+    });
+    // A panic drops `done` without sending; the session thread then hits
+    // it again while collecting and reports it.
+    match finished.recv_timeout(timeout) {
+        Err(RecvTimeoutError::Timeout) => Err(InferenceTimeout::Elapsed),
+        _ => Ok(()),
+    }
+}
+
+/// If `result` serializes to more than `max_bytes`, flag it as truncated and
+/// return how many leading `nodes` fit. Returns `None` when it already fits.
+///
+/// `types` is always kept whole: the registry already counts those types as
+/// sent, so later responses won't repeat them.
+fn fit_nodes(
+    result: &mut GetTypesResult,
+    nodes: &[NodeAttribution],
+    encoding: NodeEncoding,
+    max_bytes: usize,
+) -> Option<usize> {
+    if json_len(result) <= max_bytes {
+        return None;
+    }
+    result.truncated = true;
+    result.total_nodes = Some(nodes.len());
+
+    // Everything but the nodes serializes the same however many are kept.
+    let base = json_len(result) - json_len(&result.nodes);
+    let fits = |len: usize| base + json_len(&NodeList::new(&nodes[..len], encoding)) <= max_bytes;
+
+    // Binary search for the longest prefix that fits.
+    let (mut lo, mut hi) = (0, nodes.len());
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        if fits(mid) {
+            lo = mid;
+        } else {
+            hi = mid - 1;
+        }
+    }
+    Some(lo)
+}
+
+/// The length of `value` serialized as compact JSON.
+fn json_len(value: &impl serde::Serialize) -> usize {
+    serde_json::to_vec(value).map_or(0, |json| json.len())
+}
+
+/// Tally references to each type ID from the nodes and the descriptors' component edges.
+fn ref_counts(
+    nodes: &[NodeAttribution],
+    types: &HashMap<TypeId, TypeDescriptor>,
+) -> HashMap<TypeId, u32> {
+    let mut counts = HashMap::new();
+    let node_refs = nodes.iter().flat_map(NodeAttribution::type_ids);
+    let edge_refs = types
+        .values()
+        .flat_map(|desc| desc.edges().into_iter().map(|(_, id)| id));
+    for id in node_refs.chain(edge_refs) {
+        *counts.entry(id).or_insert(0) += 1;
+    }
+    counts
+}
+
+/// Drop the descriptors in `types` that no node references, directly or
+/// through the component edges of other descriptors in `types`.
+fn prune_unreferenced<'a>(
+    nodes: impl IntoIterator<Item = &'a NodeAttribution>,
+    types: &mut HashMap<TypeId, TypeDescriptor>,
+) {
+    let mut reachable: HashSet<TypeId> = HashSet::new();
+    let mut pending: Vec<TypeId> = nodes
+        .into_iter()
+        .flat_map(NodeAttribution::type_ids)
+        .collect();
+    while let Some(id) = pending.pop() {
+        if reachable.insert(id)
+            && let Some(desc) = types.get(&id)
+        {
+            pending.extend(desc.edges().into_iter().map(|(_, id)| id));
+        }
+    }
+    types.retain(|id, _| reachable.contains(id));
+}
+
+/// Group type IDs by descriptor kind, sorted so clients can binary-search them.
+fn kind_index(types: &HashMap<TypeId, TypeDescriptor>) -> BTreeMap<&'static str, Vec<TypeId>> {
+    let mut index: BTreeMap<&'static str, Vec<TypeId>> = BTreeMap::new();
+    for (&id, desc) in types {
+        index.entry(desc.kind()).or_default().push(id);
+    }
+    for ids in index.values_mut() {
+        ids.sort_unstable();
+    }
+    index
+}
+
+fn handle_get_raw_type(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    registry: &TypeRegistry<'_>,
+) -> JsonRpcResponse {
+    let params: GetRawTypeParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let Some(ty) = registry.get_type(params.type_id) else {
+        return JsonRpcResponse::error(
+            request.id.clone(),
+            ErrorCode::InvalidParams,
+            format!("Unknown type {}", params.type_id),
+        );
+    };
+
+    // Attaching the database lets salsa print interned fields rather than bare IDs.
+    let debug = salsa::attach(db, || format!("{ty:?}"));
+    let response = GetRawTypeResult { debug };
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_class_hierarchy<'db>(
+    request: &JsonRpcRequest,
+    db: &'db ProjectDatabase,
+    registry: &mut TypeRegistry<'db>,
+    collected_files: &HashSet<File>,
+) -> JsonRpcResponse {
+    let params: GetClassHierarchyParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let Some(Type::ClassLiteral(class)) = registry.get_type(params.type_id) else {
+        return JsonRpcResponse::error(
+            request.id.clone(),
+            ErrorCode::InvalidParams,
+            format!("Type {} is not a known class literal", params.type_id),
+        );
+    };
+
+    let files: Vec<File> = if params.full {
+        db.project().files(db).iter().copied().collect()
+    } else {
+        collected_files.iter().copied().collect()
+    };
+
+    registry.start_tracking();
+    let hierarchy = hierarchy::class_hierarchy(db, class, files, registry);
+
+    let response = GetClassHierarchyResult {
+        ancestors: hierarchy.ancestors,
+        descendants: hierarchy.descendants,
+        types: registry.drain_new_types(),
+    };
 
     JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
 }
@@ -385,9 +2180,209 @@ fn handle_get_type_registry(
     request: &JsonRpcRequest,
     registry: &TypeRegistry<'_>,
 ) -> JsonRpcResponse {
-    let response = GetTypeRegistryResult {
-        types: registry.all_descriptors(),
+    // Params are optional; `null` or absent means the whole registry.
+    let params: Option<GetTypeRegistryParams> = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
     };
 
+    let types = match params.unwrap_or_default().type_ids {
+        Some(type_ids) => registry.reachable_descriptors(type_ids),
+        None => registry.all_descriptors(),
+    };
+    let response = GetTypeRegistryResult { types };
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_type_by_display(
+    request: &JsonRpcRequest,
+    registry: &TypeRegistry<'_>,
+) -> JsonRpcResponse {
+    let params: GetTypeByDisplayParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let type_ids = registry.find_by_display(&params.display);
+    let types = type_ids
+        .iter()
+        .filter_map(|&id| Some((id, registry.get_descriptor(id)?.clone())))
+        .collect();
+    let response = GetTypeByDisplayResult { type_ids, types };
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_dump_registry_graph(
+    request: &JsonRpcRequest,
+    registry: &TypeRegistry<'_>,
+) -> JsonRpcResponse {
+    let (nodes, edges) = registry.graph();
+    let response = DumpRegistryGraphResult { nodes, edges };
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_version(request: &JsonRpcRequest) -> JsonRpcResponse {
+    JsonRpcResponse::success(
+        request.id.clone(),
+        serde_json::to_value(VersionResult::current()).unwrap(),
+    )
+}
+
+fn handle_healthcheck(request: &JsonRpcRequest, db: &ProjectDatabase) -> JsonRpcResponse {
+    let (resolved, unresolved) = project::healthcheck(db);
+    let response = HealthcheckResult {
+        ok: unresolved.is_empty(),
+        resolved,
+        unresolved,
+    };
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_list_files(request: &JsonRpcRequest, db: &ProjectDatabase) -> JsonRpcResponse {
+    let mut files: Vec<String> = db
+        .project()
+        .files(db)
+        .iter()
+        .filter_map(|file| file.path(db).as_system_path().map(ToString::to_string))
+        .collect();
+    files.sort_unstable();
+
+    JsonRpcResponse::success(
+        request.id.clone(),
+        serde_json::to_value(ListFilesResult { files }).unwrap(),
+    )
+}
+
+fn handle_get_token_types(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    project_root: &SystemPathBuf,
+    position_encoding: PositionEncoding,
+) -> JsonRpcResponse {
+    let params: GetTokenTypesParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    // Use a scratch registry so that types discovered here are still reported
+    // as new by a later `getTypes` call on the session registry.
+    let mut registry = TypeRegistry::new();
+    let result = collector::collect_types(db, file, &mut registry);
+    let source = ruff_db::source::source_text(db, file);
+
+    let mut tokens = tokens::token_types(&result.nodes, &registry, source.as_str());
+    let encoder = OffsetEncoder::new(db, file, position_encoding);
+    for token in &mut tokens {
+        let end = encoder.encode(token.start + token.length);
+        token.start = encoder.encode(token.start);
+        token.length = end - token.start;
+    }
+
+    let response = GetTokenTypesResult { tokens };
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_dependencies(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    project_root: &SystemPathBuf,
+) -> JsonRpcResponse {
+    let params: GetDependenciesParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    let response = imports::dependencies(db, file, project_root, params.depth);
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_coverage(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    project_root: &SystemPathBuf,
+) -> JsonRpcResponse {
+    let params: GetCoverageParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    // Like `getTokenTypes`, use a scratch registry to leave the session's alone.
+    let mut registry = TypeRegistry::new();
+    let result = collector::collect_types(db, file, &mut registry);
+    let response = coverage::coverage(&result.nodes, &registry);
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_diagnostics(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    project_root: &SystemPathBuf,
+    position_encoding: PositionEncoding,
+) -> JsonRpcResponse {
+    let params: GetDiagnosticsParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    let encoder = OffsetEncoder::new(db, file, position_encoding);
+    let response = GetDiagnosticsResult {
+        diagnostics: diagnostics::diagnostics(db, file, &encoder),
+    };
+
+    JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
+}
+
+fn handle_get_imports(
+    request: &JsonRpcRequest,
+    db: &ProjectDatabase,
+    project_root: &SystemPathBuf,
+    position_encoding: PositionEncoding,
+) -> JsonRpcResponse {
+    let params: GetImportsParams = match parse_params(request) {
+        Ok(p) => p,
+        Err(response) => return response,
+    };
+
+    let file = match resolve_file(request, db, project_root, &params.file) {
+        Ok(f) => f,
+        Err(response) => return response,
+    };
+
+    let mut imports = imports::collect_imports(db, file);
+    let encoder = OffsetEncoder::new(db, file, position_encoding);
+    for import in &mut imports {
+        import.start = encoder.encode(import.start);
+        import.end = encoder.encode(import.end);
+    }
+
+    let response = GetImportsResult { imports };
+
     JsonRpcResponse::success(request.id.clone(), serde_json::to_value(response).unwrap())
 }
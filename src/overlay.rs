@@ -0,0 +1,135 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use ruff_db::file_revision::FileRevision;
+use ruff_db::system::{
+    CaseSensitivity, DirectoryEntry, FileType, GlobError, Metadata, OsSystem, PatternError, Result,
+    System, SystemPath, SystemPathBuf, SystemVirtualPath, WalkDirectoryBuilder, WritableSystem,
+};
+use ruff_notebook::{Notebook, NotebookError};
+
+/// The OS file system, except that one file reads as in-memory source, whether
+/// or not it exists on disk.
+///
+/// Used by `--stdin`, so the piped source is inferred at its `--as` path
+/// inside the real project and resolves imports from its neighbours.
+#[derive(Debug, Clone)]
+pub struct OverlaySystem {
+    os: OsSystem,
+    path: SystemPathBuf,
+    source: Arc<str>,
+}
+
+impl OverlaySystem {
+    pub fn new(os: OsSystem, path: SystemPathBuf, source: &str) -> Self {
+        Self {
+            os,
+            path,
+            source: source.into(),
+        }
+    }
+
+    fn is_overlaid(&self, path: &SystemPath) -> bool {
+        path == self.path
+    }
+}
+
+impl System for OverlaySystem {
+    fn path_metadata(&self, path: &SystemPath) -> Result<Metadata> {
+        if self.is_overlaid(path) {
+            // The source never changes, so any fixed revision will do.
+            return Ok(Metadata::new(FileRevision::new(1), None, FileType::File));
+        }
+        self.os.path_metadata(path)
+    }
+
+    fn canonicalize_path(&self, path: &SystemPath) -> Result<SystemPathBuf> {
+        if self.is_overlaid(path) {
+            return Ok(path.to_path_buf());
+        }
+        self.os.canonicalize_path(path)
+    }
+
+    fn read_to_string(&self, path: &SystemPath) -> Result<String> {
+        if self.is_overlaid(path) {
+            return Ok(self.source.to_string());
+        }
+        self.os.read_to_string(path)
+    }
+
+    fn read_to_notebook(&self, path: &SystemPath) -> std::result::Result<Notebook, NotebookError> {
+        self.os.read_to_notebook(path)
+    }
+
+    fn read_virtual_path_to_string(&self, path: &SystemVirtualPath) -> Result<String> {
+        self.os.read_virtual_path_to_string(path)
+    }
+
+    fn read_virtual_path_to_notebook(
+        &self,
+        path: &SystemVirtualPath,
+    ) -> std::result::Result<Notebook, NotebookError> {
+        self.os.read_virtual_path_to_notebook(path)
+    }
+
+    fn path_exists_case_sensitive(&self, path: &SystemPath, prefix: &SystemPath) -> bool {
+        self.is_overlaid(path) || self.os.path_exists_case_sensitive(path, prefix)
+    }
+
+    fn case_sensitivity(&self) -> CaseSensitivity {
+        self.os.case_sensitivity()
+    }
+
+    fn current_directory(&self) -> &SystemPath {
+        self.os.current_directory()
+    }
+
+    fn user_config_directory(&self) -> Option<SystemPathBuf> {
+        self.os.user_config_directory()
+    }
+
+    fn cache_dir(&self) -> Option<SystemPathBuf> {
+        self.os.cache_dir()
+    }
+
+    fn read_directory<'a>(
+        &'a self,
+        path: &SystemPath,
+    ) -> Result<Box<dyn Iterator<Item = Result<DirectoryEntry>> + 'a>> {
+        self.os.read_directory(path)
+    }
+
+    fn walk_directory(&self, path: &SystemPath) -> WalkDirectoryBuilder {
+        self.os.walk_directory(path)
+    }
+
+    fn glob(
+        &self,
+        pattern: &str,
+    ) -> std::result::Result<
+        Box<dyn Iterator<Item = std::result::Result<SystemPathBuf, GlobError>> + '_>,
+        PatternError,
+    > {
+        self.os.glob(pattern)
+    }
+
+    fn as_writable(&self) -> Option<&dyn WritableSystem> {
+        None
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn dyn_clone(&self) -> Box<dyn System> {
+        Box::new(self.clone())
+    }
+
+    fn env_var(&self, name: &str) -> std::result::Result<String, std::env::VarError> {
+        self.os.env_var(name)
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{Scope, ScopedJoinHandle};
+
+use ty_project::ProjectDatabase;
+
+/// A fixed-size pool of threads for jobs that only run read-only queries.
+///
+/// Each job carries its own database clone: Salsa allows clones to query
+/// concurrently, and because no clone outlives its job, an idle pool never
+/// blocks a later write to the database.
+pub struct WorkerPool<'scope, J> {
+    sender: Option<Sender<(ProjectDatabase, J)>>,
+    handles: Vec<ScopedJoinHandle<'scope, ()>>,
+}
+
+impl<'scope, J: Send + 'scope> WorkerPool<'scope, J> {
+    pub fn spawn<'env, F>(
+        scope: &'scope Scope<'scope, 'env>,
+        workers: usize,
+        handle: &'env F,
+    ) -> Self
+    where
+        F: Fn(&ProjectDatabase, J) + Sync,
+    {
+        let (sender, receiver) = mpsc::channel::<(ProjectDatabase, J)>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let handles = (0..workers)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                scope.spawn(move || {
+                    loop {
+                        let job = receiver.lock().unwrap().recv();
+                        let Ok((db, job)) = job else {
+                            return;
+                        };
+                        handle(&db, job);
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            sender: Some(sender),
+            handles,
+        }
+    }
+
+    /// Queue a job to run against a clone of `db`.
+    pub fn submit(&self, db: &ProjectDatabase, job: J) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send((db.clone(), job));
+        }
+    }
+
+    /// Wait for all queued jobs to finish and stop the workers.
+    pub fn finish(mut self) {
+        self.sender = None;
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use ruff_db::files::File;
+use ruff_db::source::{SourceText, line_index, source_text};
+use ruff_text_size::{TextRange, TextSize};
+use serde::Deserialize;
+use ty_python_semantic::Db;
+
+/// The unit in which emitted offsets are counted, negotiated at `initialize`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PositionEncoding {
+    /// UTF-8 bytes (the native offset unit of ty's ranges).
+    #[default]
+    Utf8,
+    /// UTF-16 code units, as used by most LSP clients.
+    Utf16,
+    /// Unicode scalar values.
+    Utf32,
+}
+
+impl PositionEncoding {
+    fn len(self, text: &str) -> u32 {
+        match self {
+            Self::Utf8 => text.len() as u32,
+            Self::Utf16 => text.encode_utf16().count() as u32,
+            Self::Utf32 => text.chars().count() as u32,
+        }
+    }
+}
+
+/// Converts UTF-8 byte offsets within a single file into a `PositionEncoding`.
+pub struct OffsetEncoder {
+    encoding: PositionEncoding,
+    source: SourceText,
+    /// Byte offset of each line start, paired with its offset in `encoding`.
+    line_starts: Vec<(TextSize, u32)>,
+}
+
+impl OffsetEncoder {
+    pub fn new(db: &dyn Db, file: File, encoding: PositionEncoding) -> Self {
+        let source = source_text(db, file);
+
+        let mut line_starts = Vec::new();
+        if encoding != PositionEncoding::Utf8 {
+            let mut previous = TextSize::default();
+            let mut units = 0;
+            for &start in line_index(db, file).line_starts() {
+                units += encoding.len(&source[TextRange::new(previous, start)]);
+                line_starts.push((start, units));
+                previous = start;
+            }
+        }
+
+        Self {
+            encoding,
+            source,
+            line_starts,
+        }
+    }
+
+    /// Convert a UTF-8 byte offset into this encoder's unit.
+    pub fn encode(&self, offset: u32) -> u32 {
+        if self.encoding == PositionEncoding::Utf8 {
+            return offset;
+        }
+
+        let offset = TextSize::from(offset);
+        let line = self
+            .line_starts
+            .partition_point(|&(start, _)| start <= offset)
+            .saturating_sub(1);
+        let (line_start, units) = self.line_starts[line];
+        units
+            + self
+                .encoding
+                .len(&self.source[TextRange::new(line_start, offset)])
+    }
+
+    /// Convert an offset in this encoder's unit back into a UTF-8 byte offset.
+    /// An offset inside a character maps to that character's start.
+    pub fn decode(&self, offset: u32) -> u32 {
+        if self.encoding == PositionEncoding::Utf8 {
+            return offset;
+        }
+
+        let line = self
+            .line_starts
+            .partition_point(|&(_, units)| units <= offset)
+            .saturating_sub(1);
+        let (line_start, units) = self.line_starts[line];
+        let mut remaining = offset - units;
+        let mut byte = line_start;
+        for c in self.source[usize::from(line_start)..].chars() {
+            let width = self.encoding.len(c.encode_utf8(&mut [0; 4]));
+            if width > remaining {
+                break;
+            }
+            remaining -= width;
+            byte += TextSize::of(c);
+        }
+        byte.into()
+    }
+}
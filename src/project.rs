@@ -1,8 +1,53 @@
 use anyhow::Context;
 use ruff_db::system::{OsSystem, SystemPath, SystemPathBuf};
+use ty_module_resolver::{KnownModule, SearchPathValidationError};
+use ty_project::metadata::options::{EnvironmentOptions, Options, ProjectOptionsOverrides};
+use ty_project::metadata::value::RelativePathBuf;
 use ty_project::{ProjectDatabase, ProjectMetadata};
+use ty_python_semantic::Db;
+use ty_python_semantic::place::known_module_symbol;
+use ty_python_semantic::types::KnownClass;
+use ty_site_packages::SitePackagesDiscoveryError;
 
-pub fn create_database(project_root: &str) -> anyhow::Result<ProjectDatabase> {
+use crate::overlay::OverlaySystem;
+
+/// The project's Python environment — interpreter, site-packages, or typeshed —
+/// could not be resolved, so inference would yield `Unknown` everywhere.
+#[derive(Debug)]
+pub struct EnvironmentError;
+
+impl std::fmt::Display for EnvironmentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Failed to resolve the Python environment")
+    }
+}
+
+impl std::error::Error for EnvironmentError {}
+
+/// Create a database for the project at `project_root`.
+///
+/// Fails with an [`EnvironmentError`] in the error chain when the Python
+/// environment can't be resolved: the interpreter or its site-packages, the
+/// module search paths, or `builtins` in typeshed. Other failures, such as an
+/// invalid configuration file, don't carry it.
+///
+/// `extra_paths` are appended to the module search path, after any configured in
+/// the project's own `ty.toml` / `pyproject.toml`. Relative entries are resolved
+/// against the project root.
+pub fn create_database(
+    project_root: &str,
+    extra_paths: &[String],
+) -> anyhow::Result<ProjectDatabase> {
+    create_database_with_overlay(project_root, extra_paths, None)
+}
+
+/// Like [`create_database`], but with `overlay` (an absolute path and its
+/// source) read from memory instead of disk. See [`OverlaySystem`].
+pub fn create_database_with_overlay(
+    project_root: &str,
+    extra_paths: &[String],
+    overlay: Option<(SystemPathBuf, &str)>,
+) -> anyhow::Result<ProjectDatabase> {
     let path = SystemPathBuf::from_path_buf(std::path::PathBuf::from(project_root))
         .map_err(|p| anyhow::anyhow!("Non-Unicode path: {}", p.display()))?;
 
@@ -16,5 +61,73 @@ pub fn create_database(project_root: &str) -> anyhow::Result<ProjectDatabase> {
         .apply_configuration_files(&system)
         .context("Failed to apply configuration files")?;
 
-    ProjectDatabase::fallible(metadata, system).context("Failed to create project database")
+    if !extra_paths.is_empty() {
+        let extra_paths = extra_paths
+            .iter()
+            .map(|p| RelativePathBuf::cli(SystemPath::absolute(p, &path)))
+            .collect();
+        let options = Options {
+            environment: Some(EnvironmentOptions {
+                extra_paths: Some(extra_paths),
+                ..EnvironmentOptions::default()
+            }),
+            ..Options::default()
+        };
+        metadata.apply_overrides(&ProjectOptionsOverrides::new(None, options));
+    }
+
+    let db = match overlay {
+        Some((overlay_path, source)) => {
+            ProjectDatabase::fallible(metadata, OverlaySystem::new(system, overlay_path, source))
+        }
+        None => ProjectDatabase::fallible(metadata, system),
+    }
+    .map_err(|e| {
+        if is_environment_failure(&e) {
+            e.context(EnvironmentError)
+        } else {
+            e.context("Failed to create the project database")
+        }
+    })?;
+
+    // Without `builtins`, even `object` is unresolvable and every type is `Unknown`.
+    if KnownClass::Object.to_class_literal(&db).is_unknown() {
+        return Err(
+            anyhow::anyhow!("`builtins` could not be resolved from typeshed")
+                .context(EnvironmentError),
+        );
+    }
+
+    Ok(db)
+}
+
+/// Whether `error` comes from resolving the Python interpreter and its
+/// site-packages, or from validating the module search paths.
+fn is_environment_failure(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.is::<SitePackagesDiscoveryError>() || cause.is::<SearchPathValidationError>()
+    })
+}
+
+/// Core symbols that every working environment resolves.
+const HEALTHCHECK_SYMBOLS: &[(KnownModule, &str)] = &[
+    (KnownModule::Builtins, "int"),
+    (KnownModule::Builtins, "str"),
+    (KnownModule::Typing, "Any"),
+];
+
+/// Try to resolve each of [`HEALTHCHECK_SYMBOLS`], returning the qualified names
+/// that resolved and those that didn't.
+pub fn healthcheck(db: &dyn Db) -> (Vec<String>, Vec<String>) {
+    let mut resolved = Vec::new();
+    let mut unresolved = Vec::new();
+    for &(module, symbol) in HEALTHCHECK_SYMBOLS {
+        let name = format!("{}.{symbol}", module.as_str());
+        if known_module_symbol(db, module, symbol).place.is_undefined() {
+            unresolved.push(name);
+        } else {
+            resolved.push(name);
+        }
+    }
+    (resolved, unresolved)
 }
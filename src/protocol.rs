@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::position::PositionEncoding;
 
 // ─── JSON-RPC envelope ───────────────────────────────────────────────
 
@@ -10,7 +12,19 @@ pub struct JsonRpcRequest {
     pub method: String,
     #[serde(default)]
     pub params: serde_json::Value,
-    pub id: serde_json::Value,
+    /// Absent for notifications, which get no response. An explicit `null`
+    /// is `Some(Value::Null)` and still gets one.
+    #[serde(default, deserialize_with = "present")]
+    pub id: Option<serde_json::Value>,
+}
+
+/// Deserialize a field that is present, even as `null`, to `Some`; absent
+/// fields fall back to `None` through `#[serde(default)]`.
+fn present<'de, D>(deserializer: D) -> Result<Option<serde_json::Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    serde_json::Value::deserialize(deserializer).map(Some)
 }
 
 #[derive(Debug, Serialize)]
@@ -20,7 +34,16 @@ pub struct JsonRpcResponse {
     pub result: Option<serde_json::Value>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<JsonRpcError>,
-    pub id: serde_json::Value,
+    /// `None` answers a notification, so the response is never written.
+    pub id: Option<serde_json::Value>,
+}
+
+/// A message the server sends unprompted, such as `typesChanged`.
+#[derive(Debug, Serialize)]
+pub struct JsonRpcNotification {
+    pub jsonrpc: &'static str,
+    pub method: &'static str,
+    pub params: serde_json::Value,
 }
 
 #[derive(Debug, Serialize)]
@@ -29,8 +52,34 @@ pub struct JsonRpcError {
     pub message: String,
 }
 
+/// Error codes sent in `JsonRpcError::code`. Besides the standard JSON-RPC
+/// codes, domain failures get their own code in the server-error range
+/// (`-32000` to `-32099`) so clients can tell them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    ParseError = -32700,
+    MethodNotFound = -32601,
+    InvalidParams = -32602,
+    /// Any failure without a more specific code below.
+    ServerError = -32000,
+    /// The project's Python environment could not be resolved.
+    EnvironmentUnresolved = -32001,
+    /// A requested file does not exist or could not be read.
+    FileNotFound = -32002,
+    /// A path is not valid Unicode.
+    NonUnicodePath = -32003,
+    /// A session method was called before `initialize`.
+    NotInitialized = -32004,
+    /// ty panicked while inferring types for a file.
+    InferenceFailed = -32005,
+    /// `initialize` was called on an already initialized session.
+    AlreadyInitialized = -32006,
+    /// Inference took longer than the session's `requestTimeoutMs`.
+    RequestTimeout = -32007,
+}
+
 impl JsonRpcResponse {
-    pub fn success(id: serde_json::Value, result: serde_json::Value) -> Self {
+    pub fn success(id: Option<serde_json::Value>, result: serde_json::Value) -> Self {
         Self {
             jsonrpc: "2.0",
             result: Some(result),
@@ -39,11 +88,14 @@ impl JsonRpcResponse {
         }
     }
 
-    pub fn error(id: serde_json::Value, code: i64, message: String) -> Self {
+    pub fn error(id: Option<serde_json::Value>, code: ErrorCode, message: String) -> Self {
         Self {
             jsonrpc: "2.0",
             result: None,
-            error: Some(JsonRpcError { code, message }),
+            error: Some(JsonRpcError {
+                code: code as i64,
+                message,
+            }),
             id,
         }
     }
@@ -55,20 +107,207 @@ impl JsonRpcResponse {
 #[serde(rename_all = "camelCase")]
 pub struct InitializeParams {
     pub project_root: String,
+    #[serde(default)]
+    pub extra_paths: Vec<String>,
+    #[serde(default)]
+    pub position_encoding: PositionEncoding,
+    /// Upper bound on the serialized size of a `getTypes` result; larger
+    /// results have their `nodes` truncated.
+    #[serde(default)]
+    pub max_response_bytes: Option<usize>,
+    /// Files larger than this many bytes are skipped by `getTypes` rather
+    /// than inferred. Overrides `--max-file-size`.
+    #[serde(default)]
+    pub max_file_size: Option<usize>,
+    /// How long a request that collects a file's types may spend inferring it
+    /// before it fails with [`ErrorCode::RequestTimeout`].
+    #[serde(default)]
+    pub request_timeout_ms: Option<u64>,
+    /// How descriptor `display` strings render unions, for the whole session.
+    #[serde(default)]
+    pub union_display_style: UnionDisplayStyle,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetTypesParams {
     pub file: String,
+    #[serde(flatten)]
+    pub types: TypeOptions,
+    #[serde(default)]
+    pub include_ref_counts: bool,
+    #[serde(default)]
+    pub include_index: bool,
+    #[serde(default)]
+    pub include_line_index: bool,
+    #[serde(default)]
+    pub node_encoding: NodeEncoding,
+}
+
+/// The `getTypes` params that decide which nodes and descriptors are
+/// collected and returned, shared with `getTypesBatch`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TypeOptions {
+    #[serde(default = "default_true")]
+    pub include_display: bool,
+    /// Descriptor kinds that keep their `display` when `include_display` is
+    /// off.
+    #[serde(default)]
+    pub keep_display_for: Vec<String>,
+    #[serde(default)]
+    pub types_only: bool,
+    #[serde(default)]
+    pub exclude_stdlib: bool,
+    /// Drop descriptors that no returned node references, directly or through
+    /// other descriptors.
+    #[serde(default)]
+    pub prune_unreferenced: bool,
+}
+
+/// How `getTypes` lays out its `nodes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NodeEncoding {
+    /// One `NodeAttribution` object per node.
+    #[default]
+    Objects,
+    /// A `ColumnarNodes` object of parallel arrays.
+    Columnar,
+}
+
+/// How `display` strings render unions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnionDisplayStyle {
+    /// `int | None`, `int | str`
+    #[default]
+    Pep604,
+    /// `Optional[int]`, `Union[int, str]`
+    Typing,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypeRegistryParams {
+    /// Only return these types and the types they reference, transitively.
+    #[serde(default)]
+    pub type_ids: Option<Vec<TypeId>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypeByDisplayParams {
+    pub display: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRawTypeParams {
+    pub type_id: TypeId,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetClassHierarchyParams {
+    pub type_id: TypeId,
+    /// Search the whole project for descendants instead of only the files
+    /// collected so far this session.
+    #[serde(default)]
+    pub full: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTokenTypesParams {
+    pub file: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCoverageParams {
+    pub file: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDependenciesParams {
+    pub file: String,
+    /// How many levels of imports to follow; `1` is direct imports only.
+    /// Unlimited when absent.
+    #[serde(default)]
+    pub depth: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypesForScopeParams {
+    pub file: String,
+    /// An offset, in the session's position encoding, inside the function or
+    /// class definition to collect.
+    pub offset: u32,
     #[serde(default = "default_true")]
     pub include_display: bool,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypesBatchParams {
+    pub files: Vec<String>,
+    #[serde(flatten)]
+    pub types: TypeOptions,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetParentsParams {
+    pub file: String,
+    /// An offset in the session's position encoding.
+    pub offset: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypeAtPositionParams {
+    pub file: String,
+    /// An offset in the session's position encoding.
+    pub offset: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetImportsParams {
+    pub file: String,
+}
+
+/// The `file` param that every single-file method takes.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileParams {
+    pub file: String,
+}
+
+/// Params of both `subscribe` and `unsubscribe`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubscribeParams {
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDiagnosticsParams {
+    pub file: String,
+}
+
 fn default_true() -> bool {
     true
 }
 
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
 // ─── Response payloads ───────────────────────────────────────────────
 
 #[derive(Debug, Serialize)]
@@ -76,10 +315,150 @@ pub struct InitializeResult {
     pub ok: bool,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionResult {
+    pub version: &'static str,
+    pub ty_version: &'static str,
+    pub ruff_version: &'static str,
+    pub ruff_commit: &'static str,
+    pub typeshed_revision: &'static str,
+}
+
+impl VersionResult {
+    /// Versions of this crate and of the `ruff` checkout it was built against.
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            ty_version: env!("TY_TYPES_TY_VERSION"),
+            ruff_version: env!("TY_TYPES_RUFF_VERSION"),
+            ruff_commit: env!("TY_TYPES_RUFF_COMMIT"),
+            typeshed_revision: env!("TY_TYPES_TYPESHED_REV"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ListFilesResult {
+    /// Absolute paths, sorted.
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthcheckResult {
+    /// Whether every checked symbol resolved.
+    pub ok: bool,
+    pub resolved: Vec<String>,
+    pub unresolved: Vec<String>,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GetTypesResult {
+    pub nodes: NodeList,
+    pub types: HashMap<TypeId, TypeDescriptor>,
+    /// How often each type ID is referenced by the nodes and descriptors in this
+    /// response. Only present when requested via `includeRefCounts`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ref_counts: Option<HashMap<TypeId, u32>>,
+    /// The IDs in `types` grouped by descriptor kind, each list sorted
+    /// ascending. Only present when requested via `includeIndex`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<BTreeMap<&'static str, Vec<TypeId>>>,
+    /// Set when `nodes` was cut short to fit `maxResponseBytes`.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+    /// The number of nodes before truncation. Only present when `truncated`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_nodes: Option<usize>,
+    /// Set when the file exceeds `maxFileSize` and was not inferred; `nodes`
+    /// and `types` are then empty.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub skipped_too_large: bool,
+    /// The offset of each line start, in the session's position encoding.
+    /// Only present when requested via `includeLineIndex`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_starts: Option<Vec<u32>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypeByDisplayResult {
+    /// Matching type IDs, ascending.
+    pub type_ids: Vec<TypeId>,
+    /// The descriptor of each matching type.
+    pub types: HashMap<TypeId, TypeDescriptor>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRawTypeResult {
+    /// ty's `Debug` representation of the type.
+    pub debug: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypesForScopeResult {
+    /// Range of the definition that was collected.
+    pub start: u32,
+    pub end: u32,
+    pub nodes: Vec<NodeAttribution>,
+    /// Descriptors for types first registered by this request.
+    pub types: HashMap<TypeId, TypeDescriptor>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypesBatchResult {
+    /// One entry per requested file, keyed by the path as given.
+    pub results: BTreeMap<String, BatchFileEntry>,
+    /// Descriptors for every type first registered by this request.
+    pub types: HashMap<TypeId, TypeDescriptor>,
+}
+
+/// The outcome of one file of a `getTypesBatch` request.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum BatchFileEntry {
+    #[serde(rename_all = "camelCase")]
+    Collected {
+        nodes: Vec<NodeAttribution>,
+        /// The IDs in `types` this file introduced, ascending: those not
+        /// sent before and not introduced by an earlier file of the batch.
+        new_type_ids: Vec<TypeId>,
+    },
+    Failed {
+        error: JsonRpcError,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetParentsResult {
+    /// The nodes containing the offset, outermost first.
     pub nodes: Vec<NodeAttribution>,
+    /// Descriptors for types first registered by this request.
+    pub types: HashMap<TypeId, TypeDescriptor>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTypeAtPositionResult {
+    /// The narrowest typed node containing the offset.
+    pub node: NodeAttribution,
+    /// Descriptors for every type the node references, transitively, plus any
+    /// first registered by this request.
+    pub types: HashMap<TypeId, TypeDescriptor>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetClassHierarchyResult {
+    pub ancestors: Vec<TypeId>,
+    pub descendants: Vec<TypeId>,
+    /// Descriptors for types first registered by this request.
     pub types: HashMap<TypeId, TypeDescriptor>,
 }
 
@@ -89,6 +468,131 @@ pub struct GetTypeRegistryResult {
     pub types: HashMap<TypeId, TypeDescriptor>,
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetTokenTypesResult {
+    pub tokens: Vec<SemanticToken>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetCoverageResult {
+    pub expressions: CoverageCount,
+    pub parameters: CoverageCount,
+    pub returns: CoverageCount,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDiagnosticsResult {
+    pub diagnostics: Vec<DiagnosticInfo>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticInfo {
+    /// Omitted for diagnostics about the file as a whole.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub range: Option<DiagnosticRange>,
+    /// `error`, `warning` or `info`.
+    pub severity: &'static str,
+    /// The lint or error ID, e.g. `unresolved-import`.
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiagnosticRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CoverageCount {
+    pub covered: u32,
+    pub total: u32,
+    /// `covered / total` as a percentage; `100` when `total` is zero.
+    pub percent: f64,
+}
+
+/// One classified identifier, in the spirit of LSP semantic tokens.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SemanticToken {
+    pub start: u32,
+    pub length: u32,
+    /// `variable`, `parameter`, `function`, `method`, `class`, `module`,
+    /// `typeParameter` or `type`.
+    pub token_type: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub modifiers: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetImportsResult {
+    pub imports: Vec<ImportInfo>,
+}
+
+/// One import edge: an `import` alias or a `from ... import` statement.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportInfo {
+    /// Byte range of the import statement.
+    pub start: u32,
+    pub end: u32,
+    /// Absolute module name; relative imports are resolved against the
+    /// importing file's package. Falls back to the module as written.
+    pub module: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved_path: Option<String>,
+    /// Names imported by a `from ... import` statement; empty for `import`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub names: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub unresolved: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetDependenciesResult {
+    /// Project files reached, starting with the requested file itself.
+    pub files: Vec<String>,
+    /// Imported modules outside the project (standard library and
+    /// third-party), which are not followed.
+    pub external: Vec<String>,
+    /// Imported modules that could not be resolved.
+    pub unresolved: Vec<String>,
+}
+
+/// The registry as a graph: one node per type, one edge per component reference.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DumpRegistryGraphResult {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphNode {
+    pub id: TypeId,
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphEdge {
+    pub from: TypeId,
+    pub to: TypeId,
+    /// The descriptor field the reference comes from, e.g. `member`,
+    /// `parameter`, `return`, `class`.
+    pub label: &'static str,
+}
+
 /// CLI one-shot output: nodes grouped by file, shared type registry.
 #[derive(Debug, Serialize)]
 pub struct CliResult {
@@ -96,11 +600,25 @@ pub struct CliResult {
     pub types: HashMap<TypeId, TypeDescriptor>,
 }
 
+/// One entry of a `--requests` batch file.
+#[derive(Debug, Deserialize)]
+pub struct BatchRequest {
+    pub file: String,
+}
+
+/// Batch output for one request: its nodes plus the types first seen in it.
+#[derive(Debug, Serialize)]
+pub struct BatchResult {
+    pub file: String,
+    pub nodes: Vec<NodeAttribution>,
+    pub types: HashMap<TypeId, TypeDescriptor>,
+}
+
 // ─── Node attribution ────────────────────────────────────────────────
 
 pub type TypeId = u32;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct NodeAttribution {
     pub start: u32,
@@ -110,6 +628,136 @@ pub struct NodeAttribution {
     pub type_id: Option<TypeId>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub call_signature: Option<CallSignatureInfo>,
+    /// What a name refers to, from its type: `module`, `class`, `function` or
+    /// `variable`. Only set on `ExprName` and `Alias` nodes with a type.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub symbol_kind: Option<&'static str>,
+    /// `false` when ty fell back to `Unknown` for the node, e.g. an unannotated
+    /// parameter or an unresolved import, rather than inferring a type.
+    #[serde(skip_serializing_if = "is_true")]
+    pub inferred: bool,
+    /// On `assert_type(value, T)` calls, whether ty accepts the assertion.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assertion_holds: Option<bool>,
+    /// On `reveal_type(value)` calls, the type ty reveals.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revealed_type_id: Option<TypeId>,
+}
+
+impl NodeAttribution {
+    /// All type IDs this node references: its own type, its revealed type,
+    /// and those of its call signature.
+    pub fn type_ids(&self) -> impl Iterator<Item = TypeId> + '_ {
+        let signature = self.call_signature.iter().flat_map(|sig| {
+            sig.parameters
+                .iter()
+                .flat_map(|p| {
+                    p.type_id
+                        .into_iter()
+                        .chain(p.default_type_id)
+                        .chain(p.param_spec_id)
+                })
+                .chain(sig.return_type_id)
+                .chain(sig.type_arguments.iter().copied())
+        });
+        self.type_id
+            .into_iter()
+            .chain(self.revealed_type_id)
+            .chain(signature)
+    }
+}
+
+/// The `nodes` of a `getTypes` result, in the requested `NodeEncoding`.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum NodeList {
+    Objects(Vec<NodeAttribution>),
+    Columnar(ColumnarNodes),
+}
+
+impl NodeList {
+    pub fn new(nodes: &[NodeAttribution], encoding: NodeEncoding) -> Self {
+        match encoding {
+            NodeEncoding::Objects => Self::Objects(nodes.to_vec()),
+            NodeEncoding::Columnar => Self::Columnar(ColumnarNodes::new(nodes)),
+        }
+    }
+}
+
+/// Nodes as parallel arrays: node `i` is described by entry `i` of `starts`,
+/// `ends`, `kinds` and `typeIds`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnarNodes {
+    /// Each distinct node kind once, in order of first appearance.
+    pub kind_table: Vec<Cow<'static, str>>,
+    pub starts: Vec<u32>,
+    pub ends: Vec<u32>,
+    /// Indices into `kind_table`.
+    pub kinds: Vec<u32>,
+    /// `0` for nodes without a type; real type IDs start at `1`.
+    pub type_ids: Vec<TypeId>,
+    /// Call signatures keyed by node index, for the nodes that have one.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub call_signatures: BTreeMap<usize, CallSignatureInfo>,
+    /// Symbol kinds keyed by node index, for the nodes that have one.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub symbol_kinds: BTreeMap<usize, &'static str>,
+    /// Indices of the nodes whose `inferred` is `false`, ascending.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub uninferred: Vec<usize>,
+    /// `assertion_holds` keyed by node index, for `assert_type` calls.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub assertion_holds: BTreeMap<usize, bool>,
+    /// `revealed_type_id` keyed by node index, for `reveal_type` calls.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub revealed_type_ids: BTreeMap<usize, TypeId>,
+}
+
+impl ColumnarNodes {
+    fn new(nodes: &[NodeAttribution]) -> Self {
+        let mut columns = Self {
+            kind_table: Vec::new(),
+            starts: Vec::with_capacity(nodes.len()),
+            ends: Vec::with_capacity(nodes.len()),
+            kinds: Vec::with_capacity(nodes.len()),
+            type_ids: Vec::with_capacity(nodes.len()),
+            call_signatures: BTreeMap::new(),
+            symbol_kinds: BTreeMap::new(),
+            uninferred: Vec::new(),
+            assertion_holds: BTreeMap::new(),
+            revealed_type_ids: BTreeMap::new(),
+        };
+        let mut kind_indices: HashMap<&str, u32> = HashMap::new();
+        for (i, node) in nodes.iter().enumerate() {
+            let kind = *kind_indices
+                .entry(node.node_kind.as_ref())
+                .or_insert_with(|| {
+                    columns.kind_table.push(node.node_kind.clone());
+                    columns.kind_table.len() as u32 - 1
+                });
+            columns.starts.push(node.start);
+            columns.ends.push(node.end);
+            columns.kinds.push(kind);
+            columns.type_ids.push(node.type_id.unwrap_or(0));
+            if let Some(signature) = &node.call_signature {
+                columns.call_signatures.insert(i, signature.clone());
+            }
+            if let Some(symbol_kind) = node.symbol_kind {
+                columns.symbol_kinds.insert(i, symbol_kind);
+            }
+            if !node.inferred {
+                columns.uninferred.push(i);
+            }
+            if let Some(holds) = node.assertion_holds {
+                columns.assertion_holds.insert(i, holds);
+            }
+            if let Some(revealed) = node.revealed_type_id {
+                columns.revealed_type_ids.insert(i, revealed);
+            }
+        }
+        columns
+    }
 }
 
 // ─── Call signature info ─────────────────────────────────────────────
@@ -121,15 +769,27 @@ pub struct CallSignatureInfo {
     pub return_type_id: Option<TypeId>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub type_arguments: Vec<TypeId>,
+    /// Index of the overload selected for this call. Only set when the
+    /// callee is overloaded and an overload matched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_overload: Option<usize>,
+    /// `false` when the arguments match no overload (or, for a plain
+    /// function, don't match its signature). The signature is then a best
+    /// effort: the first overload's.
+    #[serde(skip_serializing_if = "is_true")]
+    pub matched: bool,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ParameterInfo {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub type_id: Option<TypeId>,
     pub kind: &'static str,
+    /// Whether the parameter has an explicit annotation, as opposed to none
+    /// (inferred as `Unknown`).
+    pub annotated: bool,
     pub has_default: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_type_id: Option<TypeId>,
@@ -141,18 +801,31 @@ pub struct ParameterInfo {
     /// carrying the name of that `ParamSpec` (e.g. `"P"`).
     #[serde(skip_serializing_if = "Option::is_none")]
     pub param_spec_name: Option<String>,
+    /// Type ID of the `typeVar` descriptor of that `ParamSpec`, alongside
+    /// `param_spec_name`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub param_spec_id: Option<TypeId>,
+    /// Set on the parameter narrowed by a `TypeIs` / `TypeGuard` return type.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub narrowed: bool,
+    /// Byte offset of the parameter in its defining `def`. Only set on
+    /// `function` / `boundMethod` descriptors, never on call signatures.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<u32>,
 }
 
 // ─── Structured type details ─────────────────────────────────────────
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ClassMemberInfo {
     pub name: String,
     pub type_id: TypeId,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TypedDictFieldInfo {
     pub name: String,
@@ -163,7 +836,7 @@ pub struct TypedDictFieldInfo {
 
 /// PEP 728 `extra_items=` policy: values of undeclared keys are exposed with this
 /// declared type and mutability. Present only when explicitly declared.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct TypedDictExtraItemsInfo {
     pub type_id: TypeId,
@@ -172,7 +845,7 @@ pub struct TypedDictExtraItemsInfo {
 
 // ─── Structured type descriptors ─────────────────────────────────────
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 #[serde(tag = "kind", rename_all = "camelCase")]
 pub enum TypeDescriptor {
     // Instance types
@@ -191,6 +864,16 @@ pub enum TypeDescriptor {
         class_id: Option<TypeId>,
     },
 
+    /// A structural protocol with no class of its own, such as the one
+    /// `hasattr(x, "name")` narrows `x` to.
+    #[serde(rename_all = "camelCase")]
+    Protocol {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        display: Option<String>,
+        /// The members the protocol requires.
+        members: Vec<ClassMemberInfo>,
+    },
+
     // Class literal: type[MyClass]
     #[serde(rename_all = "camelCase")]
     ClassLiteral {
@@ -203,8 +886,32 @@ pub enum TypeDescriptor {
         type_parameters: Vec<TypeId>,
         #[serde(skip_serializing_if = "Vec::is_empty")]
         supertypes: Vec<TypeId>,
+        /// Bases ty synthesizes that the class statement doesn't spell out,
+        /// such as the `Generic[T]` base of `class Box[T]`.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        implicit_bases: Vec<TypeId>,
         #[serde(skip_serializing_if = "Vec::is_empty")]
         members: Vec<ClassMemberInfo>,
+        /// The class's metaclass, omitted when it is the default `type`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        metaclass: Option<TypeId>,
+        /// Whether the class is a `typing.Protocol`.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        is_protocol: bool,
+        /// Whether the protocol is decorated with `@runtime_checkable`, so it
+        /// can be used with `isinstance`.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        runtime_checkable: bool,
+    },
+
+    /// A specialized generic class, such as `list[int]` in an annotation.
+    #[serde(rename_all = "camelCase")]
+    GenericAlias {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        display: Option<String>,
+        /// The unspecialized class.
+        origin: TypeId,
+        type_args: Vec<TypeId>,
     },
 
     // type[C] — subclass-of
@@ -224,10 +931,32 @@ pub enum TypeDescriptor {
     },
 
     // Composite types
+    #[serde(rename_all = "camelCase")]
     Union {
         #[serde(skip_serializing_if = "Option::is_none")]
         display: Option<String>,
         members: Vec<TypeId>,
+        /// `true` when exactly one member is `None` (i.e. `X | None`).
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        optional: bool,
+        /// The non-`None` portion of an optional union, which may itself be a union.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        optional_inner: Option<TypeId>,
+    },
+
+    /// A union of two or more literal values, i.e. `Literal[1, 2, 3]`,
+    /// optionally with `None` (`Literal[1, 2] | None`).
+    #[serde(rename_all = "camelCase")]
+    LiteralUnion {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        display: Option<String>,
+        members: Vec<TypeId>,
+        /// `true` when one member is `None`, as for `Union`.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        optional: bool,
+        /// The literal members without `None`, for an optional literal union.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        optional_inner: Option<TypeId>,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -251,6 +980,10 @@ pub enum TypeDescriptor {
         parameters: Vec<ParameterInfo>,
         #[serde(skip_serializing_if = "Option::is_none")]
         return_type: Option<TypeId>,
+        /// The body can run off its end, returning `None` without a `return`,
+        /// and the return is unannotated or includes `None`.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        implicit_none_return: bool,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -261,6 +994,10 @@ pub enum TypeDescriptor {
         parameters: Vec<ParameterInfo>,
         #[serde(skip_serializing_if = "Option::is_none")]
         return_type: Option<TypeId>,
+        /// Set for the `...` form, as in `Callable[..., int]`, which accepts
+        /// any arguments; `parameters` is then empty.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        unspecified_params: bool,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -278,6 +1015,9 @@ pub enum TypeDescriptor {
         parameters: Vec<ParameterInfo>,
         #[serde(skip_serializing_if = "Option::is_none")]
         return_type: Option<TypeId>,
+        /// The instance (or class, for classmethods) the method is bound to.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        receiver: Option<TypeId>,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -308,8 +1048,11 @@ pub enum TypeDescriptor {
 
     #[serde(rename_all = "camelCase")]
     StringLiteral {
+        /// ty's rendering, e.g. `Literal["a\"b\n"]`, with quotes and control
+        /// characters escaped.
         #[serde(skip_serializing_if = "Option::is_none")]
         display: Option<String>,
+        /// The string's exact content, with no quoting or escaping.
         value: String,
     },
 
@@ -322,10 +1065,14 @@ pub enum TypeDescriptor {
 
     #[serde(rename_all = "camelCase")]
     EnumLiteral {
+        /// Always `Literal[Class.MEMBER]`.
         #[serde(skip_serializing_if = "Option::is_none")]
         display: Option<String>,
         class_name: String,
         member_name: String,
+        /// The type of the member's value, e.g. `Literal[1]` for `RED = 1`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        member_value_display: Option<String>,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -363,6 +1110,10 @@ pub enum TypeDescriptor {
         #[serde(skip_serializing_if = "Option::is_none")]
         display: Option<String>,
         name: String,
+        /// The module whose `TypeVar(...)` call or type parameter list defines
+        /// the variable.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        module_name: Option<String>,
         /// "TypeVar", "ParamSpec", "TypeVarTuple", "Self"
         #[serde(skip_serializing_if = "Option::is_none")]
         typevar_kind: Option<String>,
@@ -374,6 +1125,20 @@ pub enum TypeDescriptor {
         constraints: Vec<TypeId>,
         #[serde(skip_serializing_if = "Option::is_none")]
         default_type: Option<TypeId>,
+        /// The class, function, or type alias whose scope binds this variable,
+        /// distinguishing same-named type variables across scopes.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        owner: Option<TypeId>,
+    },
+
+    /// `typing.Self`: the type of the receiver in a class's methods.
+    #[serde(rename_all = "camelCase")]
+    SelfType {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        display: Option<String>,
+        /// The class `Self` is bound to, when known.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        bound_class: Option<TypeId>,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -408,6 +1173,12 @@ pub enum TypeDescriptor {
         name: String,
         #[serde(skip_serializing_if = "Vec::is_empty")]
         fields: Vec<TypedDictFieldInfo>,
+        /// Names of the `required` fields, in field order.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        required_keys: Vec<String>,
+        /// Names of the fields that may be omitted, in field order.
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        optional_keys: Vec<String>,
         /// `true` when the TypedDict forbids undeclared keys (`closed=True`,
         /// or equivalently `extra_items=Never`). Omitted when `false`.
         #[serde(skip_serializing_if = "std::ops::Not::not")]
@@ -422,6 +1193,10 @@ pub enum TypeDescriptor {
         #[serde(skip_serializing_if = "Option::is_none")]
         display: Option<String>,
         narrowed_type: TypeId,
+        /// The name of the argument this guard narrows, once ty has bound it
+        /// to a call site (e.g. `v` in `is_str(v)`).
+        #[serde(skip_serializing_if = "Option::is_none")]
+        param_name: Option<String>,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -429,6 +1204,8 @@ pub enum TypeDescriptor {
         #[serde(skip_serializing_if = "Option::is_none")]
         display: Option<String>,
         guarded_type: TypeId,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        param_name: Option<String>,
     },
 
     #[serde(rename_all = "camelCase")]
@@ -446,6 +1223,14 @@ pub enum TypeDescriptor {
         name: String,
     },
 
+    /// The `...` singleton, kept apart from ordinary instances so stub bodies and
+    /// `tuple[int, ...]` can be rendered as written.
+    #[serde(rename_all = "camelCase")]
+    Ellipsis {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        display: Option<String>,
+    },
+
     #[serde(rename_all = "camelCase")]
     Property {
         #[serde(skip_serializing_if = "Option::is_none")]
@@ -479,10 +1264,13 @@ impl TypeDescriptor {
     pub fn strip_display(&mut self) {
         match self {
             Self::Instance { display, .. }
+            | Self::Protocol { display, .. }
             | Self::ClassLiteral { display, .. }
+            | Self::GenericAlias { display, .. }
             | Self::SubclassOf { display, .. }
             | Self::TypeForm { display, .. }
             | Self::Union { display, .. }
+            | Self::LiteralUnion { display, .. }
             | Self::Intersection { display, .. }
             | Self::Function { display, .. }
             | Self::Callable { display, .. }
@@ -499,6 +1287,7 @@ impl TypeDescriptor {
             | Self::Truthy { display, .. }
             | Self::Falsy { display, .. }
             | Self::TypeVar { display, .. }
+            | Self::SelfType { display, .. }
             | Self::Module { display, .. }
             | Self::TypeAlias { display, .. }
             | Self::KnownInstance { display, .. }
@@ -507,6 +1296,7 @@ impl TypeDescriptor {
             | Self::TypeGuard { display, .. }
             | Self::NewType { display, .. }
             | Self::SpecialForm { display, .. }
+            | Self::Ellipsis { display }
             | Self::Property { display, .. }
             | Self::EnumComplement { display, .. }
             | Self::Other { display, .. } => {
@@ -514,4 +1304,448 @@ impl TypeDescriptor {
             }
         }
     }
+
+    /// Like [`Self::strip_display`], but keeps the `display` of descriptors
+    /// whose [`kind`](Self::kind) is in `kinds`.
+    pub fn strip_display_except(&mut self, kinds: &[&str]) {
+        if !kinds.contains(&self.kind()) {
+            self.strip_display();
+        }
+    }
+
+    /// What a name bound to this type refers to: `module`, `class`,
+    /// `function`, or else `variable`.
+    pub fn symbol_kind(&self) -> &'static str {
+        match self {
+            Self::Module { .. } => "module",
+            Self::ClassLiteral { .. } | Self::GenericAlias { .. } => "class",
+            Self::Function { .. } => "function",
+            _ => "variable",
+        }
+    }
+
+    /// The `display` field, regardless of variant.
+    pub fn display(&self) -> Option<&str> {
+        match self {
+            Self::Instance { display, .. }
+            | Self::Protocol { display, .. }
+            | Self::ClassLiteral { display, .. }
+            | Self::GenericAlias { display, .. }
+            | Self::SubclassOf { display, .. }
+            | Self::TypeForm { display, .. }
+            | Self::Union { display, .. }
+            | Self::LiteralUnion { display, .. }
+            | Self::Intersection { display, .. }
+            | Self::Function { display, .. }
+            | Self::Callable { display, .. }
+            | Self::BoundMethod { display, .. }
+            | Self::WrapperDescriptor { display, .. }
+            | Self::IntLiteral { display, .. }
+            | Self::BoolLiteral { display, .. }
+            | Self::StringLiteral { display, .. }
+            | Self::BytesLiteral { display, .. }
+            | Self::EnumLiteral { display, .. }
+            | Self::LiteralString { display, .. }
+            | Self::Dynamic { display, .. }
+            | Self::Never { display, .. }
+            | Self::Truthy { display, .. }
+            | Self::Falsy { display, .. }
+            | Self::TypeVar { display, .. }
+            | Self::SelfType { display, .. }
+            | Self::Module { display, .. }
+            | Self::TypeAlias { display, .. }
+            | Self::KnownInstance { display, .. }
+            | Self::TypedDict { display, .. }
+            | Self::TypeIs { display, .. }
+            | Self::TypeGuard { display, .. }
+            | Self::NewType { display, .. }
+            | Self::SpecialForm { display, .. }
+            | Self::Ellipsis { display }
+            | Self::Property { display, .. }
+            | Self::EnumComplement { display, .. }
+            | Self::Other { display, .. } => display.as_deref(),
+        }
+    }
+
+    /// The module defining the type, for descriptors that record one.
+    pub fn module_name(&self) -> Option<&str> {
+        match self {
+            Self::Instance { module_name, .. }
+            | Self::ClassLiteral { module_name, .. }
+            | Self::Function { module_name, .. }
+            | Self::BoundMethod { module_name, .. }
+            | Self::TypeVar { module_name, .. } => module_name.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The serialized `kind` discriminator.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Instance { .. } => "instance",
+            Self::Protocol { .. } => "protocol",
+            Self::ClassLiteral { .. } => "classLiteral",
+            Self::GenericAlias { .. } => "genericAlias",
+            Self::SubclassOf { .. } => "subclassOf",
+            Self::TypeForm { .. } => "typeForm",
+            Self::Union { .. } => "union",
+            Self::LiteralUnion { .. } => "literalUnion",
+            Self::Intersection { .. } => "intersection",
+            Self::Function { .. } => "function",
+            Self::Callable { .. } => "callable",
+            Self::BoundMethod { .. } => "boundMethod",
+            Self::WrapperDescriptor { .. } => "wrapperDescriptor",
+            Self::IntLiteral { .. } => "intLiteral",
+            Self::BoolLiteral { .. } => "boolLiteral",
+            Self::StringLiteral { .. } => "stringLiteral",
+            Self::BytesLiteral { .. } => "bytesLiteral",
+            Self::EnumLiteral { .. } => "enumLiteral",
+            Self::LiteralString { .. } => "literalString",
+            Self::Dynamic { .. } => "dynamic",
+            Self::Never { .. } => "never",
+            Self::Truthy { .. } => "truthy",
+            Self::Falsy { .. } => "falsy",
+            Self::TypeVar { .. } => "typeVar",
+            Self::SelfType { .. } => "selfType",
+            Self::Module { .. } => "module",
+            Self::TypeAlias { .. } => "typeAlias",
+            Self::KnownInstance { .. } => "knownInstance",
+            Self::TypedDict { .. } => "typedDict",
+            Self::TypeIs { .. } => "typeIs",
+            Self::TypeGuard { .. } => "typeGuard",
+            Self::NewType { .. } => "newType",
+            Self::SpecialForm { .. } => "specialForm",
+            Self::Ellipsis { .. } => "ellipsis",
+            Self::Property { .. } => "property",
+            Self::EnumComplement { .. } => "enumComplement",
+            Self::Other { .. } => "other",
+        }
+    }
+
+    /// Labeled references from this descriptor to its component types, in
+    /// field order.
+    pub fn edges(&self) -> Vec<(&'static str, TypeId)> {
+        let mut edges = Vec::new();
+        match self {
+            Self::Instance {
+                supertypes,
+                type_args,
+                class_id,
+                ..
+            } => {
+                edges.extend(supertypes.iter().map(|&id| ("supertype", id)));
+                edges.extend(type_args.iter().map(|&id| ("typeArg", id)));
+                edges.extend(class_id.map(|id| ("class", id)));
+            }
+            Self::Protocol { members, .. } => {
+                edges.extend(members.iter().map(|m| ("member", m.type_id)));
+            }
+            Self::ClassLiteral {
+                type_parameters,
+                supertypes,
+                implicit_bases,
+                members,
+                metaclass,
+                ..
+            } => {
+                edges.extend(type_parameters.iter().map(|&id| ("typeParameter", id)));
+                edges.extend(supertypes.iter().map(|&id| ("supertype", id)));
+                edges.extend(implicit_bases.iter().map(|&id| ("implicitBase", id)));
+                edges.extend(members.iter().map(|m| ("member", m.type_id)));
+                edges.extend(metaclass.map(|id| ("metaclass", id)));
+            }
+            Self::GenericAlias {
+                origin, type_args, ..
+            } => {
+                edges.push(("origin", *origin));
+                edges.extend(type_args.iter().map(|&id| ("typeArg", id)));
+            }
+            Self::SubclassOf { base, .. } => edges.push(("base", *base)),
+            Self::TypeForm { type_argument, .. } => edges.push(("typeArgument", *type_argument)),
+            Self::Union {
+                members,
+                optional_inner,
+                ..
+            } => {
+                edges.extend(members.iter().map(|&id| ("member", id)));
+                edges.extend(optional_inner.map(|id| ("optionalInner", id)));
+            }
+            Self::LiteralUnion {
+                members,
+                optional_inner,
+                ..
+            } => {
+                edges.extend(members.iter().map(|&id| ("member", id)));
+                edges.extend(optional_inner.map(|id| ("optionalInner", id)));
+            }
+            Self::Intersection {
+                positive, negative, ..
+            } => {
+                edges.extend(positive.iter().map(|&id| ("positive", id)));
+                edges.extend(negative.iter().map(|&id| ("negative", id)));
+            }
+            Self::Function {
+                type_parameters,
+                parameters,
+                return_type,
+                ..
+            } => {
+                edges.extend(type_parameters.iter().map(|&id| ("typeParameter", id)));
+                push_parameter_edges(&mut edges, parameters);
+                edges.extend(return_type.map(|id| ("return", id)));
+            }
+            Self::BoundMethod {
+                type_parameters,
+                parameters,
+                return_type,
+                receiver,
+                ..
+            } => {
+                edges.extend(type_parameters.iter().map(|&id| ("typeParameter", id)));
+                push_parameter_edges(&mut edges, parameters);
+                edges.extend(return_type.map(|id| ("return", id)));
+                edges.extend(receiver.map(|id| ("receiver", id)));
+            }
+            Self::Callable {
+                parameters,
+                return_type,
+                ..
+            }
+            | Self::WrapperDescriptor {
+                parameters,
+                return_type,
+                ..
+            } => {
+                push_parameter_edges(&mut edges, parameters);
+                edges.extend(return_type.map(|id| ("return", id)));
+            }
+            Self::TypeVar {
+                upper_bound,
+                constraints,
+                default_type,
+                owner,
+                ..
+            } => {
+                edges.extend(upper_bound.map(|id| ("bound", id)));
+                edges.extend(constraints.iter().map(|&id| ("constraint", id)));
+                edges.extend(default_type.map(|id| ("default", id)));
+                edges.extend(owner.map(|id| ("owner", id)));
+            }
+            Self::SelfType { bound_class, .. } => {
+                edges.extend(bound_class.map(|id| ("boundClass", id)));
+            }
+            Self::TypeAlias {
+                value_type,
+                type_parameters,
+                ..
+            } => {
+                edges.extend(value_type.map(|id| ("value", id)));
+                edges.extend(type_parameters.iter().map(|&id| ("typeParameter", id)));
+            }
+            Self::TypedDict {
+                fields,
+                extra_items,
+                ..
+            } => {
+                edges.extend(fields.iter().map(|f| ("field", f.type_id)));
+                edges.extend(extra_items.as_ref().map(|e| ("extraItems", e.type_id)));
+            }
+            Self::TypeIs { narrowed_type, .. } => edges.push(("narrowed", *narrowed_type)),
+            Self::TypeGuard { guarded_type, .. } => edges.push(("guarded", *guarded_type)),
+            Self::NewType { base_type, .. } => edges.push(("base", *base_type)),
+            Self::EnumComplement { class_id, rest, .. } => {
+                edges.push(("class", *class_id));
+                edges.extend(rest.iter().map(|&id| ("rest", id)));
+            }
+            Self::IntLiteral { .. }
+            | Self::BoolLiteral { .. }
+            | Self::StringLiteral { .. }
+            | Self::BytesLiteral { .. }
+            | Self::EnumLiteral { .. }
+            | Self::LiteralString { .. }
+            | Self::Dynamic { .. }
+            | Self::Never { .. }
+            | Self::Truthy { .. }
+            | Self::Falsy { .. }
+            | Self::Module { .. }
+            | Self::KnownInstance { .. }
+            | Self::SpecialForm { .. }
+            | Self::Ellipsis { .. }
+            | Self::Property { .. }
+            | Self::Other { .. } => {}
+        }
+        edges
+    }
+
+    /// Mutable references to the component type IDs, in the same order as
+    /// `edges`.
+    pub fn edges_mut(&mut self) -> Vec<&mut TypeId> {
+        let mut edges = Vec::new();
+        match self {
+            Self::Instance {
+                supertypes,
+                type_args,
+                class_id,
+                ..
+            } => {
+                edges.extend(supertypes.iter_mut());
+                edges.extend(type_args.iter_mut());
+                edges.extend(class_id.as_mut());
+            }
+            Self::Protocol { members, .. } => {
+                edges.extend(members.iter_mut().map(|m| &mut m.type_id));
+            }
+            Self::ClassLiteral {
+                type_parameters,
+                supertypes,
+                implicit_bases,
+                members,
+                metaclass,
+                ..
+            } => {
+                edges.extend(type_parameters.iter_mut());
+                edges.extend(supertypes.iter_mut());
+                edges.extend(implicit_bases.iter_mut());
+                edges.extend(members.iter_mut().map(|m| &mut m.type_id));
+                edges.extend(metaclass.as_mut());
+            }
+            Self::GenericAlias {
+                origin, type_args, ..
+            } => {
+                edges.push(origin);
+                edges.extend(type_args.iter_mut());
+            }
+            Self::SubclassOf { base, .. } => edges.push(base),
+            Self::TypeForm { type_argument, .. } => edges.push(type_argument),
+            Self::Union {
+                members,
+                optional_inner,
+                ..
+            } => {
+                edges.extend(members.iter_mut());
+                edges.extend(optional_inner.as_mut());
+            }
+            Self::LiteralUnion {
+                members,
+                optional_inner,
+                ..
+            } => {
+                edges.extend(members.iter_mut());
+                edges.extend(optional_inner.as_mut());
+            }
+            Self::Intersection {
+                positive, negative, ..
+            } => {
+                edges.extend(positive.iter_mut());
+                edges.extend(negative.iter_mut());
+            }
+            Self::Function {
+                type_parameters,
+                parameters,
+                return_type,
+                ..
+            } => {
+                edges.extend(type_parameters.iter_mut());
+                push_parameter_edges_mut(&mut edges, parameters);
+                edges.extend(return_type.as_mut());
+            }
+            Self::BoundMethod {
+                type_parameters,
+                parameters,
+                return_type,
+                receiver,
+                ..
+            } => {
+                edges.extend(type_parameters.iter_mut());
+                push_parameter_edges_mut(&mut edges, parameters);
+                edges.extend(return_type.as_mut());
+                edges.extend(receiver.as_mut());
+            }
+            Self::Callable {
+                parameters,
+                return_type,
+                ..
+            }
+            | Self::WrapperDescriptor {
+                parameters,
+                return_type,
+                ..
+            } => {
+                push_parameter_edges_mut(&mut edges, parameters);
+                edges.extend(return_type.as_mut());
+            }
+            Self::TypeVar {
+                upper_bound,
+                constraints,
+                default_type,
+                owner,
+                ..
+            } => {
+                edges.extend(upper_bound.as_mut());
+                edges.extend(constraints.iter_mut());
+                edges.extend(default_type.as_mut());
+                edges.extend(owner.as_mut());
+            }
+            Self::SelfType { bound_class, .. } => edges.extend(bound_class.as_mut()),
+            Self::TypeAlias {
+                value_type,
+                type_parameters,
+                ..
+            } => {
+                edges.extend(value_type.as_mut());
+                edges.extend(type_parameters.iter_mut());
+            }
+            Self::TypedDict {
+                fields,
+                extra_items,
+                ..
+            } => {
+                edges.extend(fields.iter_mut().map(|f| &mut f.type_id));
+                edges.extend(extra_items.as_mut().map(|e| &mut e.type_id));
+            }
+            Self::TypeIs { narrowed_type, .. } => edges.push(narrowed_type),
+            Self::TypeGuard { guarded_type, .. } => edges.push(guarded_type),
+            Self::NewType { base_type, .. } => edges.push(base_type),
+            Self::EnumComplement { class_id, rest, .. } => {
+                edges.push(class_id);
+                edges.extend(rest.iter_mut());
+            }
+            Self::IntLiteral { .. }
+            | Self::BoolLiteral { .. }
+            | Self::StringLiteral { .. }
+            | Self::BytesLiteral { .. }
+            | Self::EnumLiteral { .. }
+            | Self::LiteralString { .. }
+            | Self::Dynamic { .. }
+            | Self::Never { .. }
+            | Self::Truthy { .. }
+            | Self::Falsy { .. }
+            | Self::Module { .. }
+            | Self::KnownInstance { .. }
+            | Self::SpecialForm { .. }
+            | Self::Ellipsis { .. }
+            | Self::Property { .. }
+            | Self::Other { .. } => {}
+        }
+        edges
+    }
+}
+
+fn push_parameter_edges(edges: &mut Vec<(&'static str, TypeId)>, parameters: &[ParameterInfo]) {
+    for param in parameters {
+        edges.extend(param.type_id.map(|id| ("parameter", id)));
+        edges.extend(param.default_type_id.map(|id| ("default", id)));
+        edges.extend(param.param_spec_id.map(|id| ("paramSpec", id)));
+    }
+}
+
+fn push_parameter_edges_mut<'a>(
+    edges: &mut Vec<&'a mut TypeId>,
+    parameters: &'a mut [ParameterInfo],
+) {
+    for param in parameters {
+        edges.extend(param.type_id.as_mut());
+        edges.extend(param.default_type_id.as_mut());
+        edges.extend(param.param_spec_id.as_mut());
+    }
 }
@@ -1,15 +1,23 @@
-use rustc_hash::FxHashMap;
+use ruff_db::files::File;
+use ruff_db::parsed::parsed_module;
+use ruff_text_size::{Ranged, TextRange};
+use rustc_hash::{FxHashMap, FxHashSet};
 use ty_python_semantic::Db;
+use ty_python_semantic::semantic_index::definition::DefinitionKind;
+use ty_python_semantic::semantic_index::scope::NodeWithScopeKind;
+use ty_python_semantic::semantic_index::use_def_map;
 use ty_python_semantic::types::list_members;
 use ty_python_semantic::types::signatures::{ConcatenateTail, ParametersKind, Signature};
 use ty_python_semantic::types::{
-    ClassLiteral, GenericContext, LiteralValueTypeKind, ParameterKind, Type, TypeVarKind,
-    TypeVarVariance,
+    BindingContext, ClassLiteral, ClassType, FunctionType, GenericContext, KnownClass,
+    KnownInstanceType, LiteralValueTypeKind, ParameterKind, Type, TypeVarKind, TypeVarVariance,
+    UnionType, binding_type,
 };
 
+use crate::position::{OffsetEncoder, PositionEncoding};
 use crate::protocol::{
-    ClassMemberInfo, ParameterInfo, TypeDescriptor, TypeId, TypedDictExtraItemsInfo,
-    TypedDictFieldInfo,
+    ClassMemberInfo, GraphEdge, GraphNode, ParameterInfo, TypeDescriptor, TypeId,
+    TypedDictExtraItemsInfo, TypedDictFieldInfo, UnionDisplayStyle,
 };
 
 /// A session-scoped registry that deduplicates types by identity.
@@ -18,11 +26,39 @@ use crate::protocol::{
 /// the same type from different files maps to the same ID.
 pub struct TypeRegistry<'db> {
     type_to_id: FxHashMap<Type<'db>, TypeId>,
+    /// The reverse of `type_to_id`, for lookups by ID.
+    id_to_type: FxHashMap<TypeId, Type<'db>>,
     descriptors: FxHashMap<TypeId, TypeDescriptor>,
     next_id: TypeId,
     /// Tracks all type IDs registered since the last `start_tracking()` call,
     /// including component types registered transitively by `build_descriptor`.
     tracked_new_ids: Vec<TypeId>,
+    /// Type IDs registered but left out of the response that registered them.
+    /// `register` tracks them as new again the next time they are referenced.
+    unsent: FxHashSet<TypeId>,
+    /// Unit for source offsets embedded in descriptors (parameter ranges).
+    position_encoding: PositionEncoding,
+    /// How descriptor `display` strings render unions.
+    union_display_style: UnionDisplayStyle,
+    /// One encoder per file with encoded offsets, since building one walks
+    /// the whole file.
+    encoders: FxHashMap<File, OffsetEncoder>,
+    /// The first ID allocated since the registry was last resumed. Lower IDs
+    /// were registered against an earlier database revision.
+    epoch_start: TypeId,
+    /// IDs from earlier revisions, by descriptor shape (see `shape`), that a
+    /// newly registered type can take over.
+    candidates: FxHashMap<TypeDescriptor, Vec<TypeId>>,
+}
+
+/// A registry detached from the database, so the database can change. The
+/// types themselves are dropped; descriptors and IDs are kept.
+pub struct SuspendedRegistry {
+    descriptors: FxHashMap<TypeId, TypeDescriptor>,
+    next_id: TypeId,
+    unsent: FxHashSet<TypeId>,
+    position_encoding: PositionEncoding,
+    union_display_style: UnionDisplayStyle,
 }
 
 pub struct RegistrationResult {
@@ -34,16 +70,74 @@ impl<'db> TypeRegistry<'db> {
     pub fn new() -> Self {
         Self {
             type_to_id: FxHashMap::default(),
+            id_to_type: FxHashMap::default(),
             descriptors: FxHashMap::default(),
             next_id: 1, // start at 1, reserve 0 for "no type"
             tracked_new_ids: Vec::new(),
+            unsent: FxHashSet::default(),
+            position_encoding: PositionEncoding::default(),
+            union_display_style: UnionDisplayStyle::default(),
+            encoders: FxHashMap::default(),
+            epoch_start: 1,
+            candidates: FxHashMap::default(),
+        }
+    }
+
+    /// Detach the registry from the database, before the database changes.
+    /// Earlier-revision descriptors that no type took over are dropped.
+    pub fn suspend(mut self) -> SuspendedRegistry {
+        self.drop_stale();
+        SuspendedRegistry {
+            descriptors: self.descriptors,
+            next_id: self.next_id,
+            unsent: self.unsent,
+            position_encoding: self.position_encoding,
+            union_display_style: self.union_display_style,
+        }
+    }
+
+    /// Attach a suspended registry to the (changed) database.
+    ///
+    /// Its types must be registered again. A type whose descriptor graph
+    /// is unchanged takes over its previous ID, so IDs the client holds stay
+    /// valid and descriptors it has are not sent again.
+    pub fn resume(suspended: SuspendedRegistry) -> Self {
+        let mut candidates: FxHashMap<TypeDescriptor, Vec<TypeId>> = FxHashMap::default();
+        for (&id, descriptor) in &suspended.descriptors {
+            candidates.entry(shape(descriptor)).or_default().push(id);
+        }
+        for ids in candidates.values_mut() {
+            ids.sort_unstable();
+        }
+        Self {
+            descriptors: suspended.descriptors,
+            next_id: suspended.next_id,
+            unsent: suspended.unsent,
+            position_encoding: suspended.position_encoding,
+            union_display_style: suspended.union_display_style,
+            epoch_start: suspended.next_id,
+            candidates,
+            ..Self::new()
         }
     }
 
+    /// Report descriptor source offsets in `encoding` instead of UTF-8 bytes.
+    pub fn with_position_encoding(mut self, encoding: PositionEncoding) -> Self {
+        self.position_encoding = encoding;
+        self
+    }
+
+    /// Render unions in descriptor `display` strings in `style`.
+    pub fn with_union_display_style(mut self, style: UnionDisplayStyle) -> Self {
+        self.union_display_style = style;
+        self
+    }
+
     /// Register a type and return its ID. If the type was already registered,
     /// returns the existing ID with is_new = false.
     pub fn register(&mut self, ty: Type<'db>, db: &'db dyn Db) -> RegistrationResult {
         if let Some(&id) = self.type_to_id.get(&ty) {
+            self.track_unsent(id);
             return RegistrationResult {
                 type_id: id,
                 is_new: false,
@@ -53,11 +147,19 @@ impl<'db> TypeRegistry<'db> {
         let id = self.next_id;
         self.next_id += 1;
         self.type_to_id.insert(ty, id);
+        self.id_to_type.insert(id, ty);
 
         let descriptor = self.build_descriptor(ty, db);
         self.descriptors.insert(id, descriptor);
         self.tracked_new_ids.push(id);
 
+        if let Some(previous) = self.reclaim(id) {
+            return RegistrationResult {
+                type_id: previous,
+                is_new: false,
+            };
+        }
+
         RegistrationResult {
             type_id: id,
             is_new: true,
@@ -69,19 +171,114 @@ impl<'db> TypeRegistry<'db> {
         self.descriptors.get(&id)
     }
 
-    /// Get all descriptors as a map.
+    /// Get the type registered under an ID.
+    pub fn get_type(&self, id: TypeId) -> Option<Type<'db>> {
+        self.id_to_type.get(&id).copied()
+    }
+
+    /// Whether `id` was registered against an earlier database revision and
+    /// no type has taken it over since. Its descriptor may be out of date.
+    pub fn is_stale(&self, id: TypeId) -> bool {
+        id < self.epoch_start && !self.id_to_type.contains_key(&id)
+    }
+
+    /// Whether any descriptor is stale (see `is_stale`).
+    pub fn has_stale(&self) -> bool {
+        self.descriptors.keys().any(|&id| self.is_stale(id))
+    }
+
+    /// Remove stale descriptors, once every type that could take one over has
+    /// been registered again.
+    pub fn drop_stale(&mut self) {
+        let epoch_start = self.epoch_start;
+        let id_to_type = &self.id_to_type;
+        let stale = |id: &TypeId| *id < epoch_start && !id_to_type.contains_key(id);
+        self.descriptors.retain(|id, _| !stale(id));
+        self.unsent.retain(|id| !stale(id));
+        self.candidates.clear();
+    }
+
+    /// Get all descriptors as a map, leaving out stale ones.
     pub fn all_descriptors(&self) -> std::collections::HashMap<TypeId, TypeDescriptor> {
         self.descriptors
             .iter()
+            .filter(|&(&id, _)| !self.is_stale(id))
             .map(|(&id, d)| (id, d.clone()))
             .collect()
     }
 
+    /// Get the descriptors of `roots` and of every type they reference, transitively.
+    pub fn reachable_descriptors(
+        &self,
+        roots: impl IntoIterator<Item = TypeId>,
+    ) -> std::collections::HashMap<TypeId, TypeDescriptor> {
+        let mut reachable = std::collections::HashMap::new();
+        let mut pending: Vec<TypeId> = roots.into_iter().collect();
+        while let Some(id) = pending.pop() {
+            if reachable.contains_key(&id) || self.is_stale(id) {
+                continue;
+            }
+            let Some(descriptor) = self.descriptors.get(&id) else {
+                continue;
+            };
+            pending.extend(descriptor.edges().into_iter().map(|(_, to)| to));
+            reachable.insert(id, descriptor.clone());
+        }
+        reachable
+    }
+
+    /// Get the IDs of all non-stale descriptors whose `display` is exactly
+    /// `display`, in ascending order. Distinct types can share a display.
+    pub fn find_by_display(&self, display: &str) -> Vec<TypeId> {
+        let mut ids: Vec<TypeId> = self
+            .descriptors
+            .iter()
+            .filter(|&(&id, d)| d.display() == Some(display) && !self.is_stale(id))
+            .map(|(&id, _)| id)
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    /// Build the registry graph of non-stale descriptors, ordered by type ID.
+    pub fn graph(&self) -> (Vec<GraphNode>, Vec<GraphEdge>) {
+        let mut ids: Vec<TypeId> = self
+            .descriptors
+            .keys()
+            .copied()
+            .filter(|&id| !self.is_stale(id))
+            .collect();
+        ids.sort_unstable();
+
+        let mut nodes = Vec::with_capacity(ids.len());
+        let mut edges = Vec::new();
+        for id in ids {
+            let descriptor = &self.descriptors[&id];
+            nodes.push(GraphNode {
+                id,
+                kind: descriptor.kind(),
+                display: descriptor.display().map(str::to_string),
+            });
+            edges.extend(descriptor.edges().into_iter().map(|(label, to)| GraphEdge {
+                from: id,
+                to,
+                label,
+            }));
+        }
+        (nodes, edges)
+    }
+
     /// Begin tracking newly registered types (including transitive components).
     pub fn start_tracking(&mut self) {
         self.tracked_new_ids.clear();
     }
 
+    /// The type IDs registered since the last `start_tracking()` call, in
+    /// the order they were registered.
+    pub fn tracked_new_ids(&self) -> &[TypeId] {
+        &self.tracked_new_ids
+    }
+
     /// Drain all type IDs registered since the last `start_tracking()` call
     /// and return their descriptors.
     pub fn drain_new_types(&mut self) -> std::collections::HashMap<TypeId, TypeDescriptor> {
@@ -91,18 +288,133 @@ impl<'db> TypeRegistry<'db> {
             .collect()
     }
 
+    /// Mark types whose descriptors were dropped from a response, although
+    /// `drain_new_types` returned them, as not sent to the client.
+    pub fn mark_unsent(&mut self, ids: impl IntoIterator<Item = TypeId>) {
+        self.unsent.extend(ids);
+    }
+
+    /// If `id` is unsent, track it as new again, along with the unsent types
+    /// its descriptor references.
+    fn track_unsent(&mut self, id: TypeId) {
+        if !self.unsent.remove(&id) {
+            return;
+        }
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            self.tracked_new_ids.push(id);
+            if let Some(descriptor) = self.descriptors.get(&id) {
+                let edges = descriptor.edges().into_iter().map(|(_, to)| to);
+                pending.extend(edges.filter(|to| self.unsent.remove(to)));
+            }
+        }
+    }
+
+    /// Move the type just registered under `id`, and the types registered
+    /// along with it, to the IDs of an identical descriptor graph from an
+    /// earlier revision. Returns the ID `id` moved to.
+    fn reclaim(&mut self, id: TypeId) -> Option<TypeId> {
+        if self.candidates.is_empty() {
+            return None;
+        }
+        let candidates = self.candidates.get(&shape(&self.descriptors[&id]))?;
+        let remap = candidates
+            .iter()
+            .find_map(|&previous| self.match_previous(id, previous))?;
+
+        for (&new, &previous) in &remap {
+            let ty = self
+                .id_to_type
+                .remove(&new)
+                .expect("matched types are registered");
+            self.type_to_id.insert(ty, previous);
+            self.id_to_type.insert(previous, ty);
+            self.descriptors.remove(&new);
+        }
+        self.tracked_new_ids.retain(|new| !remap.contains_key(new));
+        // Types registered along with `id` that found no match may reference
+        // ones that did.
+        for new in id..self.next_id {
+            if let Some(descriptor) = self.descriptors.get_mut(&new) {
+                for edge in descriptor.edges_mut() {
+                    if let Some(&previous) = remap.get(edge) {
+                        *edge = previous;
+                    }
+                }
+            }
+        }
+        for &previous in remap.values() {
+            self.track_unsent(previous);
+        }
+        if (id..self.next_id).all(|new| remap.contains_key(&new)) {
+            self.next_id = id;
+        }
+        Some(remap[&id])
+    }
+
+    /// Pair the descriptor graph registered under `id` with that of the
+    /// unclaimed earlier-revision ID `previous`, or `None` if they differ.
+    fn match_previous(&self, id: TypeId, previous: TypeId) -> Option<FxHashMap<TypeId, TypeId>> {
+        let mut remap = FxHashMap::default();
+        let mut claimed = FxHashSet::default();
+        let mut pending = vec![(id, previous)];
+        while let Some((new, previous)) = pending.pop() {
+            if new < id {
+                // Registered before `id`, so already matched or not.
+                if new != previous {
+                    return None;
+                }
+                continue;
+            }
+            if let Some(&matched) = remap.get(&new) {
+                if matched != previous {
+                    return None;
+                }
+                continue;
+            }
+            if previous >= self.epoch_start
+                || self.id_to_type.contains_key(&previous)
+                || !claimed.insert(previous)
+            {
+                return None;
+            }
+            let new_descriptor = self.descriptors.get(&new)?;
+            let previous_descriptor = self.descriptors.get(&previous)?;
+            if shape(new_descriptor) != shape(previous_descriptor) {
+                return None;
+            }
+            remap.insert(new, previous);
+            let new_edges = new_descriptor.edges().into_iter().map(|(_, to)| to);
+            let previous_edges = previous_descriptor.edges().into_iter().map(|(_, to)| to);
+            pending.extend(new_edges.zip(previous_edges));
+        }
+        Some(remap)
+    }
+
     /// Register a type that is a component of another type (e.g., union member,
     /// parameter type), returning just its ID.
     pub fn register_component(&mut self, ty: Type<'db>, db: &'db dyn Db) -> TypeId {
         self.register(ty, db).type_id
     }
 
-    fn resolve_module_name(&self, db: &'db dyn Db, file: ruff_db::files::File) -> Option<String> {
+    /// The offset encoder for `file`, built on first use.
+    fn encoder(&mut self, db: &'db dyn Db, file: File) -> &OffsetEncoder {
+        let encoding = self.position_encoding;
+        self.encoders
+            .entry(file)
+            .or_insert_with(|| OffsetEncoder::new(db, file, encoding))
+    }
+
+    fn resolve_module_name(&self, db: &'db dyn Db, file: File) -> Option<String> {
         ty_module_resolver::file_to_module(db, file).map(|m| m.name(db).to_string())
     }
 
     fn display_string(&self, ty: Type<'db>, db: &'db dyn Db) -> Option<String> {
-        Some(format!("{}", ty.display(db)))
+        let display = format!("{}", ty.display(db));
+        Some(match self.union_display_style {
+            UnionDisplayStyle::Pep604 => display,
+            UnionDisplayStyle::Typing => typing_union_syntax(&display),
+        })
     }
 
     fn build_type_parameters(
@@ -133,6 +445,31 @@ impl<'db> TypeRegistry<'db> {
             .collect()
     }
 
+    /// Bases that appear in a class's MRO without being written in its `class`
+    /// statement: the `Generic[...]` base of a PEP 695 generic class.
+    fn implicit_bases(&mut self, cl: ClassLiteral<'db>, db: &'db dyn Db) -> Vec<TypeId> {
+        let ClassLiteral::Static(class) = cl else {
+            return vec![];
+        };
+        class
+            .pep695_generic_context(db)
+            .map(|context| {
+                let generic = Type::KnownInstance(KnownInstanceType::SubscriptedGeneric(context));
+                self.register_component(generic, db)
+            })
+            .into_iter()
+            .collect()
+    }
+
+    /// Register a class's metaclass, unless it is the default `type` or unresolvable.
+    fn metaclass_component(&mut self, metaclass: Type<'db>, db: &'db dyn Db) -> Option<TypeId> {
+        match metaclass {
+            Type::ClassLiteral(class) if class.is_known(db, KnownClass::Type) => None,
+            Type::Dynamic(_) => None,
+            _ => Some(self.register_component(metaclass, db)),
+        }
+    }
+
     fn build_params_from_signature(
         &mut self,
         sig: &Signature<'db>,
@@ -140,19 +477,20 @@ impl<'db> TypeRegistry<'db> {
     ) -> (Vec<TypeId>, Vec<ParameterInfo>, Option<TypeId>) {
         let type_parameters = self.build_type_parameters(sig.generic_context, db);
 
-        let (in_concatenate, param_spec_name) = match sig.parameters().kind() {
-            ParametersKind::ParamSpec(tv) => (false, Some(tv.name(db).to_string())),
-            ParametersKind::Concatenate(ConcatenateTail::ParamSpec(tv)) => {
-                (true, Some(tv.name(db).to_string()))
-            }
+        let (in_concatenate, param_spec) = match sig.parameters().kind() {
+            ParametersKind::ParamSpec(tv) => (false, Some(tv)),
+            ParametersKind::Concatenate(ConcatenateTail::ParamSpec(tv)) => (true, Some(tv)),
             ParametersKind::Concatenate(ConcatenateTail::Gradual) => (true, None),
             _ => (false, None),
         };
+        let param_spec_name = param_spec.map(|tv| tv.name(db).to_string());
+        let param_spec_id = param_spec.map(|tv| self.register_component(Type::TypeVar(tv), db));
 
         let parameters: Vec<ParameterInfo> = sig
             .parameters()
             .into_iter()
             .map(|param| {
+                let annotated = !param.annotated_type().is_unknown();
                 let type_id = {
                     let ann_ty = param.annotated_type();
                     if matches!(ann_ty, Type::Dynamic(_)) {
@@ -183,19 +521,24 @@ impl<'db> TypeRegistry<'db> {
                     .map(|dt| self.register_component(dt, db));
                 let is_variadic = param.is_variadic() || param.is_keyword_variadic();
                 let concatenate_prefix = in_concatenate && !is_variadic;
-                let this_param_spec_name = if is_variadic {
-                    param_spec_name.clone()
+                let (this_param_spec_name, this_param_spec_id) = if is_variadic {
+                    (param_spec_name.clone(), param_spec_id)
                 } else {
-                    None
+                    (None, None)
                 };
                 ParameterInfo {
                     name,
                     type_id,
                     kind,
+                    annotated,
                     has_default,
                     default_type_id,
                     concatenate_prefix,
                     param_spec_name: this_param_spec_name,
+                    param_spec_id: this_param_spec_id,
+                    narrowed: false,
+                    start: None,
+                    end: None,
                 }
             })
             .collect();
@@ -224,7 +567,114 @@ impl<'db> TypeRegistry<'db> {
             Some(s) => s,
             None => return (vec![], vec![], None),
         };
-        self.build_params_from_signature(sig, db)
+        let (type_parameters, mut parameters, return_type) =
+            self.build_params_from_signature(sig, db);
+
+        // Attach the source range of each parameter in the defining `def`, and
+        // take `annotated` from the source: the signature can't tell an implicit
+        // `self: Self` from a written one. The signature is the first
+        // `@overload`'s when there are any, so its `def` is too.
+        let (overloads, implementation) = func.literal(db).overloads_and_implementation(db);
+        let Some(overload) = overloads.first().copied().or(*implementation) else {
+            return (type_parameters, parameters, return_type);
+        };
+        let definition = overload.definition(db);
+        let module = parsed_module(db, definition.file(db)).load(db);
+        if let DefinitionKind::Function(function) = definition.kind(db) {
+            let encoder = self.encoder(db, definition.file(db));
+            let sources: FxHashMap<&str, (TextRange, bool)> = function
+                .node(&module)
+                .parameters
+                .iter()
+                .map(|p| {
+                    let p = p.as_parameter();
+                    (p.name.as_str(), (p.range(), p.annotation.is_some()))
+                })
+                .collect();
+            for param in &mut parameters {
+                if let Some(&(range, annotated)) = sources.get(param.name.as_str()) {
+                    param.start = Some(encoder.encode(range.start().into()));
+                    param.end = Some(encoder.encode(range.end().into()));
+                    param.annotated = annotated;
+                }
+            }
+        }
+
+        (type_parameters, parameters, return_type)
+    }
+
+    /// Register the type arguments `class` is specialized with, if any.
+    fn specialization_args(&mut self, class: ClassType<'db>, db: &'db dyn Db) -> Vec<TypeId> {
+        class
+            .static_class_literal(db)
+            .and_then(|(_, spec)| spec)
+            .map(|spec| {
+                spec.types(db)
+                    .iter()
+                    .map(|&t| self.register_component(t, db))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Flag the parameter narrowed by a `TypeIs` / `TypeGuard` return type: the
+    /// first positional parameter after `skip` leading receiver parameters.
+    fn mark_narrowed_parameter(
+        &self,
+        parameters: &mut [ParameterInfo],
+        return_type: Option<TypeId>,
+        skip: usize,
+    ) {
+        let is_guard = return_type
+            .and_then(|id| self.descriptors.get(&id))
+            .is_some_and(|d| {
+                matches!(
+                    d,
+                    TypeDescriptor::TypeIs { .. } | TypeDescriptor::TypeGuard { .. }
+                )
+            });
+        if !is_guard {
+            return;
+        }
+        if let Some(param) = parameters
+            .iter_mut()
+            .filter(|p| matches!(p.kind, "positionalOnly" | "positionalOrKeyword"))
+            .nth(skip)
+        {
+            param.narrowed = true;
+        }
+    }
+
+    /// Whether `func`'s implementation can run off its end, and that is where
+    /// the `None` in its return type comes from: the return is unannotated or
+    /// includes `None`. Stub files have no bodies to run off.
+    fn implicit_none_return(func: FunctionType<'db>, db: &'db dyn Db) -> bool {
+        let implementation = func.literal(db).last_definition(db);
+        if implementation.definition(db).file(db).is_stub(db)
+            || !use_def_map(db, implementation.body_scope(db)).can_implicitly_return_none(db)
+        {
+            return false;
+        }
+        match implementation.signature(db).return_ty {
+            Type::Dynamic(_) => true,
+            Type::Union(union) => union.elements(db).iter().any(|member| member.is_none(db)),
+            return_ty => return_ty.is_none(db),
+        }
+    }
+
+    /// The type of the value an enum member is assigned in its class body,
+    /// such as `Literal[1]` for `RED = 1`.
+    fn enum_member_value_display(
+        enum_class: ClassLiteral<'db>,
+        member_name: &str,
+        db: &'db dyn Db,
+    ) -> Option<String> {
+        let ClassLiteral::Static(static_class) = enum_class else {
+            return None;
+        };
+        list_members::all_end_of_scope_members(db, static_class.body_scope(db))
+            .find(|mwd| mwd.member.name.as_str() == member_name)
+            .map(|mwd| format!("{}", mwd.member.ty.display(db)))
     }
 
     fn typevar_kind_str(kind: TypeVarKind) -> &'static str {
@@ -272,11 +722,20 @@ impl<'db> TypeRegistry<'db> {
                     LiteralValueTypeKind::LiteralString => {
                         TypeDescriptor::LiteralString { display }
                     }
-                    LiteralValueTypeKind::Enum(e) => TypeDescriptor::EnumLiteral {
-                        display,
-                        class_name: e.enum_class(db).name(db).to_string(),
-                        member_name: e.name(db).to_string(),
-                    },
+                    LiteralValueTypeKind::Enum(e) => {
+                        let enum_class = e.enum_class(db);
+                        let member_name = e.name(db).to_string();
+                        TypeDescriptor::EnumLiteral {
+                            display,
+                            class_name: enum_class.name(db).to_string(),
+                            member_value_display: Self::enum_member_value_display(
+                                enum_class,
+                                &member_name,
+                                db,
+                            ),
+                            member_name,
+                        }
+                    }
                 }
             }
 
@@ -290,12 +749,43 @@ impl<'db> TypeRegistry<'db> {
 
             Type::Union(union_ty) => {
                 let display = self.display_string(ty, db);
-                let members: Vec<TypeId> = union_ty
-                    .elements(db)
+                let elements = union_ty.elements(db);
+                let members: Vec<TypeId> = elements
                     .iter()
                     .map(|&member| self.register_component(member, db))
                     .collect();
-                TypeDescriptor::Union { display, members }
+                // `X | None`: point at the non-None portion, which for
+                // `X | Y | None` is the union `X | Y`.
+                let (nones, rest): (Vec<Type<'db>>, Vec<Type<'db>>) = elements
+                    .iter()
+                    .copied()
+                    .partition(|member| member.is_none(db));
+                // `Literal[1, 2, 3]` is modeled as a union of literal values,
+                // and `Literal[1, 2] | None` as the same plus `None`.
+                let literal = rest.len() > 1
+                    && rest.iter().all(|member| {
+                        matches!(member, Type::LiteralValue(literal)
+                            if !matches!(literal.kind(), LiteralValueTypeKind::LiteralString))
+                    });
+                let optional = nones.len() == 1;
+                let optional_inner = optional.then(|| {
+                    let inner = UnionType::from_elements(db, rest);
+                    self.register_component(inner, db)
+                });
+                if literal {
+                    return TypeDescriptor::LiteralUnion {
+                        display,
+                        members,
+                        optional,
+                        optional_inner,
+                    };
+                }
+                TypeDescriptor::Union {
+                    display,
+                    members,
+                    optional,
+                    optional_inner,
+                }
             }
 
             Type::Intersection(intersection_ty) => {
@@ -315,6 +805,15 @@ impl<'db> TypeRegistry<'db> {
                 }
             }
 
+            Type::NominalInstance(instance)
+                if instance
+                    .class_literal(db)
+                    .is_known(db, KnownClass::EllipsisType) =>
+            {
+                let display = self.display_string(ty, db);
+                TypeDescriptor::Ellipsis { display }
+            }
+
             Type::NominalInstance(instance) => {
                 let display = self.display_string(ty, db);
                 let cl = instance.class_literal(db);
@@ -323,18 +822,7 @@ impl<'db> TypeRegistry<'db> {
 
                 let supertypes = self.supertypes_from_class_literal(cl, db);
 
-                // Extract type arguments from specialization
-                let class_type = instance.class(db);
-                let type_args: Vec<TypeId> = class_type
-                    .static_class_literal(db)
-                    .and_then(|(_, spec)| spec)
-                    .map(|spec| {
-                        spec.types(db)
-                            .iter()
-                            .map(|&t| self.register_component(t, db))
-                            .collect()
-                    })
-                    .unwrap_or_default();
+                let type_args = self.specialization_args(instance.class(db), db);
 
                 // Register the class literal as a component
                 let class_id = Some(self.register_component(Type::ClassLiteral(cl), db));
@@ -358,17 +846,9 @@ impl<'db> TypeRegistry<'db> {
 
                     let supertypes = self.supertypes_from_class_literal(cl, db);
 
-                    let class_type = nominal.class(db);
-                    let type_args: Vec<TypeId> = class_type
-                        .static_class_literal(db)
-                        .and_then(|(_, spec)| spec)
-                        .map(|spec| {
-                            spec.types(db)
-                                .iter()
-                                .map(|&t| self.register_component(t, db))
-                                .collect()
-                        })
-                        .unwrap_or_default();
+                    // A generic protocol class keeps its specialization, so
+                    // `SupportsAbs[int]` reports `int` like a nominal generic.
+                    let type_args = self.specialization_args(nominal.class(db), db);
 
                     let class_id = Some(self.register_component(Type::ClassLiteral(cl), db));
 
@@ -381,16 +861,21 @@ impl<'db> TypeRegistry<'db> {
                         class_id,
                     }
                 } else {
-                    // Synthesized protocols have no class backing
-                    let class_name = format!("{}", ty.display(db));
-                    TypeDescriptor::Instance {
-                        display,
-                        class_name,
-                        module_name: None,
-                        supertypes: vec![],
-                        type_args: vec![],
-                        class_id: None,
-                    }
+                    // Synthesized protocols (e.g. from `hasattr` narrowing) have no
+                    // class backing; describe them by the members they require.
+                    let members: Vec<ClassMemberInfo> = instance
+                        .inner
+                        .interface(db)
+                        .members(db)
+                        .map(|member| {
+                            let type_id = self.register_component(member.ty(), db);
+                            ClassMemberInfo {
+                                name: member.name().to_string(),
+                                type_id,
+                            }
+                        })
+                        .collect();
+                    TypeDescriptor::Protocol { display, members }
                 }
             }
 
@@ -401,6 +886,7 @@ impl<'db> TypeRegistry<'db> {
                 let type_parameters =
                     self.build_type_parameters(class_literal.generic_context(db), db);
                 let supertypes = self.supertypes_from_class_literal(class_literal, db);
+                let implicit_bases = self.implicit_bases(class_literal, db);
 
                 // Extract directly-defined class members (not inherited)
                 let members: Vec<ClassMemberInfo> = match class_literal {
@@ -418,43 +904,42 @@ impl<'db> TypeRegistry<'db> {
                     _ => vec![],
                 };
 
+                let metaclass = self.metaclass_component(class_literal.metaclass(db), db);
+                let is_protocol = class_literal.is_protocol(db);
+                let runtime_checkable = class_literal
+                    .into_protocol_class(db)
+                    .is_some_and(|protocol| protocol.is_runtime_checkable(db));
+
                 TypeDescriptor::ClassLiteral {
                     display,
                     class_name,
                     module_name,
                     type_parameters,
                     supertypes,
+                    implicit_bases,
                     members,
+                    metaclass,
+                    is_protocol,
+                    runtime_checkable,
                 }
             }
 
             Type::GenericAlias(alias) => {
                 let display = self.display_string(ty, db);
-                let origin = alias.origin(db);
-                let class_name = origin.name(db).to_string();
-                let module_name = self.resolve_module_name(db, origin.file(db));
-                let supertypes: Vec<TypeId> = origin
-                    .explicit_bases(db)
+                let origin = self.register_component(
+                    Type::ClassLiteral(ClassLiteral::Static(alias.origin(db))),
+                    db,
+                );
+                let type_args = alias
+                    .specialization(db)
+                    .types(db)
                     .iter()
-                    .map(|&base| self.register_component(base, db))
+                    .map(|&t| self.register_component(t, db))
                     .collect();
-                let members: Vec<ClassMemberInfo> =
-                    list_members::all_end_of_scope_members(db, origin.body_scope(db))
-                        .map(|mwd| {
-                            let type_id = self.register_component(mwd.member.ty, db);
-                            ClassMemberInfo {
-                                name: mwd.member.name.to_string(),
-                                type_id,
-                            }
-                        })
-                        .collect();
-                TypeDescriptor::ClassLiteral {
+                TypeDescriptor::GenericAlias {
                     display,
-                    class_name,
-                    module_name,
-                    type_parameters: vec![],
-                    supertypes,
-                    members,
+                    origin,
+                    type_args,
                 }
             }
 
@@ -485,7 +970,17 @@ impl<'db> TypeRegistry<'db> {
                 let display = self.display_string(ty, db);
                 let name = func.name(db).to_string();
                 let module_name = self.resolve_module_name(db, func.file(db));
-                let (type_parameters, parameters, return_type) = self.build_function_params(ty, db);
+                let (type_parameters, mut parameters, return_type) =
+                    self.build_function_params(ty, db);
+                // A method defined in a class body narrows the parameter after
+                // its receiver; a staticmethod has no receiver.
+                let in_class = matches!(
+                    func.definition(db).scope(db).node(db),
+                    NodeWithScopeKind::Class(_)
+                );
+                let skip = usize::from(in_class && !func.is_staticmethod(db));
+                self.mark_narrowed_parameter(&mut parameters, return_type, skip);
+                let implicit_none_return = Self::implicit_none_return(func, db);
                 TypeDescriptor::Function {
                     display,
                     name,
@@ -493,6 +988,7 @@ impl<'db> TypeRegistry<'db> {
                     type_parameters,
                     parameters,
                     return_type,
+                    implicit_none_return,
                 }
             }
 
@@ -500,18 +996,26 @@ impl<'db> TypeRegistry<'db> {
                 let display = self.display_string(ty, db);
                 let sigs = callable_ty.signatures(db);
                 if let Some(sig) = sigs.iter().next() {
-                    let (_type_params, parameters, return_type) =
+                    let (_type_params, mut parameters, return_type) =
                         self.build_params_from_signature(sig, db);
+                    // `...` is modeled as gradual `*args: Any, **kwargs: Any`.
+                    let unspecified_params =
+                        matches!(sig.parameters().kind(), ParametersKind::Gradual);
+                    if unspecified_params {
+                        parameters.clear();
+                    }
                     TypeDescriptor::Callable {
                         display,
                         parameters,
                         return_type,
+                        unspecified_params,
                     }
                 } else {
                     TypeDescriptor::Callable {
                         display,
                         parameters: vec![],
                         return_type: None,
+                        unspecified_params: false,
                     }
                 }
             }
@@ -522,7 +1026,8 @@ impl<'db> TypeRegistry<'db> {
                 let func_ty = Type::FunctionLiteral(func);
                 let name = Some(func.name(db).to_string());
                 // Derive class name from the self_instance type
-                let class_name = match bound.self_instance(db) {
+                let self_instance = bound.self_instance(db);
+                let class_name = match self_instance {
                     Type::NominalInstance(inst) => {
                         Some(inst.class_literal(db).name(db).to_string())
                     }
@@ -532,8 +1037,11 @@ impl<'db> TypeRegistry<'db> {
                     _ => None,
                 };
                 let module_name = self.resolve_module_name(db, func.file(db));
-                let (type_parameters, parameters, return_type) =
+                let (type_parameters, mut parameters, return_type) =
                     self.build_function_params(func_ty, db);
+                // The unbound signature still lists the receiver first.
+                self.mark_narrowed_parameter(&mut parameters, return_type, 1);
+                let receiver = Some(self.register_component(self_instance, db));
                 TypeDescriptor::BoundMethod {
                     display,
                     name,
@@ -542,16 +1050,24 @@ impl<'db> TypeRegistry<'db> {
                     type_parameters,
                     parameters,
                     return_type,
+                    receiver,
                 }
             }
 
             Type::KnownBoundMethod(known_bound) => {
                 let display = self.display_string(ty, db);
                 let class_name = Some(known_bound.class().name(db).to_string());
-                let sigs: Vec<_> = known_bound.signatures(db).collect();
-                let (type_parameters, parameters, return_type) = sigs
-                    .first()
-                    .map(|sig| self.build_params_from_signature(sig, db))
+                // Prefer the method's own signature; fall back to its callable
+                // upcast, which covers known methods that only expose a
+                // synthesized signature through the callable protocol.
+                let sig = known_bound.signatures(db).next().or_else(|| {
+                    match ty.try_upcast_to_callable(db)?.into_type(db) {
+                        Type::Callable(callable) => callable.signatures(db).iter().next().cloned(),
+                        _ => None,
+                    }
+                });
+                let (type_parameters, parameters, return_type) = sig
+                    .map(|sig| self.build_params_from_signature(&sig, db))
                     .unwrap_or((vec![], vec![], None));
                 TypeDescriptor::BoundMethod {
                     display,
@@ -561,6 +1077,7 @@ impl<'db> TypeRegistry<'db> {
                     type_parameters,
                     parameters,
                     return_type,
+                    receiver: None,
                 }
             }
 
@@ -573,6 +1090,21 @@ impl<'db> TypeRegistry<'db> {
                 }
             }
 
+            Type::TypeVar(bound_tv) if bound_tv.typevar(db).is_self(db) => {
+                let display = self.display_string(ty, db);
+                // `Self` is bounded by an instance of the enclosing class.
+                let bound_class = match bound_tv.typevar(db).upper_bound(db) {
+                    Some(Type::NominalInstance(instance)) => {
+                        Some(self.register_component(Type::from(instance.class(db)), db))
+                    }
+                    _ => None,
+                };
+                TypeDescriptor::SelfType {
+                    display,
+                    bound_class,
+                }
+            }
+
             Type::TypeVar(bound_tv) => {
                 let display = self.display_string(ty, db);
                 let name = bound_tv.name(db).to_string();
@@ -580,6 +1112,9 @@ impl<'db> TypeRegistry<'db> {
                 let typevar_kind = Some(Self::typevar_kind_str(kind).to_string());
 
                 let typevar = bound_tv.typevar(db);
+                let module_name = typevar
+                    .definition(db)
+                    .and_then(|definition| self.resolve_module_name(db, definition.file(db)));
 
                 let variance = Some(
                     match bound_tv.variance(db) {
@@ -603,14 +1138,24 @@ impl<'db> TypeRegistry<'db> {
                     .default_type(db)
                     .map(|dt| self.register_component(dt, db));
 
+                let owner = match bound_tv.binding_context(db) {
+                    BindingContext::Definition(definition) => {
+                        let owner_ty = binding_type(db, definition);
+                        (!owner_ty.is_unknown()).then(|| self.register_component(owner_ty, db))
+                    }
+                    _ => None,
+                };
+
                 TypeDescriptor::TypeVar {
                     display,
                     name,
+                    module_name,
                     typevar_kind,
                     variance,
                     upper_bound,
                     constraints,
                     default_type,
+                    owner,
                 }
             }
 
@@ -652,6 +1197,10 @@ impl<'db> TypeRegistry<'db> {
                         }
                     })
                     .collect();
+                let (required, optional): (Vec<_>, Vec<_>) =
+                    fields.iter().partition(|field| field.required);
+                let required_keys = required.into_iter().map(|f| f.name.clone()).collect();
+                let optional_keys = optional.into_iter().map(|f| f.name.clone()).collect();
                 // PEP 728 openness: `Closed` forbids undeclared keys, `Extra` exposes
                 // them with a declared type and mutability. `ImplicitlyOpen` (the
                 // default) carries neither flag.
@@ -668,6 +1217,8 @@ impl<'db> TypeRegistry<'db> {
                     display,
                     name,
                     fields,
+                    required_keys,
+                    optional_keys,
                     closed,
                     extra_items,
                 }
@@ -679,6 +1230,7 @@ impl<'db> TypeRegistry<'db> {
                 TypeDescriptor::TypeIs {
                     display,
                     narrowed_type,
+                    param_name: type_is.place_name(db),
                 }
             }
 
@@ -688,6 +1240,7 @@ impl<'db> TypeRegistry<'db> {
                 TypeDescriptor::TypeGuard {
                     display,
                     guarded_type,
+                    param_name: type_guard.place_name(db),
                 }
             }
 
@@ -776,3 +1329,139 @@ impl<'db> TypeRegistry<'db> {
         }
     }
 }
+
+/// `descriptor` with every component type ID zeroed, to compare descriptors
+/// of types registered against different revisions.
+fn shape(descriptor: &TypeDescriptor) -> TypeDescriptor {
+    let mut shape = descriptor.clone();
+    for edge in shape.edges_mut() {
+        *edge = 0;
+    }
+    shape
+}
+
+/// Rewrite every PEP 604 union in a ty display string in `typing` style, at
+/// any depth: `X | None` becomes `Optional[X]`, and any other union
+/// `Union[X, Y, ...]`. Quoted strings, as in `Literal["a | b"]`, are copied
+/// as is.
+fn typing_union_syntax(display: &str) -> String {
+    UnionRewriter {
+        text: display,
+        pos: 0,
+    }
+    .sequence(None)
+}
+
+/// Separators between the unions of a display string, besides brackets: in
+/// type argument lists, and in the parameters and return type of signatures.
+const DISPLAY_SEPARATORS: &[&str] = &[", ", ": ", " -> ", " = "];
+
+/// The state of `typing_union_syntax`: the display string and the offset
+/// reached in it.
+struct UnionRewriter<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl UnionRewriter<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.pos..]
+    }
+
+    fn at_separator(&self) -> Option<&'static str> {
+        DISPLAY_SEPARATORS
+            .iter()
+            .copied()
+            .find(|separator| self.rest().starts_with(separator))
+    }
+
+    /// Unions and the separators between them, up to `closer` (which is left
+    /// unread) or the end of the string.
+    fn sequence(&mut self, closer: Option<char>) -> String {
+        let mut out = String::new();
+        loop {
+            out.push_str(&self.union());
+            if let Some(separator) = self.at_separator() {
+                out.push_str(separator);
+                self.pos += separator.len();
+                continue;
+            }
+            match self.rest().chars().next() {
+                None => return out,
+                Some(c) if Some(c) == closer => return out,
+                // An unbalanced closing bracket: keep it and carry on.
+                Some(c) => {
+                    out.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    /// Operands separated by ` | `, rendered in `typing` style.
+    fn union(&mut self) -> String {
+        let mut operands = vec![self.operand()];
+        while self.rest().starts_with(" | ") {
+            self.pos += " | ".len();
+            operands.push(self.operand());
+        }
+        if operands.len() == 1 {
+            return operands.pop().unwrap_or_default();
+        }
+        let displays: Vec<&str> = operands.iter().map(String::as_str).collect();
+        match displays.as_slice() {
+            [inner, "None"] | ["None", inner] => format!("Optional[{inner}]"),
+            _ => format!("Union[{}]", operands.join(", ")),
+        }
+    }
+
+    /// One union operand: everything up to a ` | `, a separator or a
+    /// closing bracket, with the unions inside its own brackets rewritten.
+    fn operand(&mut self) -> String {
+        let mut out = String::new();
+        while let Some(c) = self.rest().chars().next() {
+            if self.rest().starts_with(" | ") || self.at_separator().is_some() {
+                break;
+            }
+            match c {
+                '[' | '(' => {
+                    let closer = if c == '[' { ']' } else { ')' };
+                    self.pos += 1;
+                    out.push(c);
+                    out.push_str(&self.sequence(Some(closer)));
+                    if self.rest().starts_with(closer) {
+                        self.pos += 1;
+                        out.push(closer);
+                    }
+                }
+                ']' | ')' => break,
+                '\'' | '"' => {
+                    let end = self.quoted_end(c);
+                    out.push_str(&self.text[self.pos..end]);
+                    self.pos = end;
+                }
+                _ => {
+                    out.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+        out
+    }
+
+    /// The offset just past the string literal that opens with `quote` at
+    /// the current offset, or the end of the string if it never closes.
+    fn quoted_end(&self, quote: char) -> usize {
+        let mut escaped = false;
+        for (i, c) in self.rest().char_indices().skip(1) {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                return self.pos + i + 1;
+            }
+        }
+        self.text.len()
+    }
+}
@@ -0,0 +1,72 @@
+use crate::protocol::{NodeAttribution, SemanticToken, TypeDescriptor};
+use crate::registry::TypeRegistry;
+
+/// Project collected nodes onto semantic-token-style identifier classifications.
+///
+/// Only identifier-shaped nodes are emitted: `ExprName`, `Alias`, and the name of
+/// each `Parameter`. The token type is derived from the kind of the node's
+/// resolved type descriptor.
+pub fn token_types(
+    nodes: &[NodeAttribution],
+    registry: &TypeRegistry<'_>,
+    source: &str,
+) -> Vec<SemanticToken> {
+    let mut tokens: Vec<SemanticToken> = nodes
+        .iter()
+        .filter_map(|node| {
+            let descriptor = node.type_id.and_then(|id| registry.get_descriptor(id));
+            let mut modifiers = Vec::new();
+            if descriptor.and_then(TypeDescriptor::module_name) == Some("builtins") {
+                modifiers.push("defaultLibrary");
+            }
+
+            match node.node_kind.as_ref() {
+                "ExprName" | "Alias" => Some(SemanticToken {
+                    start: node.start,
+                    length: node.end - node.start,
+                    token_type: descriptor.map_or("variable", token_type_for),
+                    modifiers,
+                }),
+                "Parameter" => {
+                    // The parameter node spans `name: annotation`; only color the name.
+                    let length = identifier_len(source, node.start as usize);
+                    modifiers.push("definition");
+                    Some(SemanticToken {
+                        start: node.start,
+                        length,
+                        token_type: "parameter",
+                        modifiers,
+                    })
+                }
+                _ => None,
+            }
+        })
+        .collect();
+
+    tokens.sort_by_key(|token| token.start);
+    tokens
+}
+
+fn token_type_for(descriptor: &TypeDescriptor) -> &'static str {
+    match descriptor {
+        TypeDescriptor::Function { .. } => "function",
+        TypeDescriptor::BoundMethod { .. } | TypeDescriptor::WrapperDescriptor { .. } => "method",
+        TypeDescriptor::ClassLiteral { .. }
+        | TypeDescriptor::GenericAlias { .. }
+        | TypeDescriptor::SubclassOf { .. } => "class",
+        TypeDescriptor::Module { .. } => "module",
+        TypeDescriptor::TypeVar { .. } | TypeDescriptor::SelfType { .. } => "typeParameter",
+        TypeDescriptor::TypeAlias { .. } => "type",
+        _ => "variable",
+    }
+}
+
+fn identifier_len(source: &str, start: usize) -> u32 {
+    source
+        .get(start..)
+        .unwrap_or_default()
+        .chars()
+        .take_while(|c| c.is_alphanumeric() || *c == '_')
+        .map(|c| c.len_utf8() as u32)
+        .sum()
+}
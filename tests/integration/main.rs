@@ -8,10 +8,16 @@ use protocol::*;
 
 /// Helper: spawn ty-types, send JSON-RPC requests, collect responses.
 fn run_session(requests: &[&str]) -> Vec<serde_json::Value> {
+    run_session_with_args(&[], requests)
+}
+
+/// Helper: like `run_session`, passing extra command-line arguments after `--serve`.
+fn run_session_with_args(args: &[&str], requests: &[&str]) -> Vec<serde_json::Value> {
     let binary = env!("CARGO_BIN_EXE_ty-types");
 
     let mut child = Command::new(binary)
         .arg("--serve")
+        .args(args)
         .stdin(Stdio::piped())
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
@@ -38,6 +44,24 @@ fn run_session(requests: &[&str]) -> Vec<serde_json::Value> {
     responses
 }
 
+/// Helper: run ty-types in one-shot mode with the given arguments, parse its JSON output.
+fn run_oneshot(args: &[&str]) -> serde_json::Value {
+    let binary = env!("CARGO_BIN_EXE_ty-types");
+
+    let output = Command::new(binary)
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .expect("failed to run ty-types");
+    assert!(
+        output.status.success(),
+        "ty-types exited with {}",
+        output.status
+    );
+
+    serde_json::from_slice(&output.stdout).unwrap()
+}
+
 /// Helper: create a temp dir with Python files, return the path.
 fn create_test_project(files: &[(&str, &str)]) -> tempfile::TempDir {
     let dir = tempfile::tempdir().unwrap();
@@ -262,6 +286,55 @@ class Animal:
     assert!(has_str, "should have 'str' instance type");
 }
 
+#[test]
+fn test_self_referential_class() {
+    let dir = create_test_project(&[(
+        "node.py",
+        "class Node:\n    next: \"Node\"\n\nn = Node()\nm = n.next\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("node.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let (class_id, class) = types
+        .iter()
+        .find(|(_, t)| t["kind"] == "classLiteral" && t["className"] == "Node")
+        .expect("should have a classLiteral for 'Node'");
+    assert_eq!(class["display"], "<class 'Node'>");
+
+    // The member points at the instance type, which points back at the class.
+    let next = class["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|m| m["name"] == "next")
+        .expect("Node should have a 'next' member");
+    let instance = &types[&next["typeId"].to_string()];
+    assert_eq!(instance["kind"], "instance");
+    assert_eq!(instance["display"], "Node");
+    assert_eq!(instance["classId"].to_string(), *class_id);
+
+    // `n.next` has the same type as `n`.
+    let type_id_of = |kind: &str| {
+        nodes
+            .iter()
+            .find(|n| n.node_kind == kind)
+            .and_then(|n| n.type_id)
+            .unwrap_or_else(|| panic!("should have a typed {kind} node"))
+    };
+    assert_eq!(
+        type_id_of("ExprAttribute"),
+        next["typeId"].as_u64().unwrap() as u32
+    );
+}
+
 #[test]
 fn test_union_type() {
     let dir = create_test_project(&[("u.py", "x: int | str = 42\n")]);
@@ -1148,3 +1221,4315 @@ fn test_non_paramspec_signature_has_no_flags() {
         );
     }
 }
+
+#[test]
+fn test_parameter_source_range() {
+    let source = "def f(a: int, b: str = \"x\") -> None: pass\n";
+    let dir = create_test_project(&[("pr.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("pr.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let func_type = types
+        .values()
+        .find(|t| t["kind"] == "function" && t["name"] == "f")
+        .expect("should have function 'f'");
+    let b_param = func_type["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == "b")
+        .expect("should have parameter 'b'");
+
+    // The range covers `b: str`, excluding the default.
+    let expected_start = source.find("b: str").unwrap() as u64;
+    assert_eq!(b_param["start"].as_u64(), Some(expected_start));
+    assert_eq!(
+        b_param["end"].as_u64(),
+        Some(expected_start + "b: str".len() as u64)
+    );
+}
+
+#[test]
+fn test_overloaded_parameter_source_range() {
+    let source = "from typing import overload\n\n@overload\ndef f(x: int) -> int: ...\n@overload\ndef f(x: str) -> str: ...\ndef f(x):\n    return x\n";
+    let dir = create_test_project(&[("ov.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("ov.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    // The parameters come from the first overload's signature, so their
+    // ranges must too, not the implementation's.
+    let expected_start = source.find("x: int").unwrap() as u64;
+    let functions: Vec<_> = types
+        .values()
+        .filter(|t| t["kind"] == "function" && t["name"] == "f")
+        .collect();
+    assert!(!functions.is_empty(), "should have function 'f'");
+    for func in functions {
+        let x_param = &func["parameters"][0];
+        assert_eq!(x_param["name"], "x");
+        assert_eq!(x_param["start"].as_u64(), Some(expected_start), "{func:?}");
+        assert_eq!(x_param["annotated"], true, "{func:?}");
+    }
+}
+
+#[test]
+fn test_builtin_bound_method_signature() {
+    let dir = create_test_project(&[("bm.py", "xs: list[int] = []\nappend = xs.append\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("bm.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let append = types
+        .values()
+        .find(|t| t["kind"] == "boundMethod" && t["name"] == "append")
+        .expect("should have a boundMethod for 'append'");
+    assert!(
+        !append["parameters"].as_array().unwrap().is_empty(),
+        "list.append should expose its parameters, got {:?}",
+        append
+    );
+    assert!(
+        append.get("returnType").is_some(),
+        "list.append should expose its return type, got {:?}",
+        append
+    );
+}
+
+#[test]
+fn test_get_token_types() {
+    let source = "import os\n\ndef greet(name: str) -> str:\n    return name\n\nclass Animal: ...\n\ngreet(\"x\")\nAnimal()\n";
+    let dir = create_test_project(&[("tok.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTokenTypes",
+            "params": {"file": "tok.py"},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let tokens = responses[1]["result"]["tokens"]
+        .as_array()
+        .expect("getTokenTypes should return a tokens array");
+    let token_at = |offset: usize| {
+        tokens
+            .iter()
+            .find(|t| t["start"].as_u64() == Some(offset as u64))
+            .unwrap_or_else(|| panic!("no token at offset {offset}: {tokens:?}"))
+    };
+
+    let greet_call = source.rfind("greet").unwrap();
+    assert_eq!(token_at(greet_call)["tokenType"], "function");
+    assert_eq!(token_at(greet_call)["length"], 5);
+
+    let animal_call = source.rfind("Animal").unwrap();
+    assert_eq!(token_at(animal_call)["tokenType"], "class");
+
+    let name_param = source.find("name:").unwrap();
+    assert_eq!(token_at(name_param)["tokenType"], "parameter");
+    assert_eq!(token_at(name_param)["length"], 4);
+}
+
+#[test]
+fn test_relative_import_alias() {
+    let dir = create_test_project(&[
+        ("pkg/__init__.py", ""),
+        ("pkg/a.py", "x: int = 1\n"),
+        ("pkg/b.py", "from .a import x\nfrom . import a\n"),
+    ]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("pkg/b.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let aliases: Vec<&NodeInfo> = nodes.iter().filter(|n| n.node_kind == "Alias").collect();
+    assert_eq!(
+        aliases.len(),
+        2,
+        "expected two Alias nodes, got {aliases:?}"
+    );
+
+    // `from .a import x` resolves `x` to `int`.
+    let x_type = &types[&aliases[0]
+        .type_id
+        .expect("x should have a type")
+        .to_string()];
+    assert_eq!(x_type["display"], "int");
+
+    // `from . import a` resolves to the sibling module.
+    let a_type = &types[&aliases[1]
+        .type_id
+        .expect("a should have a type")
+        .to_string()];
+    assert_eq!(a_type["kind"], "module");
+    assert_eq!(a_type["moduleName"], "pkg.a");
+}
+
+#[test]
+fn test_matched_overload_index() {
+    let dir = create_test_project(&[(
+        "ov.py",
+        "from typing import overload\n\
+         @overload\n\
+         def f(x: int) -> int: ...\n\
+         @overload\n\
+         def f(x: str) -> str: ...\n\
+         def f(x): return x\n\
+         def g(x: int) -> int: return x\n\
+         a = f(1)\n\
+         b = f(\"s\")\n\
+         c = g(1)\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("ov.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+
+    let calls: Vec<&CallSignatureInfo> = nodes
+        .iter()
+        .filter(|n| n.node_kind == "ExprCall")
+        .filter_map(|n| n.call_signature.as_ref())
+        .collect();
+    assert_eq!(calls.len(), 3, "expected three call signatures");
+
+    assert_eq!(
+        calls[0].matched_overload,
+        Some(0),
+        "f(1) selects overload 0"
+    );
+    assert_eq!(
+        calls[1].matched_overload,
+        Some(1),
+        "f(\"s\") selects overload 1"
+    );
+    assert_eq!(
+        calls[2].matched_overload, None,
+        "non-overloaded call has no matchedOverload"
+    );
+}
+
+#[test]
+fn test_dump_registry_graph() {
+    let dir = create_test_project(&[("gr.py", "x: int | str = 42\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("gr.py", 2),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "dumpRegistryGraph",
+            "id": 3
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let graph = &responses[2]["result"];
+    let nodes = graph["nodes"].as_array().expect("nodes array");
+    let edges = graph["edges"].as_array().expect("edges array");
+
+    let union = nodes
+        .iter()
+        .find(|n| n["kind"] == "union" && n["display"] == "int | str")
+        .expect("graph should have the 'int | str' union node");
+
+    let member_targets: Vec<&serde_json::Value> = edges
+        .iter()
+        .filter(|e| e["from"] == union["id"] && e["label"] == "member")
+        .map(|e| &e["to"])
+        .collect();
+    assert_eq!(
+        member_targets.len(),
+        2,
+        "union should have an edge per member"
+    );
+
+    for display in ["int", "str"] {
+        let member = nodes
+            .iter()
+            .find(|n| n["kind"] == "instance" && n["display"] == display)
+            .unwrap_or_else(|| panic!("graph should have a node for '{display}'"));
+        assert!(
+            member_targets.contains(&&member["id"]),
+            "union should have a member edge to '{display}'"
+        );
+    }
+}
+
+#[test]
+fn test_oneshot_no_display() {
+    let dir = create_test_project(&[("nd.py", "x: int | str = 42\n")]);
+    let file = dir.path().join("nd.py");
+
+    let with_display = run_oneshot(&[file.to_str().unwrap()]);
+    let types = with_display["types"].as_object().unwrap();
+    assert!(
+        types.values().any(|t| t.get("display").is_some()),
+        "default one-shot output should include display strings"
+    );
+
+    let without_display = run_oneshot(&[file.to_str().unwrap(), "--no-display"]);
+    let types = without_display["types"].as_object().unwrap();
+    assert!(!types.is_empty());
+    for t in types.values() {
+        assert!(
+            t.get("display").is_none(),
+            "--no-display should strip display, got {:?}",
+            t
+        );
+    }
+}
+
+#[test]
+fn test_except_handler_binding() {
+    let dir = create_test_project(&[(
+        "ex.py",
+        "try:\n    pass\nexcept ValueError as e:\n    print(e)\nexcept KeyError:\n    pass\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("ex.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let handlers: Vec<&NodeInfo> = nodes
+        .iter()
+        .filter(|n| n.node_kind == "ExceptHandler")
+        .collect();
+    assert_eq!(handlers.len(), 2, "expected two ExceptHandler nodes");
+
+    let e_type = &types[&handlers[0]
+        .type_id
+        .expect("named handler should have a type")
+        .to_string()];
+    assert_eq!(e_type["kind"], "instance");
+    assert_eq!(e_type["className"], "ValueError");
+
+    assert!(
+        handlers[1].type_id.is_none(),
+        "unnamed handler has no binding to attribute"
+    );
+}
+
+#[test]
+fn test_ref_counts() {
+    let dir = create_test_project(&[(
+        "rc.py",
+        "def add(a: int, b: int) -> int:\n    return a + b\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "rc.py", "includeRefCounts": true},
+            "id": 2
+        })
+        .to_string(),
+        &get_types_request("rc.py", 3),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let (int_id, _) = types
+        .iter()
+        .find(|(_, t)| t["kind"] == "instance" && t["display"] == "int")
+        .expect("should have 'int' instance type");
+
+    let int_refs = result["refCounts"][int_id]
+        .as_u64()
+        .expect("refCounts should have an entry for 'int'");
+    assert!(int_refs > 1, "'int' should be referenced more than once");
+
+    assert!(
+        responses[2]["result"].get("refCounts").is_none(),
+        "refCounts should be omitted unless requested"
+    );
+}
+
+#[test]
+fn test_literal_union() {
+    let dir = create_test_project(&[(
+        "lu.py",
+        "from typing import Literal\nx: Literal[1, 2, 3]\ny: Literal[1] | None\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("lu.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let literal_union = types
+        .values()
+        .find(|t| t["kind"] == "literalUnion")
+        .expect("Literal[1, 2, 3] should produce a literalUnion");
+    let members = literal_union["members"].as_array().unwrap();
+    assert_eq!(members.len(), 3);
+    for member in members {
+        assert_eq!(types[&member.to_string()]["kind"], "intLiteral");
+    }
+
+    // A union mixing a literal with None stays a plain union.
+    assert!(
+        types
+            .values()
+            .any(|t| t["kind"] == "union" && t["display"] == "Literal[1] | None"),
+        "Literal[1] | None should remain a union"
+    );
+}
+
+#[test]
+fn test_literal_union_optional_and_inferred() {
+    let source = "from typing import Literal\n\
+                  a: Literal[1, 2] | None\n\
+                  def f(c: bool, d: bool):\n    \
+                      x = 1 if c else 2\n    \
+                      y = 1 if c else None\n    \
+                      z = 1 if c else 2 if d else None\n";
+    let dir = create_test_project(&[("lu.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("lu.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let find = |display: &str| {
+        types
+            .values()
+            .find(|t| t["display"] == display)
+            .unwrap_or_else(|| panic!("should have {display}, got {types:?}"))
+    };
+
+    // The literals plus `None` still make a literal union, flagged optional.
+    let optional = find("Literal[1, 2] | None");
+    assert_eq!(optional["kind"], "literalUnion");
+    assert_eq!(optional["optional"], true);
+    assert_eq!(optional["members"].as_array().unwrap().len(), 3);
+    let inner = &types[&optional["optionalInner"].to_string()];
+    assert_eq!(inner["kind"], "literalUnion");
+    assert_eq!(inner["display"], "Literal[1, 2]");
+    assert!(inner.get("optional").is_none());
+
+    // An inferred union of literals is the same type as the annotation.
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let kind_of = |target: &str| {
+        let start = source.find(target).unwrap() as u32;
+        let node = nodes
+            .iter()
+            .find(|n| n.node_kind == "ExprName" && n.start == start)
+            .unwrap_or_else(|| panic!("should have a node for {target}"));
+        types[&node.type_id.unwrap().to_string()]["kind"].clone()
+    };
+    assert_eq!(kind_of("x = "), "literalUnion");
+    // A single literal with `None` stays a plain union.
+    assert_eq!(kind_of("y = "), "union");
+    assert_eq!(kind_of("z = "), "literalUnion");
+}
+
+#[test]
+fn test_get_imports() {
+    let dir = create_test_project(&[
+        ("sibling.py", "x: int = 1\n"),
+        (
+            "importer.py",
+            "import sibling\nfrom sibling import x\nimport missing_module\n",
+        ),
+    ]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getImports",
+            "params": {"file": "importer.py"},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let imports = responses[1]["result"]["imports"]
+        .as_array()
+        .expect("getImports should return an imports array");
+    assert_eq!(imports.len(), 3, "got {imports:?}");
+
+    for import in &imports[..2] {
+        assert_eq!(import["module"], "sibling");
+        let path = import["resolvedPath"]
+            .as_str()
+            .expect("sibling import should resolve");
+        assert!(path.ends_with("sibling.py"), "got {path}");
+        assert!(import.get("unresolved").is_none());
+    }
+    assert_eq!(imports[1]["names"], serde_json::json!(["x"]));
+
+    assert_eq!(imports[2]["module"], "missing_module");
+    assert_eq!(imports[2]["unresolved"], true);
+    assert!(imports[2].get("resolvedPath").is_none());
+}
+
+#[test]
+fn test_optional_union() {
+    let dir = create_test_project(&[(
+        "opt.py",
+        "a: int | str | None\nb: int | None\nc: int | str\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("opt.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let union = |display: &str| {
+        types
+            .values()
+            .find(|t| t["kind"] == "union" && t["display"] == display)
+            .unwrap_or_else(|| panic!("should have a '{display}' union"))
+    };
+
+    let a = union("int | str | None");
+    assert_eq!(a["optional"], true);
+    let inner = &types[&a["optionalInner"].to_string()];
+    assert_eq!(inner["kind"], "union");
+    assert_eq!(inner["display"], "int | str");
+
+    let b = union("int | None");
+    assert_eq!(b["optional"], true);
+    assert_eq!(types[&b["optionalInner"].to_string()]["display"], "int");
+
+    let c = union("int | str");
+    assert!(c.get("optional").is_none());
+    assert!(c.get("optionalInner").is_none());
+}
+
+#[test]
+fn test_worker_pool_out_of_order_responses() {
+    let slow: String = (0..2000)
+        .map(|i| format!("def f{i}(x: int) -> list[int]:\n    return [x, f{i}(x)[0]]\n"))
+        .collect();
+    let dir = create_test_project(&[("slow.py", &slow), ("fast.py", "x = 1\n")]);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .args(["--serve", "--workers", "2"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn ty-types");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut responses = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(&l.unwrap()).unwrap());
+
+    writeln!(
+        stdin,
+        "{}",
+        initialize_request(dir.path().to_str().unwrap(), 1)
+    )
+    .unwrap();
+    writeln!(stdin, "{}", get_types_request("fast.py", 2)).unwrap();
+    let first: Vec<serde_json::Value> = responses.by_ref().take(2).collect();
+    assert_eq!(first[1]["id"], 2);
+
+    // fast.py is inferred now, so the session thread answers it again as
+    // soon as it reads the request, while a worker infers slow.py. Both
+    // requests go in one write, so they are read together.
+    let requests = format!(
+        "{}\n{}\n{}\n",
+        get_types_request("slow.py", 3),
+        get_types_request("fast.py", 4),
+        shutdown_request(99)
+    );
+    stdin.write_all(requests.as_bytes()).unwrap();
+    drop(stdin);
+
+    let rest: Vec<serde_json::Value> = responses.collect();
+    child.wait().unwrap();
+    let ids: Vec<Option<u64>> = rest.iter().map(|r| r["id"].as_u64()).collect();
+    assert_eq!(
+        ids,
+        [Some(4), Some(3), Some(99)],
+        "fast.py should respond first, and shutdown wait for slow.py"
+    );
+
+    let slow_types: TypeMap = serde_json::from_value(rest[1]["result"]["types"].clone()).unwrap();
+    assert!(slow_types.values().any(|t| t["name"] == "f1999"));
+}
+
+#[test]
+fn test_method_type_guard_narrows_parameter_after_receiver() {
+    let dir = create_test_project(&[(
+        "mg.py",
+        "from typing_extensions import TypeIs\n\
+         class Checker:\n\
+         \x20   def is_str(self, v: object) -> TypeIs[str]: ...\n\
+         \x20   @staticmethod\n\
+         \x20   def is_int(v: object) -> TypeIs[int]: ...\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("mg.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let narrowed_params = |name: &str| -> Vec<String> {
+        let func = types
+            .values()
+            .find(|t| t["kind"] == "function" && t["name"] == name)
+            .unwrap_or_else(|| panic!("should have function '{name}'"));
+        func["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|p| p["narrowed"] == true)
+            .map(|p| p["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+    assert_eq!(narrowed_params("is_str"), vec!["v"]);
+    assert_eq!(narrowed_params("is_int"), vec!["v"]);
+}
+
+#[test]
+fn test_type_guard_parameter_linkage() {
+    let dir = create_test_project(&[(
+        "tg.py",
+        "from typing_extensions import TypeIs\n\
+         def is_str(x: object, strict: bool = False) -> TypeIs[str]: ...\n\
+         v: object = 1\n\
+         r = is_str(v)\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("tg.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let is_str = types
+        .values()
+        .find(|t| t["kind"] == "function" && t["name"] == "is_str")
+        .expect("should have function 'is_str'");
+
+    let guard = &types[&is_str["returnType"].to_string()];
+    assert_eq!(guard["kind"], "typeIs");
+    assert_eq!(types[&guard["narrowedType"].to_string()]["display"], "str");
+
+    let narrowed: Vec<&serde_json::Value> = is_str["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|p| p["narrowed"] == true)
+        .collect();
+    assert_eq!(narrowed.len(), 1, "exactly one parameter is narrowed");
+    assert_eq!(narrowed[0]["name"], "x");
+
+    // At the call site, the guard is bound to the argument.
+    let call = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprCall")
+        .expect("should have the is_str(v) call");
+    let bound_guard = &types[&call.type_id.expect("call should have a type").to_string()];
+    assert_eq!(bound_guard["kind"], "typeIs");
+    assert_eq!(bound_guard["paramName"], "v");
+}
+
+#[test]
+fn test_replay_log() {
+    let dir = create_test_project(&[("a.py", "x: int = 42\n")]);
+    let log = [
+        initialize_request(dir.path().to_str().unwrap(), 1),
+        get_types_request("a.py", 2),
+        shutdown_request(99),
+    ]
+    .join("\n");
+    let log_path = dir.path().join("requests.log");
+    std::fs::write(&log_path, log).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .arg("--replay")
+        .arg(&log_path)
+        .stderr(Stdio::null())
+        .output()
+        .expect("failed to run ty-types");
+    assert!(output.status.success());
+
+    let responses: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0]["result"]["ok"], true);
+    assert_eq!(responses[1]["id"], 2);
+    let nodes: Vec<NodeInfo> =
+        serde_json::from_value(responses[1]["result"]["nodes"].clone()).unwrap();
+    assert!(nodes.iter().any(|n| n.node_kind == "ExprNumberLiteral"));
+    assert_eq!(responses[2]["id"], 99);
+}
+
+#[test]
+fn test_header_framing() {
+    let dir = create_test_project(&[("a.py", "x: int = 42\n")]);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .args(["--serve", "--framing=headers"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn ty-types");
+    let mut stdin = child.stdin.take().unwrap();
+
+    let initialize = initialize_request(dir.path().to_str().unwrap(), 1);
+    write!(
+        stdin,
+        "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{initialize}",
+        initialize.len()
+    )
+    .unwrap();
+
+    // A body with embedded newlines, its header flushed ahead of it.
+    let get_types = serde_json::to_string_pretty(&serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "getTypes",
+        "params": {"file": "a.py"},
+        "id": 2
+    }))
+    .unwrap();
+    write!(stdin, "Content-Length: {}\r\n", get_types.len()).unwrap();
+    stdin.flush().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(50));
+    write!(stdin, "\r\n{get_types}").unwrap();
+
+    let shutdown = shutdown_request(99);
+    write!(
+        stdin,
+        "Content-Length: {}\r\n\r\n{shutdown}",
+        shutdown.len()
+    )
+    .unwrap();
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    let responses = parse_framed_responses(&output.stdout);
+
+    assert_eq!(responses.len(), 3);
+    assert_eq!(responses[0]["result"]["ok"], true);
+    assert_eq!(responses[1]["id"], 2);
+    let nodes: Vec<NodeInfo> =
+        serde_json::from_value(responses[1]["result"]["nodes"].clone()).unwrap();
+    assert!(nodes.iter().any(|n| n.node_kind == "ExprNumberLiteral"));
+    assert_eq!(responses[2]["id"], 99);
+}
+
+/// Split `--framing=headers` output into its message bodies.
+fn parse_framed_responses(mut rest: &[u8]) -> Vec<serde_json::Value> {
+    let mut responses = Vec::new();
+    while !rest.is_empty() {
+        let header_end = rest
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .expect("response should have a header");
+        let header = std::str::from_utf8(&rest[..header_end]).unwrap();
+        let length: usize = header
+            .strip_prefix("Content-Length: ")
+            .and_then(|n| n.parse().ok())
+            .unwrap_or_else(|| panic!("unexpected header {header:?}"));
+        let body = &rest[header_end + 4..header_end + 4 + length];
+        responses.push(serde_json::from_slice(body).unwrap());
+        rest = &rest[header_end + 4 + length..];
+    }
+    responses
+}
+
+#[test]
+fn test_header_framing_recovers_from_malformed_headers() {
+    let dir = create_test_project(&[("a.py", "x: int = 42\n")]);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .args(["--serve", "--framing=headers"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn ty-types");
+    let mut stdin = child.stdin.take().unwrap();
+    let frame = |body: &str| format!("Content-Length: {}\r\n\r\n{body}", body.len());
+
+    let initialize = initialize_request(dir.path().to_str().unwrap(), 1);
+    write!(stdin, "{}", frame(&initialize)).unwrap();
+    // Not a header at all, followed by a valid frame.
+    write!(stdin, "not a header\r\n\r\n").unwrap();
+    write!(stdin, "{}", frame(&get_types_request("a.py", 2))).unwrap();
+    // An unreadable length, whose body runs into the next frame's header.
+    write!(stdin, "Content-Length: lots\r\n\r\n{{}}").unwrap();
+    write!(stdin, "{}", frame(&shutdown_request(99))).unwrap();
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    let responses = parse_framed_responses(&output.stdout);
+
+    assert_eq!(responses.len(), 5, "got {responses:?}");
+    assert_eq!(responses[0]["result"]["ok"], true);
+    assert_eq!(responses[1]["error"]["code"], -32700);
+    assert!(responses[1]["id"].is_null());
+    assert_eq!(responses[2]["id"], 2);
+    assert!(responses[2]["result"]["nodes"].is_array());
+    assert_eq!(responses[3]["error"]["code"], -32700);
+    let message = responses[3]["error"]["message"].as_str().unwrap();
+    assert!(message.contains("Content-Length"), "got {message}");
+    assert_eq!(responses[4]["id"], 99);
+}
+
+#[test]
+fn test_class_metaclass() {
+    let dir = create_test_project(&[(
+        "meta.py",
+        "from abc import ABCMeta\n\
+         class Meta(type): ...\n\
+         class WithMeta(metaclass=Meta): ...\n\
+         class Abstract(metaclass=ABCMeta): ...\n\
+         class Plain: ...\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("meta.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let class = |name: &str| {
+        types
+            .values()
+            .find(|t| t["kind"] == "classLiteral" && t["className"] == name)
+            .unwrap_or_else(|| panic!("should have classLiteral '{name}'"))
+    };
+
+    let with_meta = class("WithMeta");
+    let meta = &types[&with_meta["metaclass"].to_string()];
+    assert_eq!(meta["kind"], "classLiteral");
+    assert_eq!(meta["className"], "Meta");
+
+    let abstract_meta = &types[&class("Abstract")["metaclass"].to_string()];
+    assert_eq!(abstract_meta["className"], "ABCMeta");
+
+    assert!(
+        class("Plain").get("metaclass").is_none(),
+        "default `type` metaclass is omitted"
+    );
+}
+
+#[test]
+fn test_position_encoding() {
+    // "é" is 2 UTF-8 bytes but 1 UTF-16 code unit, so `x` starts at byte 12
+    // but at UTF-16 offset 11.
+    let dir = create_test_project(&[("enc.py", "s = \"café\"\nx = 1\n")]);
+    let root = dir.path().to_str().unwrap();
+
+    let x_range = |encoding: &str| {
+        let responses = run_session(&[
+            &serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "initialize",
+                "params": {"projectRoot": root, "positionEncoding": encoding},
+                "id": 1
+            })
+            .to_string(),
+            &get_types_request("enc.py", 2),
+            &shutdown_request(99),
+        ]);
+        assert_eq!(responses[0]["result"]["ok"], true);
+        let nodes = responses[1]["result"]["nodes"].as_array().unwrap();
+        let x = nodes
+            .iter()
+            .filter(|n| n["nodeKind"] == "ExprName")
+            .last()
+            .expect("should have a node for `x`");
+        (x["start"].as_u64().unwrap(), x["end"].as_u64().unwrap())
+    };
+
+    assert_eq!(x_range("utf8"), (12, 13));
+    assert_eq!(x_range("utf16"), (11, 12));
+    assert_eq!(x_range("utf32"), (11, 12));
+}
+
+#[test]
+fn test_pep695_type_alias_statement() {
+    let dir = create_test_project(&[("alias.py", "type Vector = list[float]\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("alias.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let nodes = result["nodes"].as_array().unwrap();
+
+    let stmt = nodes
+        .iter()
+        .find(|n| n["nodeKind"] == "StmtTypeAlias")
+        .expect("should have a StmtTypeAlias node");
+    let alias = &types[&stmt["typeId"].to_string()];
+    assert_eq!(alias["kind"], "typeAlias");
+    assert_eq!(alias["name"], "Vector");
+
+    let name = nodes
+        .iter()
+        .find(|n| n["nodeKind"] == "ExprName" && n["start"] == 5)
+        .expect("should have a node for the alias name");
+    assert_eq!(name["typeId"], stmt["typeId"]);
+
+    assert!(
+        nodes.iter().any(|n| n["nodeKind"] == "ExprSubscript"),
+        "the alias value should be visited"
+    );
+}
+
+#[test]
+fn test_implicit_generic_base() {
+    let dir = create_test_project(&[(
+        "bases.py",
+        "class Base: ...\n\
+         class Box[T](Base): ...\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("bases.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let class = |name: &str| {
+        types
+            .values()
+            .find(|t| t["kind"] == "classLiteral" && t["className"] == name)
+            .unwrap_or_else(|| panic!("should have classLiteral '{name}'"))
+    };
+
+    let the_box = class("Box");
+    let declared = the_box["supertypes"].as_array().unwrap();
+    assert_eq!(declared.len(), 1);
+    assert_eq!(types[&declared[0].to_string()]["className"], "Base");
+
+    let implicit = the_box["implicitBases"].as_array().unwrap();
+    assert_eq!(implicit.len(), 1);
+    let generic = types[&implicit[0].to_string()]["display"].as_str().unwrap();
+    assert!(generic.contains("Generic"), "got {generic}");
+
+    assert!(class("Base").get("implicitBases").is_none());
+}
+
+#[test]
+fn test_kind_index() {
+    let dir =
+        create_test_project(&[("idx.py", "def f(x: int | str) -> None: ...\nclass C: ...\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "idx.py", "includeIndex": true},
+            "id": 2
+        })
+        .to_string(),
+        &get_types_request("idx.py", 3),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let (union_id, _) = types
+        .iter()
+        .find(|(_, t)| t["kind"] == "union")
+        .expect("should have a union type");
+
+    let unions: Vec<u64> = result["index"]["union"]
+        .as_array()
+        .expect("index should have a 'union' entry")
+        .iter()
+        .map(|id| id.as_u64().unwrap())
+        .collect();
+    assert!(unions.contains(&union_id.parse().unwrap()));
+    assert!(
+        unions.windows(2).all(|w| w[0] < w[1]),
+        "ids should be sorted"
+    );
+
+    for (kind, ids) in result["index"].as_object().unwrap() {
+        for id in ids.as_array().unwrap() {
+            assert_eq!(types[&id.to_string()]["kind"], *kind);
+        }
+    }
+
+    assert!(
+        responses[2]["result"].get("index").is_none(),
+        "index should be omitted unless requested"
+    );
+}
+
+#[test]
+fn test_extra_paths() {
+    let dir = create_test_project(&[
+        ("project/app.py", "import vendored_lib\n"),
+        ("vendor/vendored_lib.py", "VALUE = 1\n"),
+    ]);
+    let root = dir.path().join("project");
+    let root = root.to_str().unwrap();
+
+    let imports = |params: serde_json::Value| {
+        let responses = run_session(&[
+            &serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "initialize",
+                "params": params,
+                "id": 1
+            })
+            .to_string(),
+            &serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "getImports",
+                "params": {"file": "app.py"},
+                "id": 2
+            })
+            .to_string(),
+            &shutdown_request(99),
+        ]);
+        responses[1]["result"]["imports"][0].clone()
+    };
+
+    let without = imports(serde_json::json!({"projectRoot": root}));
+    assert_eq!(without["unresolved"], true);
+
+    let with = imports(serde_json::json!({"projectRoot": root, "extraPaths": ["../vendor"]}));
+    assert!(with.get("unresolved").is_none(), "got {with}");
+    assert!(
+        with["resolvedPath"]
+            .as_str()
+            .unwrap()
+            .ends_with("vendored_lib.py")
+    );
+}
+
+#[test]
+fn test_version_before_initialize() {
+    let responses = run_session(&[
+        &serde_json::json!({"jsonrpc": "2.0", "method": "version", "id": 1}).to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[0]["result"];
+    let version = result["version"].as_str().unwrap();
+    assert!(!version.is_empty());
+    for field in ["tyVersion", "ruffVersion", "ruffCommit", "typeshedRevision"] {
+        assert!(result[field].is_string(), "missing {field}");
+    }
+}
+
+#[test]
+fn test_annotation_subscript_is_generic_alias() {
+    let dir = create_test_project(&[("ann.py", "x: list[int] = []\ny = list[int]()\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("ann.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let nodes = result["nodes"].as_array().unwrap();
+    let subscripts: Vec<_> = nodes
+        .iter()
+        .filter(|n| n["nodeKind"] == "ExprSubscript")
+        .collect();
+    assert_eq!(subscripts.len(), 2);
+
+    let annotation = &types[&subscripts[0]["typeId"].to_string()];
+    assert_eq!(annotation["kind"], "genericAlias");
+    let origin = &types[&annotation["origin"].to_string()];
+    assert_eq!(origin["kind"], "classLiteral");
+    assert_eq!(origin["className"], "list");
+    let type_args = annotation["typeArgs"].as_array().unwrap();
+    assert_eq!(type_args.len(), 1);
+    assert_eq!(types[&type_args[0].to_string()]["display"], "int");
+
+    let x = nodes
+        .iter()
+        .find(|n| n["nodeKind"] == "ExprName" && n["start"] == 0)
+        .expect("should have a node for `x`");
+    assert_eq!(types[&x["typeId"].to_string()]["kind"], "instance");
+}
+
+#[test]
+fn test_generic_alias_descriptor() {
+    let source = "def total(xs: list[int]) -> int:\n    return sum(xs)\n";
+    let dir = create_test_project(&[("ga.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("ga.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+
+    let start = source.find("list[int]").unwrap() as u32;
+    let node = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprSubscript" && n.start == start)
+        .expect("should have a node for `list[int]`");
+    let alias = &types[&node.type_id.unwrap().to_string()];
+    assert_eq!(alias["kind"], "genericAlias");
+    assert_eq!(alias["display"], "<class 'list[int]'>");
+
+    let int_id = types
+        .iter()
+        .find(|(_, t)| t["kind"] == "instance" && t["className"] == "int")
+        .map(|(id, _)| id.parse::<u64>().unwrap())
+        .expect("should have an `int` instance");
+    assert_eq!(alias["typeArgs"], serde_json::json!([int_id]));
+
+    let origin = &types[&alias["origin"].to_string()];
+    assert_eq!(origin["kind"], "classLiteral");
+    assert_eq!(origin["className"], "list");
+    assert_eq!(origin["moduleName"], "builtins");
+}
+
+#[test]
+fn test_annotation_special_form_subscript_is_wrapped_type() {
+    let source = "from typing import Annotated, ClassVar, Final\n\
+                  a: Annotated[int, \"meta\"] = 1\n\
+                  b: Final[int] = 1\n\
+                  class C:\n\
+                  \x20   c: ClassVar[int] = 1\n";
+    let dir = create_test_project(&[("sf.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("sf.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let nodes = result["nodes"].as_array().unwrap();
+
+    for annotation in ["Annotated[int, \"meta\"]", "Final[int]", "ClassVar[int]"] {
+        let start = source.find(annotation).unwrap();
+        let node = nodes
+            .iter()
+            .find(|n| n["nodeKind"] == "ExprSubscript" && n["start"] == start)
+            .unwrap_or_else(|| panic!("should have a node for `{annotation}`"));
+        let ty = &types[&node["typeId"].to_string()];
+        assert_eq!(ty["kind"], "instance", "{annotation}: {ty}");
+        assert_eq!(ty["className"], "int", "{annotation}: {ty}");
+    }
+}
+
+#[test]
+fn test_unresolvable_python_environment() {
+    let dir = create_test_project(&[
+        ("ty.toml", "[environment]\npython = \"does-not-exist\"\n"),
+        ("app.py", "x = 1\n"),
+    ]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &shutdown_request(99),
+    ]);
+
+    let error = &responses[0]["error"];
+    assert_eq!(error["code"], -32001, "got {}", responses[0]);
+    let message = error["message"].as_str().unwrap();
+    assert!(message.contains("Python environment"), "got {message}");
+}
+
+#[test]
+fn test_malformed_config_is_not_an_environment_error() {
+    let dir = create_test_project(&[("ty.toml", "[environment\n"), ("app.py", "x = 1\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &shutdown_request(99),
+    ]);
+
+    assert_eq!(
+        responses[0]["error"]["code"], -32000,
+        "got {}",
+        responses[0]
+    );
+}
+
+#[test]
+fn test_project_without_python_config() {
+    // No `ty.toml`, `pyproject.toml` or interpreter: the bundled typeshed
+    // still resolves `builtins`, so nothing falls back to `Unknown`.
+    let dir = create_test_project(&[("app.py", "n = len('ab')\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("app.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    assert_eq!(responses[0]["result"]["ok"], true, "got {}", responses[0]);
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let n = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprName" && n.start == 0)
+        .expect("should have a node for `n`");
+    assert_eq!(types[&n.type_id.unwrap().to_string()]["display"], "int");
+}
+
+#[test]
+fn test_subclass_of_typevar_call_signature() {
+    let dir = create_test_project(&[(
+        "make.py",
+        "def make[T](cls: type[T]) -> T:\n    return cls()\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("make.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+
+    let signature = nodes
+        .iter()
+        .filter(|n| n.node_kind == "ExprCall")
+        .find_map(|n| n.call_signature.as_ref())
+        .expect("cls() should have a call signature");
+    let return_type = &types[&signature.return_type_id.unwrap().to_string()];
+    assert_eq!(return_type["kind"], "typeVar");
+    assert_eq!(return_type["name"], "T");
+}
+
+#[test]
+fn test_subscribed_file_pushes_types_when_changed() {
+    let dir = create_test_project(&[("watched.py", "x = 1\n")]);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .arg("--serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn ty-types");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut messages = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(&l.unwrap()).unwrap());
+    let file_changed = |id: u64| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "fileChanged",
+            "params": {"file": "watched.py"},
+            "id": id
+        })
+    };
+
+    writeln!(
+        stdin,
+        "{}",
+        initialize_request(dir.path().to_str().unwrap(), 1)
+    )
+    .unwrap();
+    writeln!(
+        stdin,
+        "{}",
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "subscribe",
+            "params": {"files": ["watched.py"]},
+            "id": 2
+        })
+    )
+    .unwrap();
+    let subscribed: Vec<serde_json::Value> = messages.by_ref().take(2).collect();
+    assert_eq!(subscribed[1]["result"]["ok"], true, "got {}", subscribed[1]);
+
+    std::fs::write(dir.path().join("watched.py"), "x = 'changed'\n").unwrap();
+    writeln!(stdin, "{}", file_changed(3)).unwrap();
+    let changed = messages.next().unwrap();
+    assert_eq!(changed["id"], 3);
+
+    // The push follows the `fileChanged` response, without an ID.
+    let push = messages.next().unwrap();
+    assert_eq!(push["method"], "typesChanged", "got {push}");
+    assert!(push.get("id").is_none());
+    assert_eq!(push["params"]["file"], "watched.py");
+    let types: TypeMap = serde_json::from_value(push["params"]["types"].clone()).unwrap();
+    assert!(
+        types
+            .values()
+            .any(|t| t["display"] == "Literal[\"changed\"]"),
+        "got {types:?}"
+    );
+
+    // After unsubscribing, changing the file pushes nothing.
+    writeln!(
+        stdin,
+        "{}",
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "unsubscribe",
+            "params": {"files": ["watched.py"]},
+            "id": 4
+        })
+    )
+    .unwrap();
+    writeln!(stdin, "{}", file_changed(5)).unwrap();
+    writeln!(stdin, "{}", shutdown_request(99)).unwrap();
+    drop(stdin);
+
+    let rest: Vec<serde_json::Value> = messages.collect();
+    child.wait().unwrap();
+    let ids: Vec<Option<u64>> = rest.iter().map(|m| m["id"].as_u64()).collect();
+    assert_eq!(ids, [Some(4), Some(5), Some(99)], "got {rest:?}");
+}
+
+#[test]
+fn test_subscriptions_survive_reload() {
+    let dir = create_test_project(&[("watched.py", "x = 1\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "subscribe",
+            "params": {"files": ["watched.py"]},
+            "id": 2
+        })
+        .to_string(),
+        &serde_json::json!({"jsonrpc": "2.0", "method": "reload", "id": 3}).to_string(),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "fileChanged",
+            "params": {"file": "watched.py"},
+            "id": 4
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    assert_eq!(responses[2]["id"], 3);
+    assert_eq!(responses[2]["result"]["ok"], true, "got {}", responses[2]);
+    assert_eq!(responses[3]["id"], 4);
+    let push = &responses[4];
+    assert_eq!(push["method"], "typesChanged", "got {push}");
+    assert_eq!(push["params"]["file"], "watched.py");
+    assert_eq!(responses[5]["id"], 99);
+}
+
+#[test]
+fn test_typevar_owner() {
+    let dir = create_test_project(&[(
+        "owners.py",
+        "from typing import TypeVar\n\
+         T = TypeVar(\"T\")\n\
+         def first(x: T) -> T: return x\n\
+         def second(y: T) -> T: return y\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("owners.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let owner_of = |function: &str| {
+        let (_, func) = types
+            .iter()
+            .find(|(_, t)| t["kind"] == "function" && t["name"] == function)
+            .unwrap_or_else(|| panic!("should have function '{function}'"));
+        let type_var = &types[&func["typeParameters"][0].to_string()];
+        assert_eq!(type_var["name"], "T");
+        type_var["owner"].clone()
+    };
+
+    let first_owner = owner_of("first");
+    let second_owner = owner_of("second");
+    assert_ne!(
+        first_owner, second_owner,
+        "each T should have its own owner"
+    );
+    assert_eq!(types[&first_owner.to_string()]["name"], "first");
+    assert_eq!(types[&second_owner.to_string()]["name"], "second");
+}
+
+#[test]
+fn test_validate_mode() {
+    let dir = create_test_project(&[
+        ("clean.py", "import os\nx = os.sep\n"),
+        ("broken.py", "import not_a_real_module\ndef f(:\n"),
+    ]);
+    let validate = |file: &str| {
+        Command::new(env!("CARGO_BIN_EXE_ty-types"))
+            .args(["--validate", dir.path().join(file).to_str().unwrap()])
+            .stderr(Stdio::null())
+            .output()
+            .expect("failed to run ty-types")
+    };
+
+    let clean = validate("clean.py");
+    assert!(clean.status.success());
+    let summary = String::from_utf8(clean.stdout).unwrap();
+    assert!(
+        summary.contains("Validated 1 file(s): 0 parse error(s), 0 unresolved import(s)"),
+        "got {summary}"
+    );
+
+    let broken = validate("broken.py");
+    assert!(!broken.status.success());
+    let summary = String::from_utf8(broken.stdout).unwrap();
+    assert!(summary.contains("1 unresolved import(s)"), "got {summary}");
+    assert!(!summary.contains(" 0 parse error(s)"), "got {summary}");
+}
+
+#[test]
+fn test_union_display_style() {
+    let source = "from typing import Literal\n\n\
+                  x: int | None = None\n\
+                  y: int | str = 1\n\
+                  z: list[int | None] = []\n\
+                  w: Literal[1, 2] | None = None\n";
+    let dir = create_test_project(&[("uds.py", source)]);
+    let root = dir.path().to_str().unwrap();
+
+    let initialize = |style: &str| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "initialize",
+            "params": {"projectRoot": root, "unionDisplayStyle": style},
+            "id": 1
+        })
+        .to_string()
+    };
+    let displays = |style: &str| {
+        let responses = run_session(&[
+            &initialize(style),
+            &get_types_request("uds.py", 2),
+            &shutdown_request(99),
+        ]);
+        let types: TypeMap =
+            serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+        types
+            .values()
+            .filter_map(|t| t["display"].as_str().map(str::to_string))
+            .collect::<Vec<_>>()
+    };
+
+    let pep604 = displays("pep604");
+    for display in [
+        "int | None",
+        "int | str",
+        "list[int | None]",
+        "Literal[1, 2] | None",
+    ] {
+        assert!(
+            pep604.iter().any(|d| d == display),
+            "{display} in {pep604:?}"
+        );
+    }
+
+    // Nested unions and literal unions are rewritten as well.
+    let typing = displays("typing");
+    for display in [
+        "Optional[int]",
+        "Union[int, str]",
+        "list[Optional[int]]",
+        "Optional[Literal[1, 2]]",
+    ] {
+        assert!(
+            typing.iter().any(|d| d == display),
+            "{display} in {typing:?}"
+        );
+    }
+    assert!(!typing.iter().any(|d| d.contains(" | ")), "got {typing:?}");
+}
+
+#[test]
+fn test_class_hierarchy() {
+    let dir = create_test_project(&[
+        ("base.py", "class Base: ...\n"),
+        (
+            "subs.py",
+            "from base import Base\n\
+             class Child(Base): ...\n\
+             class Grandchild(Child): ...\n\
+             class Unrelated: ...\n",
+        ),
+    ]);
+
+    let hierarchy_request = |type_id: &serde_json::Value, full: bool, id: u64| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getClassHierarchy",
+            "params": {"typeId": type_id, "full": full},
+            "id": id
+        })
+        .to_string()
+    };
+
+    // Discover Base's type ID first.
+    let root = dir.path().to_str().unwrap();
+    let responses = run_session(&[
+        &initialize_request(root, 1),
+        &get_types_request("base.py", 2),
+        &shutdown_request(99),
+    ]);
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let (base_id, _) = types
+        .iter()
+        .find(|(_, t)| t["kind"] == "classLiteral" && t["className"] == "Base")
+        .expect("should have classLiteral 'Base'");
+    let base_id: serde_json::Value = base_id.parse::<u64>().unwrap().into();
+
+    let responses = run_session(&[
+        &initialize_request(root, 1),
+        &get_types_request("base.py", 2),
+        &hierarchy_request(&base_id, false, 3),
+        &get_types_request("subs.py", 4),
+        &hierarchy_request(&base_id, false, 5),
+        &shutdown_request(99),
+    ]);
+
+    // Only base.py has been collected: no descendants yet.
+    let before = &responses[2]["result"];
+    assert!(before["descendants"].as_array().unwrap().is_empty());
+    assert!(
+        !before["ancestors"].as_array().unwrap().is_empty(),
+        "object"
+    );
+
+    let mut registry: TypeMap =
+        serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    for response in &responses[2..5] {
+        let types: TypeMap = serde_json::from_value(response["result"]["types"].clone()).unwrap();
+        registry.extend(types);
+    }
+
+    let mut names: Vec<&str> = responses[4]["result"]["descendants"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| registry[&id.to_string()]["className"].as_str().unwrap())
+        .collect();
+    names.sort_unstable();
+    assert_eq!(names, ["Child", "Grandchild"]);
+
+    // A project-wide search finds them without collecting subs.py first.
+    let responses = run_session(&[
+        &initialize_request(root, 1),
+        &get_types_request("base.py", 2),
+        &hierarchy_request(&base_id, true, 3),
+        &shutdown_request(99),
+    ]);
+    assert_eq!(
+        responses[2]["result"]["descendants"]
+            .as_array()
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+#[test]
+fn test_batch_requests() {
+    let dir = create_test_project(&[
+        ("a.py", "x: int = 1\n"),
+        ("b.py", "y: int = 2\nz: str = \"s\"\n"),
+    ]);
+    let batch = dir.path().join("batch.json");
+    std::fs::write(
+        &batch,
+        serde_json::json!([
+            {"file": dir.path().join("a.py")},
+            {"file": dir.path().join("b.py")}
+        ])
+        .to_string(),
+    )
+    .unwrap();
+
+    let output = run_oneshot(&["--requests", batch.to_str().unwrap()]);
+    let results = output.as_array().expect("batch output should be an array");
+    assert_eq!(results.len(), 2);
+    assert!(results[0]["file"].as_str().unwrap().ends_with("a.py"));
+    assert!(results[1]["file"].as_str().unwrap().ends_with("b.py"));
+
+    let a_types: TypeMap = serde_json::from_value(results[0]["types"].clone()).unwrap();
+    let b_types: TypeMap = serde_json::from_value(results[1]["types"].clone()).unwrap();
+    assert!(a_types.values().any(|t| t["display"] == "int"));
+    assert!(
+        !b_types.values().any(|t| t["display"] == "int"),
+        "int was already reported for a.py"
+    );
+    assert!(b_types.values().any(|t| t["display"] == "str"));
+    assert!(a_types.keys().all(|id| !b_types.contains_key(id)));
+    assert!(!results[1]["nodes"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_lambda_parameter_default() {
+    let dir = create_test_project(&[("lam.py", "f = lambda x=1: x\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("lam.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let nodes = result["nodes"].as_array().unwrap();
+
+    // `lambda x=1: x` — the default `1` starts at byte 13.
+    let default = nodes
+        .iter()
+        .find(|n| n["nodeKind"] == "ExprNumberLiteral" && n["start"] == 13)
+        .expect("the default expression should be attributed");
+    assert_eq!(
+        types[&default["typeId"].to_string()]["display"],
+        "Literal[1]"
+    );
+
+    let lambda = nodes
+        .iter()
+        .find(|n| n["nodeKind"] == "ExprLambda")
+        .expect("should have an ExprLambda node");
+    let callable = &types[&lambda["typeId"].to_string()];
+    let param = &callable["parameters"][0];
+    assert_eq!(param["name"], "x");
+    assert_eq!(param["hasDefault"], true);
+    assert_eq!(param["defaultTypeId"], default["typeId"]);
+}
+
+#[test]
+fn test_types_only() {
+    let dir = create_test_project(&[("to.py", "def f(x: int) -> str:\n    return str(x)\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "to.py", "typesOnly": true},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    assert!(result["nodes"].as_array().unwrap().is_empty());
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    assert!(types.values().any(|t| t["kind"] == "function"));
+    assert!(types.values().any(|t| t["display"] == "int"));
+}
+
+#[test]
+fn test_self_type() {
+    let dir = create_test_project(&[(
+        "selfish.py",
+        "from typing import Self\n\
+         class Node:\n    def copy(self) -> Self:\n        return self\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("selfish.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let copy = types
+        .values()
+        .find(|t| t["kind"] == "function" && t["name"] == "copy")
+        .expect("should have function 'copy'");
+    let self_type = &types[&copy["returnType"].to_string()];
+    assert_eq!(self_type["kind"], "selfType");
+
+    let bound_class = &types[&self_type["boundClass"].to_string()];
+    assert_eq!(bound_class["kind"], "classLiteral");
+    assert_eq!(bound_class["className"], "Node");
+}
+
+#[test]
+fn test_healthcheck() {
+    let dir = create_test_project(&[("main.py", "x = 1\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({"jsonrpc": "2.0", "method": "healthcheck", "id": 2}).to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    assert_eq!(result["ok"], true);
+    assert_eq!(
+        result["resolved"],
+        serde_json::json!(["builtins.int", "builtins.str", "typing.Any"])
+    );
+    assert_eq!(result["unresolved"], serde_json::json!([]));
+}
+
+#[test]
+fn test_bound_method_receiver() {
+    let dir = create_test_project(&[(
+        "dog.py",
+        "class Dog:\n    def bark(self) -> str:\n        return 'woof'\n\ndog = Dog()\nbark = dog.bark\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("dog.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let bark = types
+        .values()
+        .find(|t| t["kind"] == "boundMethod" && t["name"] == "bark")
+        .expect("should have a boundMethod for 'bark'");
+    let receiver = &types[&bark["receiver"].to_string()];
+    assert_eq!(receiver["kind"], "instance");
+    assert_eq!(receiver["className"], "Dog");
+}
+
+#[test]
+fn test_type_checking_import() {
+    let source = "from typing import TYPE_CHECKING\n\nif TYPE_CHECKING:\n    from decimal import Decimal\n\ndef f(x: Decimal) -> None: ...\n";
+    let dir = create_test_project(&[("tc.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("tc.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    // The import inside the `if TYPE_CHECKING:` body is attributed.
+    let alias = nodes
+        .iter()
+        .find(|n| n.node_kind == "Alias")
+        .expect("should have an Alias node for Decimal");
+    let alias_type = &types[&alias.type_id.expect("Decimal should resolve").to_string()];
+    assert_eq!(alias_type["kind"], "classLiteral");
+    assert_eq!(alias_type["className"], "Decimal");
+
+    // So is its use in the annotation.
+    let annotation_start = source.find("x: Decimal").unwrap() as u32 + 3;
+    let annotation = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprName" && n.start == annotation_start)
+        .expect("should have a node for the `Decimal` annotation");
+    let annotation_type = &types[&annotation
+        .type_id
+        .expect("annotation should resolve")
+        .to_string()];
+    assert_eq!(annotation_type["kind"], "classLiteral");
+    assert_eq!(annotation_type["className"], "Decimal");
+}
+
+#[test]
+fn test_ellipsis_literal() {
+    let dir = create_test_project(&[("stub.py", "def f(): ...\n\nx = None\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("stub.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let ellipsis = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprEllipsisLiteral")
+        .expect("should have a node for `...`");
+    let ellipsis_type = &types[&ellipsis
+        .type_id
+        .expect("`...` should have a type")
+        .to_string()];
+    assert_eq!(ellipsis_type["kind"], "ellipsis");
+
+    let none = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprNoneLiteral")
+        .expect("should have a node for `None`");
+    let none_type = &types[&none.type_id.expect("`None` should have a type").to_string()];
+    assert_ne!(none_type["kind"], "ellipsis");
+}
+
+#[test]
+fn test_threads_flag() {
+    let dir = create_test_project(&[(
+        "main.py",
+        "def f(x: int) -> str:\n    return str(x)\n\ny = f(1)\n",
+    )]);
+    let file = dir.path().join("main.py");
+    let file = file.to_str().unwrap();
+
+    let default = run_oneshot(&[file]);
+    let sequential = run_oneshot(&["--threads", "1", file]);
+    assert_eq!(default, sequential);
+}
+
+#[test]
+fn test_super_method_resolves_next_in_mro() {
+    let source = "class A:\n    def method(self) -> int:\n        return 1\n\nclass B(A):\n    def method(self) -> str:\n        super().method()\n        return ''\n";
+    let dir = create_test_project(&[("sup.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("sup.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let attribute_start = source.find("super().method").unwrap() as u32;
+    let attribute = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprAttribute" && n.start == attribute_start)
+        .expect("should have a node for `super().method`");
+    let method = &types[&attribute
+        .type_id
+        .expect("`super().method` should have a type")
+        .to_string()];
+    assert_eq!(method["kind"], "boundMethod");
+    assert_eq!(method["name"], "method");
+
+    // `A.method` returns `int`; `B.method` would return `str`.
+    let return_type = &types[&method["returnType"].to_string()];
+    assert_eq!(return_type["display"], "int");
+
+    // Its parameters are those of `A`'s `def`.
+    let a_def = source.find("def method").unwrap() as u64;
+    let b_def = source.rfind("def method").unwrap() as u64;
+    let self_start = method["parameters"][0]["start"].as_u64();
+    assert!(
+        self_start.is_none_or(|start| a_def < start && start < b_def),
+        "expected A's parameters, got {method:?}"
+    );
+}
+
+#[test]
+fn test_error_codes() {
+    let dir = create_test_project(&[("main.py", "x = 1\n")]);
+
+    let responses = run_session(&[
+        &get_types_request("main.py", 1),
+        &initialize_request(dir.path().to_str().unwrap(), 2),
+        &get_types_request("missing.py", 3),
+        &shutdown_request(99),
+    ]);
+
+    assert_eq!(
+        responses[0]["error"]["code"], -32004,
+        "got {}",
+        responses[0]
+    );
+    assert_eq!(
+        responses[2]["error"]["code"], -32002,
+        "got {}",
+        responses[2]
+    );
+}
+
+#[test]
+fn test_parameter_annotated_flag() {
+    let dir = create_test_project(&[("ann.py", "def f(x: int, y):\n    pass\n\nf(1, 2)\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("ann.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let f = types
+        .values()
+        .find(|t| t["kind"] == "function" && t["name"] == "f")
+        .expect("should have a function type for f");
+    assert_eq!(f["parameters"][0]["annotated"], true);
+    assert_eq!(f["parameters"][1]["annotated"], false);
+
+    let call = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprCall")
+        .expect("should have a call node");
+    let sig = call
+        .call_signature
+        .as_ref()
+        .expect("should have a call signature");
+    assert!(sig.parameters[0].annotated);
+    assert!(!sig.parameters[1].annotated);
+}
+
+#[test]
+fn test_get_raw_type() {
+    let dir = create_test_project(&[("main.py", "x = 1\n")]);
+
+    let raw_type_request = |id| {
+        serde_json::json!({"jsonrpc": "2.0", "method": "getRawType", "params": {"typeId": 1}, "id": id})
+            .to_string()
+    };
+    let requests = [
+        initialize_request(dir.path().to_str().unwrap(), 1),
+        get_types_request("main.py", 2),
+        raw_type_request(3),
+        shutdown_request(99),
+    ];
+    let requests: Vec<&str> = requests.iter().map(String::as_str).collect();
+
+    let responses = run_session_with_args(&["--debug"], &requests);
+    let debug = responses[2]["result"]["debug"].as_str().unwrap();
+    assert!(!debug.is_empty());
+
+    // Without `--debug` the method doesn't exist.
+    let responses = run_session(&requests);
+    assert_eq!(
+        responses[2]["error"]["code"], -32601,
+        "got {}",
+        responses[2]
+    );
+}
+
+#[test]
+fn test_comprehension_variable_does_not_leak() {
+    let source = "x = 'outer'\nys = [x for x in range(3)]\nz = x\n";
+    let dir = create_test_project(&[("comp.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("comp.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let type_at = |start: usize| {
+        let node = nodes
+            .iter()
+            .find(|n| n.node_kind == "ExprName" && n.start == start as u32)
+            .unwrap_or_else(|| panic!("should have an ExprName at {start}"));
+        &types[&node.type_id.expect("name should have a type").to_string()]
+    };
+
+    // The comprehension's own `x` is an `int`...
+    let element = type_at(source.find("[x").unwrap() + 1);
+    assert_eq!(element["display"], "int");
+
+    // ...but the `x` after it is still the outer string.
+    let after = type_at(source.rfind('x').unwrap());
+    assert_eq!(after["kind"], "stringLiteral");
+}
+
+#[test]
+fn test_string_literal_value_and_display_escaping() {
+    let dir = create_test_project(&[("s.py", "s = 'say \"hi\"\\nbye'\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("s.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let literal = types
+        .values()
+        .find(|t| t["kind"] == "stringLiteral")
+        .expect("should have a stringLiteral type");
+
+    // `value` holds the actual characters...
+    assert_eq!(literal["value"], "say \"hi\"\nbye");
+
+    // ...while `display` is the escaped `Literal[...]` form.
+    let display = literal["display"].as_str().unwrap();
+    assert_eq!(display, r#"Literal["say \"hi\"\nbye"]"#);
+    assert!(!display.contains('\n'));
+}
+
+#[test]
+fn test_decorator_call_signature() {
+    let source = "from typing import Callable\n\ndef decorator_factory(n: int) -> Callable[[Callable[[], None]], Callable[[], None]]:\n    return lambda f: f\n\n@decorator_factory(42)\ndef handler() -> None:\n    pass\n";
+    let dir = create_test_project(&[("deco.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("deco.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+
+    let decorator_start = source.find("decorator_factory(42)").unwrap() as u32;
+    let call = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprCall" && n.start == decorator_start)
+        .expect("should have a call node for the decorator");
+    assert!(call.type_id.is_some(), "decorator call should have a type");
+
+    let sig = call
+        .call_signature
+        .as_ref()
+        .expect("decorator call should have a call signature");
+    assert_eq!(sig.parameters.len(), 1);
+    assert_eq!(sig.parameters[0].name, "n");
+}
+
+#[test]
+fn test_max_response_bytes_truncates_nodes() {
+    let source: String = (0..2000).map(|i| format!("x{i} = 1\n")).collect();
+    let dir = create_test_project(&[("big.py", &source)]);
+    let max_bytes = 16 * 1024;
+
+    let responses = run_session(&[
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "initialize",
+            "params": {"projectRoot": dir.path().to_str().unwrap(), "maxResponseBytes": max_bytes},
+            "id": 1
+        })
+        .to_string(),
+        &get_types_request("big.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    assert_eq!(result["truncated"], true, "got {result}");
+
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let total = result["totalNodes"].as_u64().unwrap() as usize;
+    assert!(!nodes.is_empty());
+    assert!(nodes.len() < total, "{} of {total} nodes kept", nodes.len());
+    assert!(serde_json::to_vec(result).unwrap().len() <= max_bytes);
+
+    // Every kept node's type is still in the response.
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    for node in &nodes {
+        if let Some(id) = node.type_id {
+            assert!(types.contains_key(&id.to_string()), "missing type {id}");
+        }
+    }
+}
+
+#[test]
+fn test_synthesized_protocol_members() {
+    let dir = create_test_project(&[(
+        "proto.py",
+        "def f(x: object) -> None:\n    if hasattr(x, 'quack'):\n        y = x\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("proto.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let protocol = types
+        .values()
+        .find(|t| t["kind"] == "protocol")
+        .expect("hasattr narrowing should produce a protocol type");
+    let members: Vec<&str> = protocol["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m["name"].as_str().unwrap())
+        .collect();
+    assert!(members.contains(&"quack"), "got {protocol:?}");
+}
+
+#[test]
+fn test_get_type_by_display() {
+    let dir = create_test_project(&[("main.py", "def f(x: int) -> int:\n    return x\n")]);
+
+    let by_display = |display: &str, id: u64| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypeByDisplay",
+            "params": {"display": display},
+            "id": id
+        })
+        .to_string()
+    };
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("main.py", 2),
+        &by_display("int", 3),
+        &by_display("NoSuchType", 4),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[2]["result"];
+    let ids = result["typeIds"].as_array().unwrap();
+    assert!(!ids.is_empty(), "`int` should be registered, got {result}");
+    for id in ids {
+        let descriptor = &result["types"][id.to_string()];
+        assert_eq!(descriptor["display"], "int");
+    }
+    let int = ids
+        .iter()
+        .map(|id| &result["types"][id.to_string()])
+        .find(|t| t["kind"] == "instance")
+        .expect("should match the `int` instance type");
+    assert_eq!(int["className"], "int");
+
+    assert_eq!(responses[3]["result"]["typeIds"], serde_json::json!([]));
+}
+
+#[test]
+fn test_keyword_only_separator() {
+    let dir = create_test_project(&[("kw.py", "def f(a, *, b): ...\n\nf(1, b=2)\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("kw.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let f = types
+        .values()
+        .find(|t| t["kind"] == "function" && t["name"] == "f")
+        .expect("should have a function type for f");
+    let params = f["parameters"].as_array().unwrap();
+    assert_eq!(params.len(), 2, "the bare `*` is not a parameter");
+    assert_eq!(params[0]["name"], "a");
+    assert_eq!(params[0]["kind"], "positionalOrKeyword");
+    assert_eq!(params[1]["name"], "b");
+    assert_eq!(params[1]["kind"], "keywordOnly");
+
+    let sig = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprCall")
+        .and_then(|n| n.call_signature.as_ref())
+        .expect("should have a call signature");
+    let kinds: Vec<&str> = sig.parameters.iter().map(|p| p.kind.as_str()).collect();
+    assert_eq!(kinds, ["positionalOrKeyword", "keywordOnly"]);
+}
+
+#[test]
+fn test_exclude_stdlib() {
+    let dir = create_test_project(&[(
+        "main.py",
+        "class User:\n    pass\n\nu = User()\nn: int = int('1')\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "main.py", "excludeStdlib": true},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    assert!(
+        types
+            .values()
+            .any(|t| t["kind"] == "instance" && t["className"] == "User"),
+        "user class should be present"
+    );
+    assert!(
+        !types.values().any(|t| t["className"] == "int"),
+        "`int` should be excluded"
+    );
+}
+
+#[test]
+fn test_excluded_stdlib_types_stay_fetchable() {
+    let source = "n: int = int('1')\n";
+    let dir = create_test_project(&[("main.py", source), ("other.py", "m = int('2')\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "main.py", "excludeStdlib": true},
+            "id": 2
+        })
+        .to_string(),
+        &serde_json::json!({"jsonrpc": "2.0", "method": "getTypeRegistry", "id": 3}).to_string(),
+        &get_types_request("other.py", 4),
+        &shutdown_request(99),
+    ]);
+
+    let nodes: Vec<NodeInfo> =
+        serde_json::from_value(responses[1]["result"]["nodes"].clone()).unwrap();
+    let int_id = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprName" && n.start == 0)
+        .and_then(|n| n.type_id)
+        .expect("`n` should have a type");
+    let excluded: TypeMap =
+        serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    assert!(!excluded.contains_key(&int_id.to_string()));
+
+    // Fetched explicitly by ID...
+    let fetched = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "main.py", "excludeStdlib": true},
+            "id": 2
+        })
+        .to_string(),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypeRegistry",
+            "params": {"typeIds": [int_id]},
+            "id": 3
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+    let by_id: TypeMap = serde_json::from_value(fetched[2]["result"]["types"].clone()).unwrap();
+    assert_eq!(by_id[&int_id.to_string()]["className"], "int");
+    let class_id = by_id[&int_id.to_string()]["classId"].to_string();
+    assert!(by_id.contains_key(&class_id), "references are included");
+    let all: TypeMap = serde_json::from_value(responses[2]["result"]["types"].clone()).unwrap();
+    assert!(by_id.len() < all.len());
+
+    // ...or sent by the next response that references it without `excludeStdlib`.
+    let later: TypeMap = serde_json::from_value(responses[3]["result"]["types"].clone()).unwrap();
+    assert_eq!(later[&int_id.to_string()]["className"], "int");
+}
+
+#[test]
+fn test_typed_dict_required_and_optional_keys() {
+    let dir = create_test_project(&[(
+        "td.py",
+        "from typing import NotRequired, TypedDict\n\
+         class Form(TypedDict):\n\
+         \x20   name: str\n\
+         \x20   email: NotRequired[str]\n\
+         \x20   age: int\n\
+         \x20   notes: NotRequired[str]\n\
+         f: Form = {\"name\": \"a\", \"age\": 1}\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("td.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let form = types
+        .values()
+        .find(|t| t["kind"] == "typedDict" && t["name"] == "Form")
+        .expect("should have a 'Form' typedDict type");
+
+    let keys = |field: &str| -> Vec<String> {
+        let mut keys: Vec<String> = serde_json::from_value(form[field].clone()).unwrap();
+        keys.sort();
+        keys
+    };
+    assert_eq!(keys("requiredKeys"), ["age", "name"]);
+    assert_eq!(keys("optionalKeys"), ["email", "notes"]);
+}
+
+#[test]
+fn test_get_coverage() {
+    let dir = create_test_project(&[(
+        "cov.py",
+        "def typed(x: int) -> int:\n    return x\n\ndef untyped(y):\n    return y\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getCoverage",
+            "params": {"file": "cov.py"},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+
+    assert_eq!(result["parameters"]["covered"], 1, "got {result}");
+    assert_eq!(result["parameters"]["total"], 2, "got {result}");
+    assert_eq!(result["returns"]["covered"], 1, "got {result}");
+    assert_eq!(result["returns"]["total"], 2, "got {result}");
+
+    // `y` is `Unknown`, so not every expression is covered.
+    let expressions = &result["expressions"];
+    let percent = expressions["percent"].as_f64().unwrap();
+    assert!(percent > 0.0 && percent < 100.0, "got {expressions}");
+    assert!(expressions["covered"].as_u64() < expressions["total"].as_u64());
+}
+
+#[test]
+fn test_get_diagnostics() {
+    let source = "import missing\n\nx: int = 'a'\n";
+    let dir = create_test_project(&[("diag.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getDiagnostics",
+            "params": {"file": "diag.py"},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let diagnostics = responses[1]["result"]["diagnostics"].as_array().unwrap();
+    let codes: Vec<&str> = diagnostics
+        .iter()
+        .map(|d| d["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["unresolved-import", "invalid-assignment"]);
+
+    for diagnostic in diagnostics {
+        assert_eq!(diagnostic["severity"], "error", "got {diagnostic}");
+        assert!(
+            !diagnostic["message"].as_str().unwrap().is_empty(),
+            "got {diagnostic}"
+        );
+    }
+
+    let range = &diagnostics[0]["range"];
+    let start = range["start"].as_u64().unwrap() as usize;
+    let end = range["end"].as_u64().unwrap() as usize;
+    assert_eq!(&source[start..end], "missing");
+}
+
+#[test]
+fn test_get_diagnostics_honors_type_ignore() {
+    let source = "a: int = 'a'  # type: ignore\n\
+                  b: int = 'b'  # type: ignore[assignment]\n\
+                  c: int = 'c'  # ty: ignore[invalid-assignment]\n\
+                  d: int = 'd'\n";
+    let dir = create_test_project(&[("ignored.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getDiagnostics",
+            "params": {"file": "ignored.py"},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    // Only the assignment without a suppression comment is reported.
+    let diagnostics = responses[1]["result"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1, "got {diagnostics:?}");
+    assert_eq!(diagnostics[0]["code"], "invalid-assignment");
+    let start = diagnostics[0]["range"]["start"].as_u64().unwrap() as usize;
+    assert!(
+        start >= source.find("d: int").unwrap(),
+        "got {}",
+        diagnostics[0]
+    );
+}
+
+#[test]
+fn test_list_files_honors_exclude() {
+    let dir = create_test_project(&[
+        ("a.py", "x = 1\n"),
+        ("pkg/b.py", "y = 2\n"),
+        ("skip.py", "z = 3\n"),
+        ("ty.toml", "[src]\nexclude = [\"skip.py\"]\n"),
+    ]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({"jsonrpc": "2.0", "method": "listFiles", "id": 2}).to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let files: Vec<String> =
+        serde_json::from_value(responses[1]["result"]["files"].clone()).unwrap();
+    let names: Vec<String> = files
+        .iter()
+        .map(|f| {
+            let path = std::path::Path::new(f);
+            assert!(path.is_absolute(), "got {f}");
+            path.strip_prefix(dir.path())
+                .unwrap_or(path)
+                .to_string_lossy()
+                .replace('\\', "/")
+        })
+        .collect();
+    assert_eq!(names, ["a.py", "pkg/b.py"]);
+}
+
+#[test]
+fn test_yield_expression_types() {
+    let dir = create_test_project(&[(
+        "gen.py",
+        "from typing import Generator\n\n\
+         def inner() -> Generator[int, None, bytes]:\n\
+         \x20   yield 1\n\
+         \x20   return b''\n\n\
+         def gen() -> Generator[int, str, None]:\n\
+         \x20   x = yield 1\n\
+         \x20   y = yield from inner()\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("gen.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let type_of = |kind: &str| {
+        let node = nodes
+            .iter()
+            .rfind(|n| n.node_kind == kind)
+            .unwrap_or_else(|| panic!("should have an {kind} node"));
+        &types[&node.type_id.expect("yield should have a type").to_string()]
+    };
+
+    // `yield` evaluates to the generator's send type...
+    assert_eq!(type_of("ExprYield")["display"], "str");
+    // ...and `yield from` to the delegated generator's return type.
+    assert_eq!(type_of("ExprYieldFrom")["display"], "bytes");
+}
+
+#[test]
+fn test_columnar_node_encoding() {
+    let dir = create_test_project(&[(
+        "main.py",
+        "def f(x: int) -> str:\n    return str(x)\n\ny = f(1)\nz = [y, y]\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("main.py", 2),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "main.py", "nodeEncoding": "columnar"},
+            "id": 3
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let objects = responses[1]["result"]["nodes"].as_array().unwrap();
+    let columnar = &responses[2]["result"]["nodes"];
+
+    let column = |name: &str| -> Vec<u64> {
+        columnar[name]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_u64().unwrap())
+            .collect()
+    };
+    let (starts, ends, kinds, type_ids) = (
+        column("starts"),
+        column("ends"),
+        column("kinds"),
+        column("typeIds"),
+    );
+    let kind_table = columnar["kindTable"].as_array().unwrap();
+    let uninferred = columnar["uninferred"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    assert_eq!(starts.len(), objects.len());
+
+    // Rebuild each object-form node from the columns.
+    let rebuilt: Vec<serde_json::Value> = (0..starts.len())
+        .map(|i| {
+            let mut node = serde_json::json!({
+                "start": starts[i],
+                "end": ends[i],
+                "nodeKind": kind_table[kinds[i] as usize],
+            });
+            if type_ids[i] != 0 {
+                node["typeId"] = type_ids[i].into();
+            }
+            if let Some(signature) = columnar["callSignatures"].get(i.to_string()) {
+                node["callSignature"] = signature.clone();
+            }
+            if let Some(symbol_kind) = columnar["symbolKinds"].get(i.to_string()) {
+                node["symbolKind"] = symbol_kind.clone();
+            }
+            if uninferred.contains(&serde_json::json!(i)) {
+                node["inferred"] = false.into();
+            }
+            node
+        })
+        .collect();
+    assert_eq!(&rebuilt, objects);
+}
+
+#[test]
+fn test_future_annotations() {
+    let source = "from __future__ import annotations\n\nclass Node:\n    pass\n\ndef f(x: Node) -> Node:\n    return x\n";
+    let dir = create_test_project(&[("fut.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("fut.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    // Both the parameter and the return annotation resolve to the class, not
+    // to a string.
+    let def = source.find("def f").unwrap();
+    let annotations: Vec<&NodeInfo> = nodes
+        .iter()
+        .filter(|n| {
+            n.node_kind == "ExprName"
+                && n.start as usize > def
+                && &source[n.start as usize..n.end as usize] == "Node"
+        })
+        .collect();
+    assert_eq!(annotations.len(), 2, "got {annotations:?}");
+    for annotation in annotations {
+        let ty = &types[&annotation
+            .type_id
+            .expect("annotation should have a type")
+            .to_string()];
+        assert_eq!(ty["kind"], "classLiteral", "got {ty:?}");
+        assert_eq!(ty["className"], "Node");
+    }
+
+    // And `x` is a `Node` instance.
+    let x = &types[&nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprName" && &source[n.start as usize..n.end as usize] == "x")
+        .and_then(|n| n.type_id)
+        .expect("`x` should have a type")
+        .to_string()];
+    assert_eq!(x["kind"], "instance");
+    assert_eq!(x["className"], "Node");
+}
+
+#[test]
+fn test_notification_gets_no_response() {
+    let dir = create_test_project(&[("main.py", "x = 1\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        r#"{"jsonrpc":"2.0","method":"getTypes","params":{"file":"main.py"}}"#,
+        r#"{"jsonrpc":"2.0","method":"noSuchMethod"}"#,
+        &shutdown_request(99),
+    ]);
+
+    let ids: Vec<_> = responses.iter().map(|r| r["id"].clone()).collect();
+    assert_eq!(ids, vec![serde_json::json!(1), serde_json::json!(99)]);
+}
+
+#[test]
+fn test_null_id_gets_response() {
+    let dir = create_test_project(&[("main.py", "x = 1\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        r#"{"jsonrpc":"2.0","method":"version","id":null}"#,
+        r#"{"jsonrpc":"2.0","method":"noSuchMethod","id":null}"#,
+        &shutdown_request(99),
+    ]);
+
+    assert_eq!(responses.len(), 4);
+    assert!(responses[1]["id"].is_null());
+    assert!(responses[1]["result"].is_object());
+    assert!(responses[2]["id"].is_null());
+    assert_eq!(responses[2]["error"]["code"], -32601);
+}
+
+#[test]
+fn test_starred_assignment_target() {
+    let source = "a, *rest = [1, 2, 3]\n";
+    let dir = create_test_project(&[("star.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("star.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let rest_start = source.find("rest").unwrap() as u32;
+    for kind in ["ExprStarred", "ExprName"] {
+        let node = nodes
+            .iter()
+            .find(|n| n.node_kind == kind && n.end == rest_start + 4)
+            .unwrap_or_else(|| panic!("should have an {kind} node for `rest`"));
+        let ty = &types[&node
+            .type_id
+            .unwrap_or_else(|| panic!("{kind} should have a type"))
+            .to_string()];
+        assert_eq!(ty["display"], "list[int]", "{kind}");
+    }
+}
+
+#[test]
+fn test_max_file_size_skips_large_files() {
+    let large = "x = 1\n".repeat(1000);
+    let dir = create_test_project(&[("small.py", "y = 2\n"), ("large.py", &large)]);
+
+    let responses = run_session_with_args(
+        &["--max-file-size", "1024"],
+        &[
+            &initialize_request(dir.path().to_str().unwrap(), 1),
+            &get_types_request("large.py", 2),
+            &get_types_request("small.py", 3),
+            &shutdown_request(99),
+        ],
+    );
+
+    let skipped = &responses[1]["result"];
+    assert_eq!(skipped["skippedTooLarge"], true, "got {skipped}");
+    assert!(skipped["nodes"].as_array().unwrap().is_empty());
+    assert!(skipped["types"].as_object().unwrap().is_empty());
+
+    let small = &responses[2]["result"];
+    assert!(small.get("skippedTooLarge").is_none());
+    assert!(!small["nodes"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_method_call_on_specialized_generic() {
+    let source = "d: dict[str, int] = {}\nv = d.get(\"k\")\n";
+    let dir = create_test_project(&[("get.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("get.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let call_start = source.find("d.get").unwrap() as u32;
+    let call = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprCall" && n.start == call_start)
+        .expect("should have a call node for d.get");
+    let sig = call
+        .call_signature
+        .as_ref()
+        .expect("d.get should have a call signature");
+
+    // The receiver's `dict[str, int]` specializes both the return type and
+    // the parameters.
+    let return_type = &types[&sig.return_type_id.unwrap().to_string()];
+    assert_eq!(return_type["display"], "int | None");
+    let key = &types[&sig.parameters[0]
+        .type_id
+        .expect("key should have a type")
+        .to_string()];
+    assert_eq!(key["display"], "str");
+}
+
+#[test]
+fn test_reload_picks_up_configuration() {
+    let dir = create_test_project(&[
+        ("project/app.py", "import vendored_lib\n"),
+        ("vendor/vendored_lib.py", "VALUE = 1\n"),
+    ]);
+    let root = dir.path().join("project");
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .arg("--serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn ty-types");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut responses = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(&l.unwrap()).unwrap());
+    let get_imports = |id: u64| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getImports",
+            "params": {"file": "app.py"},
+            "id": id
+        })
+        .to_string()
+    };
+
+    writeln!(stdin, "{}", initialize_request(root.to_str().unwrap(), 1)).unwrap();
+    writeln!(stdin, "{}", get_imports(2)).unwrap();
+    let before = responses.nth(1).unwrap();
+    assert_eq!(before["result"]["imports"][0]["unresolved"], true);
+
+    std::fs::write(
+        root.join("ty.toml"),
+        "[environment]\nextra-paths = [\"../vendor\"]\n",
+    )
+    .unwrap();
+    writeln!(
+        stdin,
+        "{}",
+        serde_json::json!({"jsonrpc": "2.0", "method": "reload", "id": 3})
+    )
+    .unwrap();
+    writeln!(stdin, "{}", get_imports(4)).unwrap();
+    writeln!(stdin, "{}", shutdown_request(99)).unwrap();
+    drop(stdin);
+
+    let rest: Vec<serde_json::Value> = responses.collect();
+    child.wait().unwrap();
+    assert_eq!(rest[0]["id"], 3);
+    assert_eq!(rest[0]["result"]["ok"], true, "got {}", rest[0]);
+    let after = &rest[1]["result"]["imports"][0];
+    assert!(after.get("unresolved").is_none(), "got {after}");
+}
+
+#[test]
+fn test_walrus_target() {
+    let source = "def f(xs: list[int]) -> None:\n    if (n := len(xs)) > 0:\n        print(n)\n";
+    let dir = create_test_project(&[("walrus.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("walrus.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let display = |node: &NodeInfo| {
+        let id = node.type_id.expect("node should have a type");
+        types[&id.to_string()]["display"].clone()
+    };
+
+    let named_start = source.find("n := ").unwrap() as u32;
+    let named = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprNamed" && n.start == named_start)
+        .expect("should have an ExprNamed node");
+    assert_eq!(display(named), "int");
+
+    let target = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprName" && n.start == named_start)
+        .expect("should have a node for the walrus target");
+    assert_eq!(display(target), "int");
+}
+
+#[test]
+fn test_implicit_none_return() {
+    let source = "def sometimes(c: bool) -> int | None:\n    if c:\n        return 1\n\ndef always(c: bool) -> int:\n    if c:\n        return 1\n    return 2\n";
+    let dir = create_test_project(&[("ret.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("ret.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let function = |name: &str| {
+        types
+            .values()
+            .find(|t| t["kind"] == "function" && t["name"] == name)
+            .unwrap_or_else(|| panic!("should have a function type for {name}"))
+    };
+
+    let sometimes = function("sometimes");
+    assert_eq!(sometimes["implicitNoneReturn"], true);
+    let return_type = &types[&sometimes["returnType"].to_string()];
+    assert_eq!(return_type["display"], "int | None");
+
+    assert!(function("always").get("implicitNoneReturn").is_none());
+}
+
+#[test]
+fn test_implicit_none_return_not_set_for_placeholder_bodies() {
+    let dir = create_test_project(&[
+        (
+            "proto.py",
+            "from typing import Protocol\n\
+             class P(Protocol):\n\
+             \x20   def size(self) -> int: ...\n\
+             def todo() -> str: ...\n",
+        ),
+        ("lib.pyi", "def find(key: str) -> int | None: ...\n"),
+        ("use.py", "from lib import find\nr = find\n"),
+    ]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("proto.py", 2),
+        &get_types_request("use.py", 3),
+        &shutdown_request(99),
+    ]);
+
+    let mut types: TypeMap =
+        serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    types.extend(
+        serde_json::from_value::<TypeMap>(responses[2]["result"]["types"].clone()).unwrap(),
+    );
+    for name in ["size", "todo", "find"] {
+        let function = types
+            .values()
+            .find(|t| t["kind"] == "function" && t["name"] == name)
+            .unwrap_or_else(|| panic!("should have a function type for {name}"));
+        assert!(
+            function.get("implicitNoneReturn").is_none(),
+            "{name}: {function:?}"
+        );
+    }
+}
+
+#[test]
+fn test_keep_display_for_kinds() {
+    let dir = create_test_project(&[(
+        "disp.py",
+        "def f(v: int | str) -> int | str:\n    return v\n\nx = 1\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "disp.py", "includeDisplay": false, "keepDisplayFor": ["union"]},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let union = types
+        .values()
+        .find(|t| t["kind"] == "union")
+        .expect("should have a union type");
+    assert_eq!(union["display"], "int | str");
+
+    let literal = types
+        .values()
+        .find(|t| t["kind"] == "intLiteral")
+        .expect("should have an intLiteral type");
+    assert!(literal.get("display").is_none(), "got {literal}");
+}
+
+#[test]
+fn test_positional_only_separator() {
+    let dir = create_test_project(&[("po.py", "def f(a, b, /, c): ...\n\nf(1, 2, c=3)\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("po.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let f = types
+        .values()
+        .find(|t| t["kind"] == "function" && t["name"] == "f")
+        .expect("should have a function type for f");
+    let params: Vec<(&str, &str)> = f["parameters"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| (p["name"].as_str().unwrap(), p["kind"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        params,
+        [
+            ("a", "positionalOnly"),
+            ("b", "positionalOnly"),
+            ("c", "positionalOrKeyword"),
+        ],
+        "the bare `/` is not a parameter"
+    );
+
+    let sig = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprCall")
+        .and_then(|n| n.call_signature.as_ref())
+        .expect("should have a call signature");
+    let kinds: Vec<&str> = sig.parameters.iter().map(|p| p.kind.as_str()).collect();
+    assert_eq!(
+        kinds,
+        ["positionalOnly", "positionalOnly", "positionalOrKeyword"]
+    );
+}
+
+#[test]
+fn test_stdin_source() {
+    let dir = create_test_project(&[
+        ("helper.py", "VALUE: int = 1\n"),
+        ("pkg/__init__.py", ""),
+        ("pkg/sibling.py", "NAME: str = 'a'\n"),
+    ]);
+    let run = |as_path: &str, source: &str| {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+            .args(["--stdin", "--as", as_path, "--project-root"])
+            .arg(dir.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to spawn ty-types");
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(source.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert!(
+            output.status.success(),
+            "ty-types exited with {}",
+            output.status
+        );
+        serde_json::from_slice::<serde_json::Value>(&output.stdout).unwrap()
+    };
+    // The type of the last `ExprName` named `name`.
+    let type_of = |result: &serde_json::Value, source: &str, name: &str| {
+        let files = result["files"].as_object().unwrap();
+        assert_eq!(files.len(), 1);
+        let start = source.rfind(name).unwrap();
+        let node = files
+            .values()
+            .next()
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|n| n["nodeKind"] == "ExprName" && n["start"] == start)
+            .unwrap_or_else(|| panic!("should have a node for `{name}`"))
+            .clone();
+        result["types"][node["typeId"].to_string()]["display"].clone()
+    };
+
+    let source = "from helper import VALUE\n\ny = VALUE + 1\n";
+    let result = run("a.py", source);
+    let path = result["files"].as_object().unwrap().keys().next().unwrap();
+    assert!(path.ends_with("a.py"), "got {path}");
+    assert_eq!(type_of(&result, source, "y"), "int");
+
+    // Inside a package, relative imports resolve against its real neighbours.
+    let source = "from .sibling import NAME\n\nz = NAME\n";
+    let result = run("pkg/piped.py", source);
+    assert_eq!(type_of(&result, source, "z"), "str");
+}
+
+#[test]
+fn test_generic_protocol_type_args() {
+    let source = "from typing import Protocol\n\nclass Getter[T](Protocol):\n    def get(self) -> T: ...\n\ndef f(g: Getter[int]) -> None:\n    g\n";
+    let dir = create_test_project(&[("proto.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("proto.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let use_start = source.rfind('g').unwrap() as u32;
+    let g = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprName" && n.start == use_start)
+        .expect("should have a node for `g`");
+    let g_type = &types[&g.type_id.expect("`g` should have a type").to_string()];
+    assert_eq!(g_type["kind"], "instance");
+    assert_eq!(g_type["className"], "Getter");
+    let type_args = g_type["typeArgs"].as_array().expect("should have typeArgs");
+    assert_eq!(type_args.len(), 1);
+    assert_eq!(types[&type_args[0].to_string()]["display"], "int");
+}
+
+#[test]
+fn test_prune_unreferenced() {
+    let dir = create_test_project(&[("main.py", "s = 'a'\nt = s.upper()\n")]);
+    let root = dir.path().to_str().unwrap();
+
+    let types_with = |params: serde_json::Value| {
+        let responses = run_session(&[
+            &initialize_request(root, 1),
+            &serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "getTypes",
+                "params": params,
+                "id": 2
+            })
+            .to_string(),
+            &shutdown_request(99),
+        ]);
+        serde_json::from_value::<TypeMap>(responses[1]["result"]["types"].clone()).unwrap()
+    };
+
+    // Unions such as `str | None` only appear in the signatures of `str`'s
+    // methods, which `excludeStdlib` drops, leaving them unreferenced.
+    let excluded = types_with(serde_json::json!({"file": "main.py", "excludeStdlib": true}));
+    assert!(excluded.values().any(|t| t["kind"] == "union"));
+
+    let pruned = types_with(serde_json::json!({
+        "file": "main.py",
+        "excludeStdlib": true,
+        "pruneUnreferenced": true
+    }));
+    assert!(!pruned.values().any(|t| t["kind"] == "union"));
+    assert!(pruned.len() < excluded.len());
+    assert!(pruned.keys().all(|id| excluded.contains_key(id)));
+}
+
+#[test]
+fn test_pruned_type_sent_when_referenced_later() {
+    let dir = create_test_project(&[
+        ("main.py", "s = 'a'\nt = s.upper()\n"),
+        ("other.py", "def f(v: str | None) -> None: ...\n"),
+    ]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "main.py", "excludeStdlib": true, "pruneUnreferenced": true},
+            "id": 2
+        })
+        .to_string(),
+        &get_types_request("other.py", 3),
+        &shutdown_request(99),
+    ]);
+
+    let pruned: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    assert!(!pruned.values().any(|t| t["display"] == "str | None"));
+
+    // `str | None` was registered, and pruned, by the first request.
+    let later: TypeMap = serde_json::from_value(responses[2]["result"]["types"].clone()).unwrap();
+    assert!(
+        later.values().any(|t| t["display"] == "str | None"),
+        "the pruned union should be sent once a node references it"
+    );
+}
+
+#[test]
+fn test_specialized_generic_base() {
+    let dir = create_test_project(&[("base.py", "class IntList(list[int]):\n    pass\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("base.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let int_list = types
+        .values()
+        .find(|t| t["kind"] == "classLiteral" && t["className"] == "IntList")
+        .expect("should have classLiteral 'IntList'");
+    let supertypes = int_list["supertypes"].as_array().unwrap();
+    assert_eq!(supertypes.len(), 1);
+
+    let base = &types[&supertypes[0].to_string()];
+    assert_eq!(base["kind"], "genericAlias", "got {base}");
+    assert_eq!(types[&base["origin"].to_string()]["className"], "list");
+    let type_args = base["typeArgs"].as_array().unwrap();
+    assert_eq!(type_args.len(), 1);
+    assert_eq!(types[&type_args[0].to_string()]["display"], "int");
+}
+
+#[test]
+fn test_request_timeout() {
+    let source: String = (0..500)
+        .map(|i| format!("def f{i}(x: int) -> list[int]:\n    return [x, f{i}(x)[0]]\n"))
+        .collect();
+    let dir = create_test_project(&[("slow.py", &source)]);
+
+    let responses = run_session(&[
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "initialize",
+            "params": {"projectRoot": dir.path().to_str().unwrap(), "requestTimeoutMs": 1},
+            "id": 1
+        })
+        .to_string(),
+        &get_types_request("slow.py", 2),
+        &serde_json::json!({"jsonrpc": "2.0", "method": "healthcheck", "id": 3}).to_string(),
+        &shutdown_request(99),
+    ]);
+
+    assert_eq!(
+        responses[1]["error"]["code"], -32007,
+        "got {}",
+        responses[1]
+    );
+    // The session keeps serving requests.
+    assert_eq!(responses[2]["result"]["ok"], true);
+}
+
+#[test]
+fn test_request_timeout_applies_to_every_collector() {
+    let source: String = (0..500)
+        .map(|i| format!("def f{i}(x: int) -> list[int]:\n    return [x, f{i}(x)[0]]\n"))
+        .collect();
+    let dir = create_test_project(&[("slow.py", &source)]);
+    let request = |method: &str, params: serde_json::Value, id: u64| {
+        serde_json::json!({"jsonrpc": "2.0", "method": method, "params": params, "id": id})
+            .to_string()
+    };
+
+    // Past the cap on abandoned inferences, requests fail without waiting,
+    // so they time out either way.
+    let responses = run_session(&[
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "initialize",
+            "params": {"projectRoot": dir.path().to_str().unwrap(), "requestTimeoutMs": 1},
+            "id": 1
+        })
+        .to_string(),
+        &request(
+            "getTypesBatch",
+            serde_json::json!({"files": ["slow.py"]}),
+            2,
+        ),
+        &request(
+            "getTypesForScope",
+            serde_json::json!({"file": "slow.py", "offset": 0}),
+            3,
+        ),
+        &request(
+            "getParents",
+            serde_json::json!({"file": "slow.py", "offset": 0}),
+            4,
+        ),
+        &request(
+            "getTypeAtPosition",
+            serde_json::json!({"file": "slow.py", "offset": 0}),
+            5,
+        ),
+        &get_types_request("slow.py", 6),
+        &serde_json::json!({"jsonrpc": "2.0", "method": "healthcheck", "id": 7}).to_string(),
+        &shutdown_request(99),
+    ]);
+
+    assert_eq!(
+        responses[1]["result"]["results"]["slow.py"]["error"]["code"], -32007,
+        "got {}",
+        responses[1]
+    );
+    for response in &responses[2..6] {
+        assert_eq!(response["error"]["code"], -32007, "got {response}");
+    }
+    assert_eq!(responses[6]["result"]["ok"], true);
+}
+
+#[test]
+fn test_symbol_kind() {
+    let source =
+        "import os\n\nclass C:\n    pass\n\ndef f() -> None:\n    pass\n\nv = 1\nos, C, f, v\n";
+    let dir = create_test_project(&[("sym.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("sym.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let nodes: Vec<NodeInfo> =
+        serde_json::from_value(responses[1]["result"]["nodes"].clone()).unwrap();
+    let last_line = source.rfind("os, C").unwrap();
+    let kinds: Vec<(&str, Option<&str>)> = nodes
+        .iter()
+        .filter(|n| n.node_kind == "ExprName" && n.start as usize >= last_line)
+        .map(|n| {
+            (
+                &source[n.start as usize..n.end as usize],
+                n.symbol_kind.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            ("os", Some("module")),
+            ("C", Some("class")),
+            ("f", Some("function")),
+            ("v", Some("variable")),
+        ]
+    );
+
+    let alias = nodes
+        .iter()
+        .find(|n| n.node_kind == "Alias")
+        .expect("should have an Alias node for `os`");
+    assert_eq!(alias.symbol_kind.as_deref(), Some("module"));
+}
+
+#[test]
+fn test_get_dependencies() {
+    let dir = create_test_project(&[
+        ("a.py", "import b\nimport os\n"),
+        ("b.py", "from c import VALUE\n"),
+        ("c.py", "import missing\n\nVALUE = 1\n"),
+    ]);
+
+    let dependencies = |depth: serde_json::Value| {
+        let responses = run_session(&[
+            &initialize_request(dir.path().to_str().unwrap(), 1),
+            &serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "getDependencies",
+                "params": {"file": "a.py", "depth": depth},
+                "id": 2
+            })
+            .to_string(),
+            &shutdown_request(99),
+        ]);
+        responses[1]["result"].clone()
+    };
+    let file_names = |result: &serde_json::Value| -> Vec<String> {
+        result["files"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|path| {
+                std::path::Path::new(path.as_str().unwrap())
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect()
+    };
+
+    let direct = dependencies(serde_json::json!(1));
+    assert_eq!(file_names(&direct), ["a.py", "b.py"]);
+    assert_eq!(direct["external"], serde_json::json!(["os"]));
+
+    let all = dependencies(serde_json::Value::Null);
+    assert_eq!(file_names(&all), ["a.py", "b.py", "c.py"]);
+    assert_eq!(all["unresolved"], serde_json::json!(["missing"]));
+}
+
+#[test]
+fn test_keyword_only_call_binding() {
+    let source = "def f(a: str, *, b: int) -> int:\n    return b\n\nf('x', b=2)\n";
+    let dir = create_test_project(&[("kwcall.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("kwcall.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let display =
+        |id: Option<u32>| types[&id.expect("should have a type").to_string()]["display"].clone();
+
+    let sig = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprCall")
+        .and_then(|n| n.call_signature.as_ref())
+        .expect("should have a call signature");
+    assert_eq!(sig.matched_overload, None);
+    let params: Vec<(&str, &str)> = sig
+        .parameters
+        .iter()
+        .map(|p| (p.name.as_str(), p.kind.as_str()))
+        .collect();
+    assert_eq!(params, [("a", "positionalOrKeyword"), ("b", "keywordOnly")]);
+    assert_eq!(display(sig.parameters[1].type_id), "int");
+    assert_eq!(display(sig.return_type_id), "int");
+}
+
+#[test]
+fn test_include_line_index() {
+    let source = "x = 1\n\ny = 'two'\nz = x\n";
+    let dir = create_test_project(&[("lines.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypes",
+            "params": {"file": "lines.py", "includeLineIndex": true},
+            "id": 2
+        })
+        .to_string(),
+        &get_types_request("lines.py", 3),
+        &shutdown_request(99),
+    ]);
+
+    // Four lines plus the empty one after the trailing newline.
+    let line_starts = &responses[1]["result"]["lineStarts"];
+    assert_eq!(line_starts, &serde_json::json!([0, 6, 7, 17, 23]));
+
+    assert!(responses[2]["result"].get("lineStarts").is_none());
+}
+
+#[test]
+fn test_flag_combination() {
+    let source = "import enum\n\nclass Perm(enum.Flag):\n    READ = 1\n    WRITE = 2\n\nr = Perm.READ\nrw = Perm.READ | Perm.WRITE\n";
+    let dir = create_test_project(&[("flags.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("flags.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let type_of = |start: usize| {
+        let node = nodes
+            .iter()
+            .find(|n| n.node_kind == "ExprName" && n.start == start as u32)
+            .expect("should have a node for the assignment target");
+        types[&node.type_id.expect("target should have a type").to_string()].clone()
+    };
+
+    let single = type_of(source.find("r = ").unwrap());
+    assert_eq!(single["kind"], "enumLiteral");
+    assert_eq!(single["className"], "Perm");
+    assert_eq!(single["memberName"], "READ");
+
+    // ty types `Flag.__or__` as returning `Self`, so the combination widens to
+    // the flag class rather than to `int` or an unknown.
+    let combined = type_of(source.find("rw = ").unwrap());
+    assert_eq!(combined["kind"], "instance");
+    assert_eq!(combined["className"], "Perm");
+}
+
+#[test]
+fn test_enum_literal_display() {
+    let source = "from enum import Enum\nfrom typing import Literal\n\n\
+                  class Color(Enum):\n    RED = 1\n    GREEN = 'g'\n    BLUE = 3\n\n\
+                  def pick(flag: bool) -> None:\n\
+                  \x20   a = Color.RED\n\
+                  \x20   b: Literal[Color.RED] = a\n\
+                  \x20   c = Color.RED if flag else Color.GREEN\n";
+    let dir = create_test_project(&[("colors.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("colors.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let type_id_at = |target: &str| {
+        let start = source.find(target).unwrap() as u32;
+        nodes
+            .iter()
+            .find(|n| n.node_kind == "ExprName" && n.start == start)
+            .and_then(|n| n.type_id)
+            .unwrap_or_else(|| panic!("should have a typed node for {target:?}"))
+    };
+
+    let red_id = type_id_at("a = ");
+    let red = &types[&red_id.to_string()];
+    assert_eq!(red["kind"], "enumLiteral");
+    assert_eq!(red["className"], "Color");
+    assert_eq!(red["memberName"], "RED");
+    assert_eq!(red["display"], "Literal[Color.RED]");
+    assert_eq!(red["memberValueDisplay"], "Literal[1]");
+
+    // The annotated target and the union member share the literal's descriptor.
+    assert_eq!(type_id_at("b: "), red_id);
+    let union = &types[&type_id_at("c = ").to_string()];
+    let members: Vec<u64> = union["members"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|m| m.as_u64().unwrap())
+        .collect();
+    assert!(members.contains(&u64::from(red_id)), "got {union}");
+    let green = members
+        .iter()
+        .map(|id| &types[&id.to_string()])
+        .find(|t| t["memberName"] == "GREEN")
+        .expect("union should contain GREEN");
+    assert_eq!(green["display"], "Literal[Color.GREEN]");
+    assert_eq!(green["memberValueDisplay"], "Literal[\"g\"]");
+}
+
+#[test]
+fn test_pretty_errors_summary() {
+    let dir = create_test_project(&[("pretty.py", "x = 1\nname = 'ty'\n")]);
+    let file = dir.path().join("pretty.py");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .args(["--pretty-errors", "--no-json"])
+        .arg(&file)
+        .output()
+        .expect("failed to run ty-types");
+    assert!(
+        output.status.success(),
+        "ty-types exited with {}",
+        output.status
+    );
+    assert!(output.stdout.is_empty(), "--no-json should suppress stdout");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("  1:1  ExprName  Literal[1]"),
+        "got:\n{stderr}"
+    );
+    assert!(
+        stderr.contains("  2:8  ExprStringLiteral  Literal[\"ty\"]"),
+        "got:\n{stderr}"
+    );
+}
+
+#[test]
+fn test_typevar_module_name() {
+    let source =
+        "from typing import TypeVar\n\nT = TypeVar('T')\n\ndef ident(x: T) -> T:\n    return x\n";
+    let dir = create_test_project(&[("first.py", source), ("second.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("first.py", 2),
+        &get_types_request("second.py", 3),
+        &shutdown_request(99),
+    ]);
+
+    let module_of_t = |response: &serde_json::Value| {
+        let types: TypeMap = serde_json::from_value(response["result"]["types"].clone()).unwrap();
+        types
+            .values()
+            .find(|t| t["kind"] == "typeVar" && t["name"] == "T")
+            .expect("should have a typeVar descriptor for T")["moduleName"]
+            .clone()
+    };
+
+    assert_eq!(module_of_t(&responses[1]), "first");
+    assert_eq!(module_of_t(&responses[2]), "second");
+}
+
+#[test]
+fn test_concatenate_param_spec_reference() {
+    let dir = create_test_project(&[(
+        "deco.py",
+        "from typing import Callable, Concatenate, ParamSpec, TypeVar\n\
+         P = ParamSpec(\"P\")\n\
+         R = TypeVar(\"R\")\n\
+         def with_id(f: Callable[Concatenate[int, P], R]) -> Callable[P, R]: ...\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("deco.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let with_id = types
+        .values()
+        .find(|t| t["kind"] == "function" && t["name"] == "with_id")
+        .expect("should have function 'with_id'");
+    let f_type = &types[&with_id["parameters"][0]["typeId"].to_string()];
+    assert_eq!(f_type["kind"], "callable");
+    let params = f_type["parameters"].as_array().unwrap();
+
+    // The concrete leading parameter comes first...
+    assert_eq!(params[0]["concatenatePrefix"], true);
+    assert_eq!(types[&params[0]["typeId"].to_string()]["display"], "int");
+
+    // ...followed by the `*args` / `**kwargs` tail referencing the ParamSpec.
+    let tail: Vec<_> = params[1..].iter().collect();
+    assert_eq!(tail.len(), 2, "got {params:?}");
+    for param in tail {
+        let param_spec = &types[&param["paramSpecId"].to_string()];
+        assert_eq!(param_spec["kind"], "typeVar");
+        assert_eq!(param_spec["name"], "P");
+        assert_eq!(param_spec["typevarKind"], "ParamSpec");
+    }
+}
+
+#[test]
+fn test_clear_file_rereads_changed_file() {
+    let dir = create_test_project(&[("edited.py", "x = 1\n"), ("stable.py", "y = 'a'\n")]);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .arg("--serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn ty-types");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut responses = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(&l.unwrap()).unwrap());
+
+    writeln!(
+        stdin,
+        "{}",
+        initialize_request(dir.path().to_str().unwrap(), 1)
+    )
+    .unwrap();
+    writeln!(stdin, "{}", get_types_request("edited.py", 2)).unwrap();
+    writeln!(stdin, "{}", get_types_request("stable.py", 3)).unwrap();
+    let before: Vec<serde_json::Value> = responses.by_ref().take(3).collect();
+    let before_types: TypeMap =
+        serde_json::from_value(before[1]["result"]["types"].clone()).unwrap();
+    assert!(before_types.values().any(|t| t["display"] == "Literal[1]"));
+
+    std::fs::write(dir.path().join("edited.py"), "x = 'changed'\n").unwrap();
+    writeln!(
+        stdin,
+        "{}",
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "clearFile",
+            "params": {"file": "edited.py"},
+            "id": 4
+        })
+    )
+    .unwrap();
+    writeln!(stdin, "{}", get_types_request("edited.py", 5)).unwrap();
+    writeln!(stdin, "{}", get_types_request("stable.py", 6)).unwrap();
+    writeln!(stdin, "{}", shutdown_request(99)).unwrap();
+    drop(stdin);
+
+    let rest: Vec<serde_json::Value> = responses.collect();
+    child.wait().unwrap();
+    assert_eq!(rest[0]["id"], 4);
+    assert_eq!(rest[0]["result"]["ok"], true, "got {}", rest[0]);
+
+    // The cleared file is inferred from its new contents...
+    let edited: TypeMap = serde_json::from_value(rest[1]["result"]["types"].clone()).unwrap();
+    assert!(
+        edited
+            .values()
+            .any(|t| t["display"] == "Literal[\"changed\"]"),
+        "got {edited:?}"
+    );
+    assert!(!edited.values().any(|t| t["display"] == "Literal[1]"));
+
+    // ...while the untouched file keeps its type IDs, with nothing new to send.
+    let stable: TypeMap = serde_json::from_value(rest[2]["result"]["types"].clone()).unwrap();
+    assert!(stable.is_empty(), "got {stable:?}");
+    let type_ids = |result: &serde_json::Value| {
+        let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+        nodes.iter().map(|n| n.type_id).collect::<Vec<_>>()
+    };
+    assert_eq!(type_ids(&rest[2]["result"]), type_ids(&before[2]["result"]));
+}
+
+#[test]
+fn test_clear_file_drops_stale_descriptors() {
+    let dir = create_test_project(&[("edited.py", "x = 1\n"), ("stable.py", "y = 'a'\n")]);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .arg("--serve")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to spawn ty-types");
+    let mut stdin = child.stdin.take().unwrap();
+    let mut responses = BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(&l.unwrap()).unwrap());
+    let by_display = |display: &str, id: u64| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypeByDisplay",
+            "params": {"display": display},
+            "id": id
+        })
+    };
+
+    writeln!(
+        stdin,
+        "{}",
+        initialize_request(dir.path().to_str().unwrap(), 1)
+    )
+    .unwrap();
+    writeln!(stdin, "{}", get_types_request("edited.py", 2)).unwrap();
+    writeln!(stdin, "{}", get_types_request("stable.py", 3)).unwrap();
+    writeln!(stdin, "{}", by_display("Literal[1]", 4)).unwrap();
+    let before: Vec<serde_json::Value> = responses.by_ref().take(4).collect();
+    assert_eq!(before[3]["result"]["typeIds"].as_array().unwrap().len(), 1);
+
+    std::fs::write(dir.path().join("edited.py"), "x = 'changed'\n").unwrap();
+    writeln!(
+        stdin,
+        "{}",
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "clearFile",
+            "params": {"file": "edited.py"},
+            "id": 5
+        })
+    )
+    .unwrap();
+    writeln!(
+        stdin,
+        "{}",
+        serde_json::json!({"jsonrpc": "2.0", "method": "getTypeRegistry", "id": 6})
+    )
+    .unwrap();
+    writeln!(stdin, "{}", by_display("Literal[1]", 7)).unwrap();
+    writeln!(stdin, "{}", shutdown_request(99)).unwrap();
+    drop(stdin);
+
+    let rest: Vec<serde_json::Value> = responses.collect();
+    child.wait().unwrap();
+    assert_eq!(rest[0]["result"]["ok"], true, "got {}", rest[0]);
+
+    // The old `x` is gone from the registry, while the untouched file's type
+    // is still there under its old ID.
+    let registry: TypeMap = serde_json::from_value(rest[1]["result"]["types"].clone()).unwrap();
+    assert!(
+        !registry.values().any(|t| t["display"] == "Literal[1]"),
+        "got {registry:?}"
+    );
+    let stable_types: TypeMap =
+        serde_json::from_value(before[2]["result"]["types"].clone()).unwrap();
+    let (stable_id, _) = stable_types
+        .iter()
+        .find(|(_, t)| t["display"] == "Literal[\"a\"]")
+        .expect("stable.py should register Literal[\"a\"]");
+    assert_eq!(registry[stable_id]["display"], "Literal[\"a\"]");
+
+    assert_eq!(rest[2]["result"]["typeIds"], serde_json::json!([]));
+    assert_eq!(rest[2]["result"]["types"], serde_json::json!({}));
+}
+
+#[test]
+fn test_intersection_negative_display() {
+    let source = "class Animal: ...\nclass Dog(Animal): ...\n\ndef f(pet: Animal) -> None:\n    if not isinstance(pet, Dog):\n        pet\n";
+    let dir = create_test_project(&[("narrow.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("narrow.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let narrowed_start = source.rfind("pet").unwrap() as u32;
+    let narrowed = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprName" && n.start == narrowed_start)
+        .expect("should have a node for the narrowed name");
+    let intersection = &types[&narrowed.type_id.unwrap().to_string()];
+    assert_eq!(intersection["kind"], "intersection");
+    assert_eq!(intersection["display"], "Animal & ~Dog");
+
+    let negative = intersection["negative"].as_array().unwrap();
+    assert_eq!(negative.len(), 1);
+    assert_eq!(types[&negative[0].to_string()]["display"], "Dog");
+}
+
+#[test]
+fn test_get_types_for_scope() {
+    let source = "def first(a: int) -> int:\n    return a + 1\n\ndef second(b: str) -> str:\n    def inner() -> str:\n        return b\n    return inner()\n";
+    let dir = create_test_project(&[("scoped.py", source)]);
+    let second_start = source.find("def second").unwrap();
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypesForScope",
+            "params": {"file": "scoped.py", "offset": source.find("inner()\n").unwrap()},
+            "id": 2
+        })
+        .to_string(),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypesForScope",
+            "params": {"file": "scoped.py", "offset": source.len() + 10},
+            "id": 3
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    assert_eq!(result["start"], second_start);
+    assert_eq!(result["end"], source.len() - 1);
+
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    assert!(!nodes.is_empty());
+    // Only the body: not the parameter `b: str` or the return annotation.
+    let body_start = source.find("    def inner").unwrap();
+    assert!(
+        nodes.iter().all(|n| n.start as usize >= body_start),
+        "should only return nodes of the body of `second`, got {nodes:?}"
+    );
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    assert!(
+        types
+            .values()
+            .any(|t| t["kind"] == "function" && t["name"] == "inner"),
+        "nested definitions should be included"
+    );
+
+    assert_eq!(responses[2]["error"]["code"], -32602);
+}
+
+#[test]
+fn test_protocol_class_flags() {
+    let dir = create_test_project(&[(
+        "protos.py",
+        "from typing import Protocol, runtime_checkable\n\n\
+         class Sized(Protocol):\n    def size(self) -> int: ...\n\n\
+         @runtime_checkable\n\
+         class Closeable(Protocol):\n    def close(self) -> None: ...\n\n\
+         class Plain:\n    pass\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("protos.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let class = |name: &str| {
+        types
+            .values()
+            .find(|t| t["kind"] == "classLiteral" && t["className"] == name)
+            .unwrap_or_else(|| panic!("should have a classLiteral for {name}"))
+    };
+
+    let sized = class("Sized");
+    assert_eq!(sized["isProtocol"], true);
+    assert!(sized.get("runtimeCheckable").is_none());
+
+    let closeable = class("Closeable");
+    assert_eq!(closeable["isProtocol"], true);
+    assert_eq!(closeable["runtimeCheckable"], true);
+
+    let plain = class("Plain");
+    assert!(plain.get("isProtocol").is_none());
+    assert!(plain.get("runtimeCheckable").is_none());
+}
+
+#[test]
+fn test_callable_unspecified_params() {
+    let dir = create_test_project(&[(
+        "gradual.py",
+        "from typing import Callable\n\ndef call(cb: Callable[..., int]) -> int:\n    return cb(1, x=2)\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("gradual.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let types: TypeMap = serde_json::from_value(responses[1]["result"]["types"].clone()).unwrap();
+    let call = types
+        .values()
+        .find(|t| t["kind"] == "function" && t["name"] == "call")
+        .expect("should have function 'call'");
+    let cb = &types[&call["parameters"][0]["typeId"].to_string()];
+    assert_eq!(cb["kind"], "callable");
+    assert_eq!(cb["unspecifiedParams"], true);
+    assert!(cb.get("parameters").is_none(), "got {cb}");
+    assert_eq!(types[&cb["returnType"].to_string()]["display"], "int");
+}
+
+#[test]
+fn test_class_getitem_subscript() {
+    let source = "class Registry:\n    def __class_getitem__(cls, item: object) -> str:\n        return 'key'\n\nx = Registry[int]\n";
+    let dir = create_test_project(&[("getitem.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("getitem.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+
+    let subscript_start = source.find("Registry[int]").unwrap() as u32;
+    let subscript = nodes
+        .iter()
+        .find(|n| n.node_kind == "ExprSubscript" && n.start == subscript_start)
+        .expect("should have a node for the subscript");
+    let ty = &types[&subscript
+        .type_id
+        .expect("subscript should have a type")
+        .to_string()];
+    assert_eq!(ty["display"], "str");
+}
+
+#[test]
+fn test_benchmark_reports_throughput() {
+    let dir = create_test_project(&[("bench.py", "def f(x: int) -> str:\n    return str(x)\n")]);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_ty-types"))
+        .args(["--benchmark", "2"])
+        .arg(dir.path().join("bench.py"))
+        .output()
+        .expect("failed to run ty-types");
+    assert!(
+        output.status.success(),
+        "ty-types exited with {}",
+        output.status
+    );
+    assert!(output.stdout.is_empty(), "benchmark should not print JSON");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("Benchmarked 1 file(s) x 2 iteration(s)"),
+        "got:\n{stderr}"
+    );
+    let files_per_sec: f64 = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("files/sec: "))
+        .expect("should report files/sec")
+        .parse()
+        .unwrap();
+    assert!(files_per_sec > 0.0);
+    assert!(stderr.contains("p50") && stderr.contains("p95"));
+}
+
+#[test]
+fn test_inferred_flag() {
+    let source = "def f(untyped, typed: int):\n    untyped\n    typed\n";
+    let dir = create_test_project(&[("infer.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("infer.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let nodes: Vec<NodeInfo> =
+        serde_json::from_value(responses[1]["result"]["nodes"].clone()).unwrap();
+    let use_of = |name: &str| {
+        let start = source.find(&format!("    {name}\n")).unwrap() as u32 + 4;
+        nodes
+            .iter()
+            .find(|n| n.node_kind == "ExprName" && n.start == start)
+            .unwrap_or_else(|| panic!("should have a node for the use of {name}"))
+    };
+
+    assert!(!use_of("untyped").inferred);
+    assert!(use_of("typed").inferred);
+}
+
+#[test]
+fn test_reveal_and_assert_type_calls() {
+    let source = "from typing import assert_type, reveal_type\n\n\
+                  reveal_type(1 + 1)\n\
+                  assert_type(len('ab'), int)\n\
+                  assert_type('a', int)\n";
+    let dir = create_test_project(&[("reveal.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("reveal.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<serde_json::Value> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let call_at = |snippet: &str| {
+        let start = source.find(snippet).unwrap();
+        nodes
+            .iter()
+            .find(|n| n["nodeKind"] == "ExprCall" && n["start"] == start)
+            .unwrap_or_else(|| panic!("should have a call node for {snippet}"))
+    };
+
+    let reveal = call_at("reveal_type(");
+    assert_eq!(
+        types[&reveal["typeId"].to_string()]["display"],
+        "Literal[2]"
+    );
+    assert!(reveal.get("assertionHolds").is_none());
+    assert_eq!(
+        types[&reveal["revealedTypeId"].to_string()]["display"],
+        "Literal[2]"
+    );
+
+    assert_eq!(call_at("assert_type(len")["assertionHolds"], true);
+    assert_eq!(call_at("assert_type('a'")["assertionHolds"], false);
+    assert!(call_at("assert_type(len").get("revealedTypeId").is_none());
+}
+
+#[test]
+fn test_no_emit_empty_omits_files_without_nodes() {
+    let dir = create_test_project(&[("empty.py", ""), ("full.py", "x = 1\n")]);
+    let empty = dir.path().join("empty.py");
+    let full = dir.path().join("full.py");
+    let args = [empty.to_str().unwrap(), full.to_str().unwrap()];
+
+    let file_names = |result: &serde_json::Value| -> Vec<String> {
+        let mut names: Vec<String> = result["files"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|path| path.rsplit(['/', '\\']).next().unwrap().to_string())
+            .collect();
+        names.sort();
+        names
+    };
+
+    let by_default = run_oneshot(&args);
+    assert_eq!(file_names(&by_default), ["empty.py", "full.py"]);
+
+    let without_empty = run_oneshot(&[args[0], args[1], "--no-emit-empty"]);
+    assert_eq!(file_names(&without_empty), ["full.py"]);
+}
+
+#[test]
+fn test_fstring_format_spec_expressions() {
+    let source = "def pad(x: str, w: int) -> str:\n    return f\"{x:{w}}\"\n";
+    let dir = create_test_project(&[("fmt.py", source)]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("fmt.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let display_at = |offset: usize| {
+        let node = nodes
+            .iter()
+            .find(|n| n.node_kind == "ExprName" && n.start == offset as u32)
+            .unwrap_or_else(|| panic!("should have an ExprName node at {offset}"));
+        types[&node.type_id.expect("name should have a type").to_string()]["display"].clone()
+    };
+
+    let value = source.find("{x").unwrap() + 1;
+    assert_eq!(display_at(value), "str");
+    // `w` sits in the format spec nested inside the replacement field.
+    let width = source.find("{w}").unwrap() + 1;
+    assert_eq!(display_at(width), "int");
+}
+
+#[test]
+fn test_get_parents_chain() {
+    let source = "def f(n: int) -> None:\n    total = abs(n + 2)\n";
+    let dir = create_test_project(&[("parents.py", source)]);
+    let offset = source.find("2)").unwrap();
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getParents",
+            "params": {"file": "parents.py", "offset": offset},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let nodes: Vec<NodeInfo> =
+        serde_json::from_value(responses[1]["result"]["nodes"].clone()).unwrap();
+    let kinds: Vec<&str> = nodes.iter().map(|n| n.node_kind.as_str()).collect();
+
+    // From the enclosing definition down to the literal under the cursor.
+    assert_eq!(kinds.first(), Some(&"StmtFunctionDef"), "got {kinds:?}");
+    let from_statement = &kinds[kinds.iter().position(|&k| k == "StmtAssign").unwrap()..];
+    assert_eq!(
+        from_statement,
+        ["StmtAssign", "ExprCall", "ExprBinOp", "ExprNumberLiteral"]
+    );
+    for pair in nodes.windows(2) {
+        assert!(pair[0].start <= pair[1].start && pair[1].end <= pair[0].end);
+    }
+    assert!(nodes.last().unwrap().type_id.is_some());
+}
+
+#[test]
+fn test_get_parents_types_are_self_contained() {
+    let source = "def f(n: int) -> None:\n    total = abs(n + 2)\n";
+    let dir = create_test_project(&[("parents.py", source)]);
+    let offset = source.find("2)").unwrap();
+
+    // `getTypes` first, so every type has already been sent.
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("parents.py", 2),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getParents",
+            "params": {"file": "parents.py", "offset": offset},
+            "id": 3
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[2]["result"];
+    let nodes: Vec<NodeInfo> = serde_json::from_value(result["nodes"].clone()).unwrap();
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    for node in &nodes {
+        if let Some(type_id) = node.type_id {
+            assert!(
+                types.contains_key(&type_id.to_string()),
+                "type {type_id} of {} missing from {types:?}",
+                node.node_kind
+            );
+        }
+    }
+}
+
+#[test]
+fn test_get_type_at_position() {
+    let source = "def f(n: int) -> None:\n    total = abs(n + 2)\n\n\nx = 1\n";
+    let dir = create_test_project(&[("hover.py", source)]);
+    let request = |offset: usize, id: u64| {
+        serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypeAtPosition",
+            "params": {"file": "hover.py", "offset": offset},
+            "id": id
+        })
+        .to_string()
+    };
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &request(source.find("n + 2").unwrap(), 2),
+        &request(source.find("abs").unwrap() + 1, 3),
+        &request(source.find("\n\n").unwrap() + 1, 4),
+        &shutdown_request(99),
+    ]);
+
+    // The name, not the `n + 2` or the call around it.
+    let result = &responses[1]["result"];
+    let node: NodeInfo = serde_json::from_value(result["node"].clone()).unwrap();
+    assert_eq!(node.node_kind, "ExprName");
+    assert_eq!(&source[node.start as usize..node.end as usize], "n");
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    assert_eq!(types[&node.type_id.unwrap().to_string()]["display"], "int");
+
+    // `types` is self-contained even though earlier responses sent some of them.
+    let result = &responses[2]["result"];
+    let node: NodeInfo = serde_json::from_value(result["node"].clone()).unwrap();
+    assert_eq!(&source[node.start as usize..node.end as usize], "abs");
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    for descriptor in types.values() {
+        for key in ["returnType", "classId"] {
+            if let Some(id) = descriptor.get(key).and_then(serde_json::Value::as_u64) {
+                assert!(types.contains_key(&id.to_string()), "{key} {id} missing");
+            }
+        }
+    }
+    assert!(types.contains_key(&node.type_id.unwrap().to_string()));
+
+    // Whitespace between statements has no node.
+    assert!(responses[3]["result"].is_null(), "got {}", responses[3]);
+    assert!(responses[3].get("error").is_none());
+}
+
+#[test]
+fn test_unmatched_overload_call() {
+    let dir = create_test_project(&[(
+        "nomatch.py",
+        "from typing import overload\n\
+         @overload\n\
+         def f(x: int) -> int: ...\n\
+         @overload\n\
+         def f(x: str) -> str: ...\n\
+         def f(x): return x\n\
+         ok = f(1)\n\
+         bad = f(b\"bytes\")\n",
+    )]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &get_types_request("nomatch.py", 2),
+        &shutdown_request(99),
+    ]);
+
+    let nodes: Vec<NodeInfo> =
+        serde_json::from_value(responses[1]["result"]["nodes"].clone()).unwrap();
+    let calls: Vec<&CallSignatureInfo> = nodes
+        .iter()
+        .filter(|n| n.node_kind == "ExprCall")
+        .map(|n| {
+            n.call_signature
+                .as_ref()
+                .expect("every call should keep a signature")
+        })
+        .collect();
+    assert_eq!(calls.len(), 2);
+
+    assert!(calls[0].matched);
+    assert_eq!(calls[0].matched_overload, Some(0));
+
+    assert!(!calls[1].matched, "f(b\"bytes\") matches no overload");
+    assert_eq!(calls[1].matched_overload, None);
+    assert_eq!(
+        calls[1].parameters.len(),
+        1,
+        "falls back to the first overload"
+    );
+}
+
+#[test]
+fn test_get_types_batch() {
+    let dir = create_test_project(&[("first.py", "x = 1\n"), ("second.py", "y = 1\nz = 'z'\n")]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypesBatch",
+            "params": {"files": ["first.py", "missing.py", "second.py"]},
+            "id": 2
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let results = &result["results"];
+
+    assert_eq!(results["missing.py"]["error"]["code"], -32002);
+
+    let new_ids = |file: &str| -> Vec<String> {
+        results[file]["newTypeIds"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|id| id.to_string())
+            .collect()
+    };
+    let first = new_ids("first.py");
+    let second = new_ids("second.py");
+    let literal_one = types
+        .iter()
+        .find(|(_, t)| t["display"] == "Literal[1]")
+        .map(|(id, _)| id.clone())
+        .expect("should have Literal[1]");
+
+    // `Literal[1]` is introduced by the first file only, and sent once.
+    assert!(first.contains(&literal_one));
+    assert!(!second.contains(&literal_one));
+    assert!(second.iter().all(|id| !first.contains(id)));
+    assert_eq!(types.len(), first.len() + second.len());
+
+    let second_nodes: Vec<NodeInfo> =
+        serde_json::from_value(results["second.py"]["nodes"].clone()).unwrap();
+    assert!(
+        second_nodes
+            .iter()
+            .any(|n| n.type_id.map(|id| id.to_string()) == Some(literal_one.clone()))
+    );
+}
+
+#[test]
+fn test_get_types_batch_options() {
+    let dir = create_test_project(&[
+        ("users.py", "class User:\n    pass\n\nu = User()\n"),
+        ("numbers.py", "n: int = int('1')\nm = 2\n"),
+        ("texts.py", "s = 'text'\n"),
+    ]);
+
+    let responses = run_session(&[
+        &initialize_request(dir.path().to_str().unwrap(), 1),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypesBatch",
+            "params": {
+                "files": ["users.py", "numbers.py"],
+                "excludeStdlib": true,
+                "includeDisplay": false,
+                "keepDisplayFor": ["intLiteral"]
+            },
+            "id": 2
+        })
+        .to_string(),
+        &serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "getTypesBatch",
+            "params": {"files": ["texts.py"], "typesOnly": true},
+            "id": 3
+        })
+        .to_string(),
+        &shutdown_request(99),
+    ]);
+
+    let result = &responses[1]["result"];
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    let user = types
+        .values()
+        .find(|t| t["kind"] == "instance" && t["className"] == "User")
+        .expect("user class should be present");
+    assert!(user.get("display").is_none());
+    assert!(
+        types
+            .values()
+            .any(|t| t["kind"] == "intLiteral" && t["display"] == "Literal[2]"),
+        "intLiteral should keep its display, got {types:?}"
+    );
+    assert!(
+        !types.values().any(|t| t["className"] == "int"),
+        "`int` should be excluded"
+    );
+    // Excluded types aren't listed as introduced by any file either.
+    for file in ["users.py", "numbers.py"] {
+        for id in result["results"][file]["newTypeIds"].as_array().unwrap() {
+            assert!(types.contains_key(&id.to_string()), "{file} lists {id}");
+        }
+    }
+
+    let result = &responses[2]["result"];
+    assert_eq!(
+        result["results"]["texts.py"]["nodes"],
+        serde_json::json!([])
+    );
+    let types: TypeMap = serde_json::from_value(result["types"].clone()).unwrap();
+    assert!(
+        types.values().any(|t| t["display"] == "Literal[\"text\"]"),
+        "got {types:?}"
+    );
+}
@@ -11,6 +11,14 @@ pub struct NodeInfo {
     pub node_kind: String,
     pub type_id: Option<u32>,
     pub call_signature: Option<CallSignatureInfo>,
+    #[serde(default)]
+    pub symbol_kind: Option<String>,
+    #[serde(default = "default_true")]
+    pub inferred: bool,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize)]
@@ -20,6 +28,10 @@ pub struct CallSignatureInfo {
     pub return_type_id: Option<u32>,
     #[serde(default)]
     pub type_arguments: Vec<u32>,
+    #[serde(default)]
+    pub matched_overload: Option<usize>,
+    #[serde(default = "default_true")]
+    pub matched: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -28,7 +40,12 @@ pub struct ParameterInfo {
     pub name: String,
     pub type_id: Option<u32>,
     pub kind: String,
+    pub annotated: bool,
     pub has_default: bool,
     #[serde(default)]
     pub default_type_id: Option<u32>,
+    #[serde(default)]
+    pub start: Option<u32>,
+    #[serde(default)]
+    pub end: Option<u32>,
 }